@@ -0,0 +1,114 @@
+use crate::age::format::AgeError;
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+fn polymod(values: impl Iterator<Item = u8>) -> u32 {
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ value as u32;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// hrp expanded for the checksum : high bits, 0, low bits
+fn hrp_expand(hrp: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    hrp.iter()
+        .map(|c| c >> 5)
+        .chain([0])
+        .chain(hrp.iter().map(|c| c & 31))
+}
+
+/// Regroups bits from `from`-bit to `to`-bit groups, padding the last group with
+/// zeros when `pad` is set
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc = 0u32;
+    let mut bits = 0u32;
+    let mut out = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+    let max = (1u32 << to) - 1;
+    for &value in data {
+        acc = (acc << from) | value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            out.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & max != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+/// Bech32 (BIP 173) encoding, in lower case and without the 90-character limit
+pub fn encode(hrp: &str, data: &[u8]) -> String {
+    let hrp = hrp.to_ascii_lowercase();
+    let values = convert_bits(data, 8, 5, true).unwrap();
+    let checksum = polymod(
+        hrp_expand(hrp.as_bytes())
+            .chain(values.iter().copied())
+            .chain([0; 6]),
+    ) ^ 1;
+    let mut out = hrp;
+    out.push('1');
+    out.extend(values.iter().map(|&v| CHARSET[v as usize] as char));
+    out.extend((0..6).map(|i| CHARSET[((checksum >> (5 * (5 - i))) & 31) as usize] as char));
+    out
+}
+
+/// Bech32 decoding : returns the lower-case hrp and the data. Mixed case is
+/// rejected.
+pub fn decode(s: &str) -> Result<(String, Vec<u8>), AgeError> {
+    if s.bytes().any(|c| c.is_ascii_lowercase()) && s.bytes().any(|c| c.is_ascii_uppercase()) {
+        return Err(AgeError::InvalidBech32);
+    }
+    let s = s.to_ascii_lowercase();
+    let separator = s.rfind('1').ok_or(AgeError::InvalidBech32)?;
+    let (hrp, rest) = (&s[..separator], &s.as_bytes()[separator + 1..]);
+    if hrp.is_empty() || rest.len() < 6 || hrp.bytes().any(|c| !(33..=126).contains(&c)) {
+        return Err(AgeError::InvalidBech32);
+    }
+    let values = rest
+        .iter()
+        .map(|c| CHARSET.iter().position(|x| x == c).map(|v| v as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or(AgeError::InvalidBech32)?;
+    if polymod(hrp_expand(hrp.as_bytes()).chain(values.iter().copied())) != 1 {
+        return Err(AgeError::InvalidBech32);
+    }
+    let data =
+        convert_bits(&values[..values.len() - 6], 5, 8, false).ok_or(AgeError::InvalidBech32)?;
+    Ok((hrp.to_string(), data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        // BIP 173 test vectors
+        assert_eq!(decode("A12UEL5L").unwrap(), ("a".to_string(), vec![]));
+        let (hrp, data) = decode("abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw").unwrap();
+        assert_eq!(hrp, "abcdef");
+        assert_eq!(
+            encode(&hrp, &data),
+            "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw"
+        );
+        assert_eq!(decode("A1G7SGD8"), Err(AgeError::InvalidBech32));
+        assert_eq!(decode("a12UEL5L"), Err(AgeError::InvalidBech32));
+
+        let data: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(&encode("age", &data)).unwrap().1, data);
+    }
+}
@@ -0,0 +1,288 @@
+use core::fmt;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use hkdf::Hkdf;
+use hkdf::hmac::{Hmac, Mac};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha2::Sha256;
+
+use crate::age::recipient::{Identity, Recipient};
+use crate::age::stream;
+use crate::hpke::primitives::HpkeError;
+
+/// First line of every age v1 file
+pub const VERSION_LINE: &str = "age-encryption.org/v1";
+pub const FILE_KEY_LEN: usize = 16;
+/// Length of the payload nonce, which precedes the STREAM ciphertext
+pub const NONCE_LEN: usize = 16;
+
+/// Number of base64 characters per line of a stanza body
+const LINE_WIDTH: usize = 64;
+const MAC_LEN: usize = 32;
+
+/// Errors raised while encrypting or decrypting an age file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgeError {
+    /// The header is not a well-formed age v1 header
+    InvalidHeader,
+    /// A stanza of a supported type has the wrong arguments or body
+    InvalidStanza(String),
+    /// The header MAC does not match the file key
+    HeaderMacMismatch,
+    /// No identity unwraps a stanza of the header
+    NoMatchingIdentity,
+    /// The payload is truncated or fails authentication
+    InvalidPayload,
+    /// Encryption needs at least one recipient
+    NoRecipients,
+    /// The string is not valid Bech32
+    InvalidBech32,
+    /// The Bech32 string has the wrong prefix or data length
+    InvalidEncoding,
+    Hpke(HpkeError),
+}
+
+impl fmt::Display for AgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgeError::InvalidHeader => write!(f, "invalid age header"),
+            AgeError::InvalidStanza(tag) => write!(f, "invalid age {tag} stanza"),
+            AgeError::HeaderMacMismatch => write!(f, "age header MAC mismatch"),
+            AgeError::NoMatchingIdentity => write!(f, "no identity matches an age recipient"),
+            AgeError::InvalidPayload => write!(f, "invalid age payload"),
+            AgeError::NoRecipients => write!(f, "no age recipients"),
+            AgeError::InvalidBech32 => write!(f, "invalid Bech32 string"),
+            AgeError::InvalidEncoding => write!(f, "invalid age recipient or identity encoding"),
+            AgeError::Hpke(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for AgeError {}
+
+impl From<HpkeError> for AgeError {
+    fn from(err: HpkeError) -> Self {
+        AgeError::Hpke(err)
+    }
+}
+
+/// Recipient stanza of the header :
+///
+/// -> tag arg...
+/// base64 body, wrapped at 64 columns, the last line being shorter (maybe empty)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stanza {
+    pub tag: String,
+    pub args: Vec<String>,
+    pub body: Vec<u8>,
+}
+
+impl Stanza {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(b"-> ");
+        out.extend_from_slice(self.tag.as_bytes());
+        for arg in &self.args {
+            out.push(b' ');
+            out.extend_from_slice(arg.as_bytes());
+        }
+        out.push(b'\n');
+        let body = STANDARD_NO_PAD.encode(&self.body);
+        for line in body.as_bytes().chunks(LINE_WIDTH) {
+            out.extend_from_slice(line);
+            out.push(b'\n');
+        }
+        if body.len().is_multiple_of(LINE_WIDTH) {
+            out.push(b'\n');
+        }
+    }
+}
+
+/// Arguments are non-empty strings of visible ASCII characters
+fn is_valid_arg(arg: &str) -> bool {
+    !arg.is_empty() && arg.bytes().all(|c| (0x21..=0x7e).contains(&c))
+}
+
+/// Splits off the next line, without its \n
+fn next_line<'a>(input: &mut &'a [u8]) -> Result<&'a [u8], AgeError> {
+    let end = input
+        .iter()
+        .position(|&c| c == b'\n')
+        .ok_or(AgeError::InvalidHeader)?;
+    let line = &input[..end];
+    *input = &input[end + 1..];
+    Ok(line)
+}
+
+/// Parsed header : the stanzas, the MAC and the bytes it covers
+struct Header<'a> {
+    stanzas: Vec<Stanza>,
+    mac: Vec<u8>,
+    mac_input: &'a [u8],
+}
+
+/// Parses the header, returning it and the payload
+fn parse_header(file: &[u8]) -> Result<(Header<'_>, &[u8]), AgeError> {
+    let mut input = file;
+    if next_line(&mut input)? != VERSION_LINE.as_bytes() {
+        return Err(AgeError::InvalidHeader);
+    }
+    let mut stanzas = Vec::new();
+    loop {
+        let line_start = file.len() - input.len();
+        let line = next_line(&mut input)?;
+        if let Some(mac) = line.strip_prefix(b"--- ") {
+            let mac = STANDARD_NO_PAD
+                .decode(mac)
+                .map_err(|_| AgeError::InvalidHeader)?;
+            if mac.len() != MAC_LEN {
+                return Err(AgeError::InvalidHeader);
+            }
+            let header = Header {
+                stanzas,
+                mac,
+                mac_input: &file[..line_start + 3],
+            };
+            return Ok((header, input));
+        }
+        let line = line.strip_prefix(b"-> ").ok_or(AgeError::InvalidHeader)?;
+        let line = core::str::from_utf8(line).map_err(|_| AgeError::InvalidHeader)?;
+        let mut args: Vec<String> = line.split(' ').map(str::to_string).collect();
+        if !args.iter().all(|arg| is_valid_arg(arg)) {
+            return Err(AgeError::InvalidHeader);
+        }
+        let tag = args.remove(0);
+
+        let mut body = Vec::new();
+        loop {
+            let line = next_line(&mut input)?;
+            if line.len() > LINE_WIDTH {
+                return Err(AgeError::InvalidHeader);
+            }
+            body.extend_from_slice(line);
+            if line.len() < LINE_WIDTH {
+                break;
+            }
+        }
+        let body = STANDARD_NO_PAD
+            .decode(&body)
+            .map_err(|_| AgeError::InvalidHeader)?;
+        stanzas.push(Stanza { tag, args, body });
+    }
+}
+
+/// HMAC-SHA256 of the header up to "---", keyed by HKDF-SHA256(file key, "header")
+fn header_mac(file_key: &[u8; FILE_KEY_LEN], mac_input: &[u8]) -> Hmac<Sha256> {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, file_key)
+        .expand(b"header", &mut key)
+        .expect("32 is a valid length for HKDF-SHA256");
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).expect("HMAC accepts any key length");
+    mac.update(mac_input);
+    mac
+}
+
+/// Payload key : HKDF-SHA256(file key, salt = nonce, "payload")
+fn payload_key(file_key: &[u8; FILE_KEY_LEN], nonce: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(nonce), file_key)
+        .expand(b"payload", &mut key)
+        .expect("32 is a valid length for HKDF-SHA256");
+    key
+}
+
+/// Encrypts plaintext to the given recipients as an age v1 file
+/// (age-encryption.org/v1) : a random file key is wrapped in one stanza per
+/// recipient, and the payload is encrypted with STREAM under a key derived from it.
+pub fn encrypt(recipients: &[Recipient], plaintext: &[u8]) -> Result<Vec<u8>, AgeError> {
+    if recipients.is_empty() {
+        return Err(AgeError::NoRecipients);
+    }
+    let mut file_key = [0u8; FILE_KEY_LEN];
+    OsRng.fill_bytes(&mut file_key);
+
+    let mut file = Vec::new();
+    file.extend_from_slice(VERSION_LINE.as_bytes());
+    file.push(b'\n');
+    for recipient in recipients {
+        recipient.wrap(&file_key)?.write(&mut file);
+    }
+    file.extend_from_slice(b"---");
+    let mac = header_mac(&file_key, &file).finalize().into_bytes();
+    file.push(b' ');
+    file.extend_from_slice(STANDARD_NO_PAD.encode(mac).as_bytes());
+    file.push(b'\n');
+
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    file.extend_from_slice(&nonce);
+    file.extend(stream::encrypt(&payload_key(&file_key, &nonce), plaintext));
+    Ok(file)
+}
+
+/// Decrypts an age v1 file with the first identity that unwraps one of its
+/// stanzas, after checking the header MAC
+pub fn decrypt(identities: &[Identity], file: &[u8]) -> Result<Vec<u8>, AgeError> {
+    let (header, payload) = parse_header(file)?;
+    let mut file_key = None;
+    'stanzas: for stanza in &header.stanzas {
+        for identity in identities {
+            if let Some(key) = identity.unwrap(stanza)? {
+                file_key = Some(key);
+                break 'stanzas;
+            }
+        }
+    }
+    let file_key = file_key.ok_or(AgeError::NoMatchingIdentity)?;
+    header_mac(&file_key, header.mac_input)
+        .verify_slice(&header.mac)
+        .map_err(|_| AgeError::HeaderMacMismatch)?;
+
+    if payload.len() < NONCE_LEN {
+        return Err(AgeError::InvalidPayload);
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    stream::decrypt(&payload_key(&file_key, nonce), ciphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for len in [0, 1, 47, 48, 49, 96] {
+            let stanza = Stanza {
+                tag: "test".to_string(),
+                args: vec!["a".to_string(), "b+/".to_string()],
+                body: vec![0x42; len],
+            };
+            let mut file = format!("{VERSION_LINE}\n").into_bytes();
+            stanza.write(&mut file);
+            let last_line = file.split(|&c| c == b'\n').rev().nth(1).unwrap();
+            assert!(last_line.len() < LINE_WIDTH);
+            file.extend_from_slice(b"--- ");
+            file.extend_from_slice(STANDARD_NO_PAD.encode([0u8; MAC_LEN]).as_bytes());
+            file.extend_from_slice(b"\npayload");
+
+            let (header, payload) = parse_header(&file).unwrap();
+            assert_eq!(header.stanzas, vec![stanza]);
+            assert!(header.mac_input.ends_with(b"\n---"));
+            assert_eq!(payload, b"payload");
+        }
+
+        for header in [
+            "age-encryption.org/v2\n--- AAAA\n",
+            "age-encryption.org/v1\n->  x\n\n--- AAAA\n",
+            "age-encryption.org/v1\n-> x\nAA=\n--- AAAA\n",
+            "age-encryption.org/v1\n-> x\n\n--- AAAA\n",
+            "age-encryption.org/v1\n-> x\n",
+        ] {
+            assert!(matches!(
+                parse_header(header.as_bytes()),
+                Err(AgeError::InvalidHeader)
+            ));
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod bech32;
+pub mod format;
+pub mod recipient;
+pub mod stream;
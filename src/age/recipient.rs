@@ -0,0 +1,201 @@
+use core::fmt;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+use crate::age::bech32;
+use crate::age::format::{AgeError, FILE_KEY_LEN, Stanza};
+use crate::hpke::context::Hpke;
+use crate::hpke::kem::HpkeKem;
+use crate::hpke::primitives::{HpkeAead, HpkeKdf};
+use crate::hybrid::xwing::{CIPHERTEXT_LEN, PUBLIC_KEY_LEN};
+use crate::kyber::kem_scheme::ParameterSet;
+use crate::kyber::keys::EncapsulationKey;
+
+/// Tag of the stanzas of the age post-quantum hybrid recipient type
+pub const STANZA_TAG: &str = "mlkem768x25519";
+/// Bech32 prefix of recipients
+pub const RECIPIENT_HRP: &str = "age1pq";
+/// Bech32 prefix of identities, which are written in upper case
+pub const IDENTITY_HRP: &str = "AGE-SECRET-KEY-PQ-";
+
+const HPKE_INFO: &[u8] = b"age-encryption.org/mlkem768x25519";
+
+/// HPKE with MLKEM768-X25519 (X-Wing), HKDF-SHA256 and ChaCha20-Poly1305
+fn hpke() -> Hpke {
+    Hpke::new(
+        HpkeKem::MlKem768X25519,
+        HpkeKdf::HkdfSha256,
+        HpkeAead::ChaCha20Poly1305,
+    )
+}
+
+/// age mlkem768x25519 recipient : an X-Wing public key.
+///
+/// The file key is sealed with HPKE base mode to the public key, with info
+/// "age-encryption.org/mlkem768x25519" and an empty aad, in a stanza
+///
+/// -> mlkem768x25519 base64(enc)
+/// base64(ct)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipient {
+    public_key: Vec<u8>,
+}
+
+impl Recipient {
+    /// Checks the length and the ML-KEM-768 part of the public key
+    pub fn from_bytes(public_key: &[u8]) -> Result<Self, AgeError> {
+        if public_key.len() != PUBLIC_KEY_LEN {
+            return Err(AgeError::InvalidEncoding);
+        }
+        let ek_len = ParameterSet::MlKem768.ek_len();
+        EncapsulationKey::from_bytes(ParameterSet::MlKem768, &public_key[..ek_len])
+            .map_err(|_| AgeError::InvalidEncoding)?;
+        Ok(Recipient {
+            public_key: public_key.to_vec(),
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Parses an age1pq1... string
+    pub fn parse(s: &str) -> Result<Self, AgeError> {
+        let (hrp, data) = bech32::decode(s)?;
+        if hrp != RECIPIENT_HRP || s.bytes().any(|c| c.is_ascii_uppercase()) {
+            return Err(AgeError::InvalidEncoding);
+        }
+        Recipient::from_bytes(&data)
+    }
+
+    /// Stanza wrapping the file key to this recipient
+    pub fn wrap(&self, file_key: &[u8; FILE_KEY_LEN]) -> Result<Stanza, AgeError> {
+        let (enc, ct) = hpke().seal(&self.public_key, HPKE_INFO, b"", file_key, None)?;
+        Ok(Stanza {
+            tag: STANZA_TAG.to_string(),
+            args: vec![STANDARD_NO_PAD.encode(enc)],
+            body: ct,
+        })
+    }
+}
+
+/// Lower-case Bech32 encoding age1pq1...
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bech32::encode(RECIPIENT_HRP, &self.public_key))
+    }
+}
+
+/// age mlkem768x25519 identity : the 32-byte X-Wing private key seed
+#[derive(Clone, PartialEq, Eq)]
+pub struct Identity {
+    seed: [u8; 32],
+}
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Identity").finish_non_exhaustive()
+    }
+}
+
+impl Identity {
+    pub fn generate() -> Self {
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        Identity { seed }
+    }
+
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Identity { seed }
+    }
+
+    pub fn seed(&self) -> &[u8; 32] {
+        &self.seed
+    }
+
+    /// Parses an AGE-SECRET-KEY-PQ-1... string
+    pub fn parse(s: &str) -> Result<Self, AgeError> {
+        let (hrp, data) = bech32::decode(s)?;
+        if hrp != IDENTITY_HRP.to_ascii_lowercase() || s.bytes().any(|c| c.is_ascii_lowercase()) {
+            return Err(AgeError::InvalidEncoding);
+        }
+        let seed = data.try_into().map_err(|_| AgeError::InvalidEncoding)?;
+        Ok(Identity { seed })
+    }
+
+    pub fn to_recipient(&self) -> Recipient {
+        let public_key = HpkeKem::MlKem768X25519
+            .public_key(&self.seed)
+            .expect("every 32-byte seed is an X-Wing private key");
+        Recipient { public_key }
+    }
+
+    /// File key of a stanza : None for stanzas of other types and stanzas sealed to
+    /// other recipients, an error for malformed mlkem768x25519 stanzas
+    pub fn unwrap(&self, stanza: &Stanza) -> Result<Option<[u8; FILE_KEY_LEN]>, AgeError> {
+        if stanza.tag != STANZA_TAG {
+            return Ok(None);
+        }
+        let invalid = || AgeError::InvalidStanza(STANZA_TAG.to_string());
+        let [enc] = stanza.args.as_slice() else {
+            return Err(invalid());
+        };
+        let enc = STANDARD_NO_PAD.decode(enc).map_err(|_| invalid())?;
+        if enc.len() != CIPHERTEXT_LEN || stanza.body.len() != FILE_KEY_LEN + 16 {
+            return Err(invalid());
+        }
+        match hpke().open(&enc, &self.seed, HPKE_INFO, b"", &stanza.body, None) {
+            Ok(file_key) => Ok(Some(file_key.try_into().unwrap())),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// Upper-case Bech32 encoding AGE-SECRET-KEY-PQ-1...
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoded = bech32::encode(IDENTITY_HRP, &self.seed);
+        write!(f, "{}", encoded.to_ascii_uppercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        let identity = Identity::generate();
+        let recipient = identity.to_recipient();
+        assert_eq!(recipient.as_bytes().len(), PUBLIC_KEY_LEN);
+
+        let encoded = identity.to_string();
+        assert!(encoded.starts_with("AGE-SECRET-KEY-PQ-1"));
+        assert_eq!(Identity::parse(&encoded).unwrap(), identity);
+        assert_eq!(
+            Identity::parse(&encoded.to_ascii_lowercase()),
+            Err(AgeError::InvalidEncoding)
+        );
+        let encoded = recipient.to_string();
+        assert!(encoded.starts_with("age1pq1"));
+        assert_eq!(Recipient::parse(&encoded).unwrap(), recipient);
+        assert_eq!(
+            Recipient::parse(&identity.to_string()),
+            Err(AgeError::InvalidEncoding)
+        );
+
+        let file_key = [9u8; FILE_KEY_LEN];
+        let stanza = recipient.wrap(&file_key).unwrap();
+        assert_eq!(identity.unwrap(&stanza).unwrap(), Some(file_key));
+        assert_eq!(Identity::generate().unwrap(&stanza).unwrap(), None);
+        let mut other = stanza.clone();
+        other.tag = "X25519".to_string();
+        assert_eq!(identity.unwrap(&other).unwrap(), None);
+        let mut malformed = stanza.clone();
+        malformed.args.push("extra".to_string());
+        assert!(identity.unwrap(&malformed).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use chacha20poly1305::ChaCha20Poly1305;
+use chacha20poly1305::aead::{Aead, KeyInit};
+
+use crate::age::format::AgeError;
+
+/// Size of a plaintext chunk : 64 KiB
+pub const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+
+/// 11-byte big-endian chunk counter, then 0x01 for the last chunk and 0x00 otherwise
+fn nonce(counter: u128, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..11].copy_from_slice(&counter.to_be_bytes()[5..]);
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// STREAM encryption of the payload with ChaCha20-Poly1305. Only an empty plaintext
+/// gives an empty last chunk.
+pub fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+    let cipher = ChaCha20Poly1305::new(key.into());
+    let chunks: Vec<&[u8]> = if plaintext.is_empty() {
+        vec![&[]]
+    } else {
+        plaintext.chunks(CHUNK_SIZE).collect()
+    };
+    let mut ciphertext = Vec::with_capacity(plaintext.len() + chunks.len() * TAG_LEN);
+    for (counter, chunk) in chunks.iter().enumerate() {
+        let nonce = nonce(counter as u128, counter == chunks.len() - 1);
+        ciphertext.extend(
+            cipher
+                .encrypt(&nonce.into(), *chunk)
+                .expect("chunks are shorter than the AEAD limit"),
+        );
+    }
+    ciphertext
+}
+
+/// STREAM decryption. Rejects truncated payloads, and empty last chunks after a
+/// full one.
+pub fn decrypt(key: &[u8; 32], ciphertext: &[u8]) -> Result<Vec<u8>, AgeError> {
+    let cipher = ChaCha20Poly1305::new(key.into());
+    if ciphertext.is_empty() {
+        return Err(AgeError::InvalidPayload);
+    }
+    let chunks: Vec<&[u8]> = ciphertext.chunks(CHUNK_SIZE + TAG_LEN).collect();
+    let mut plaintext = Vec::with_capacity(ciphertext.len());
+    for (counter, chunk) in chunks.iter().enumerate() {
+        let last = counter == chunks.len() - 1;
+        let decrypted = cipher
+            .decrypt(&nonce(counter as u128, last).into(), *chunk)
+            .map_err(|_| AgeError::InvalidPayload)?;
+        if last && decrypted.is_empty() && counter > 0 {
+            return Err(AgeError::InvalidPayload);
+        }
+        plaintext.extend(decrypted);
+    }
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        let key = [7u8; 32];
+        for len in [0, 1, CHUNK_SIZE, CHUNK_SIZE + 1, 2 * CHUNK_SIZE] {
+            let plaintext = vec![0xa5u8; len];
+            let ciphertext = encrypt(&key, &plaintext);
+            let chunks = len.div_ceil(CHUNK_SIZE).max(1);
+            assert_eq!(ciphertext.len(), len + chunks * TAG_LEN);
+            assert_eq!(decrypt(&key, &ciphertext).unwrap(), plaintext);
+        }
+
+        // Dropping the last chunk leaves a non-final chunk at the end
+        let ciphertext = encrypt(&key, &[1u8; CHUNK_SIZE + 10]);
+        assert_eq!(
+            decrypt(&key, &ciphertext[..CHUNK_SIZE + TAG_LEN]),
+            Err(AgeError::InvalidPayload)
+        );
+        assert_eq!(decrypt(&key, &[]), Err(AgeError::InvalidPayload));
+    }
+}
@@ -0,0 +1,403 @@
+use core::fmt;
+
+use crate::hash::tuple_hash256;
+use crate::kyber::kem_scheme::ParameterSet;
+use crate::kyber::keys::{DecapsulationKey, EncapsulationKey, KeyError};
+
+/// Length of the session key
+pub const SESSION_KEY_LEN: usize = 32;
+
+/// Variants of the key exchange of the Kyber submission (Section 5 of the round 3
+/// specification)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AkeMode {
+    /// Kyber.UAKE : only the responder has a static key, two encapsulations
+    Unilateral,
+    /// Kyber.AKE : both parties have a static key, three encapsulations
+    Mutual,
+}
+
+impl AkeMode {
+    /// Customization string of the session key derivation
+    fn label(&self) -> &'static [u8] {
+        match self {
+            AkeMode::Unilateral => b"Kyber.UAKE",
+            AkeMode::Mutual => b"Kyber.AKE",
+        }
+    }
+}
+
+/// Errors raised by the key exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AkeError {
+    /// The message does not have the length fixed by the parameter set and the mode
+    InvalidMessageLength { expected: usize, found: usize },
+    /// The keys or the messages do not all use the same parameter set
+    ParameterSetMismatch,
+    /// The second flight is not in the mode of the initiator
+    ModeMismatch,
+    /// The ephemeral encapsulation key fails the modulus check
+    Key(KeyError),
+}
+
+impl fmt::Display for AkeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AkeError::InvalidMessageLength { expected, found } => {
+                write!(f, "invalid AKE message length {found}, expected {expected}")
+            }
+            AkeError::ParameterSetMismatch => write!(f, "AKE keys use different parameter sets"),
+            AkeError::ModeMismatch => write!(f, "AKE message in another mode"),
+            AkeError::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for AkeError {}
+
+impl From<KeyError> for AkeError {
+    fn from(err: KeyError) -> Self {
+        AkeError::Key(err)
+    }
+}
+
+fn check_length(expected: usize, found: usize) -> Result<(), AkeError> {
+    if expected != found {
+        return Err(AkeError::InvalidMessageLength { expected, found });
+    }
+    Ok(())
+}
+
+/// First flight, initiator to responder : ephemeral key ek_e || c_B, where
+/// c_B is an encapsulation to the responder static key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitiatorMessage {
+    ek_e: EncapsulationKey,
+    c_b: Vec<u8>,
+}
+
+impl InitiatorMessage {
+    pub fn len(parameter_set: ParameterSet) -> usize {
+        parameter_set.ek_len() + parameter_set.ciphertext_len()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.ek_e.as_bytes(), &self.c_b].concat()
+    }
+
+    /// Parses the message and checks the ephemeral encapsulation key
+    pub fn from_bytes(parameter_set: ParameterSet, bytes: &[u8]) -> Result<Self, AkeError> {
+        check_length(InitiatorMessage::len(parameter_set), bytes.len())?;
+        let (ek_e, c_b) = bytes.split_at(parameter_set.ek_len());
+        Ok(InitiatorMessage {
+            ek_e: EncapsulationKey::from_bytes(parameter_set, ek_e)?,
+            c_b: c_b.to_vec(),
+        })
+    }
+}
+
+/// Second flight, responder to initiator : c_e || c_A, where c_e is an encapsulation
+/// to the ephemeral key and c_A, only in the mutual mode, an encapsulation to the
+/// initiator static key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponderMessage {
+    parameter_set: ParameterSet,
+    mode: AkeMode,
+    c_e: Vec<u8>,
+    c_a: Option<Vec<u8>>,
+}
+
+impl ResponderMessage {
+    pub fn len(parameter_set: ParameterSet, mode: AkeMode) -> usize {
+        match mode {
+            AkeMode::Unilateral => parameter_set.ciphertext_len(),
+            AkeMode::Mutual => 2 * parameter_set.ciphertext_len(),
+        }
+    }
+
+    pub fn parameter_set(&self) -> ParameterSet {
+        self.parameter_set
+    }
+
+    pub fn mode(&self) -> AkeMode {
+        self.mode
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.c_e, self.c_a.as_deref().unwrap_or_default()].concat()
+    }
+
+    pub fn from_bytes(
+        parameter_set: ParameterSet,
+        mode: AkeMode,
+        bytes: &[u8],
+    ) -> Result<Self, AkeError> {
+        check_length(ResponderMessage::len(parameter_set, mode), bytes.len())?;
+        let (c_e, c_a) = bytes.split_at(parameter_set.ciphertext_len());
+        Ok(ResponderMessage {
+            parameter_set,
+            mode,
+            c_e: c_e.to_vec(),
+            c_a: (mode == AkeMode::Mutual).then(|| c_a.to_vec()),
+        })
+    }
+}
+
+/// Session key : TupleHash256 over the static keys, both flights and the KEM
+/// shared secrets, customized with the mode.
+///
+/// A tampered ciphertext does not raise an error (implicit rejection), it only
+/// leads to different session keys : key confirmation is left to the protocol.
+fn session_key(
+    mode: AkeMode,
+    ek_a: Option<&EncapsulationKey>,
+    ek_b: &EncapsulationKey,
+    first: &InitiatorMessage,
+    second: &ResponderMessage,
+    secrets: &[&[u8]],
+) -> [u8; SESSION_KEY_LEN] {
+    let first = first.to_bytes();
+    let second = second.to_bytes();
+    let mut transcript: Vec<&[u8]> = vec![
+        ek_b.parameter_set().name().as_bytes(),
+        ek_a.map_or(&[], EncapsulationKey::as_bytes),
+        ek_b.as_bytes(),
+        &first,
+        &second,
+    ];
+    transcript.extend_from_slice(secrets);
+    tuple_hash256(&transcript, SESSION_KEY_LEN, mode.label())
+        .try_into()
+        .unwrap()
+}
+
+/// Initiator state between the two flights
+pub struct Initiator {
+    mode: AkeMode,
+    static_key: Option<DecapsulationKey>,
+    responder: EncapsulationKey,
+    dk_e: DecapsulationKey,
+    k_b: Vec<u8>,
+    message: InitiatorMessage,
+}
+
+impl Initiator {
+    /// Kyber.UAKE : the initiator stays anonymous and authenticates the responder
+    pub fn unilateral(responder: &EncapsulationKey) -> Self {
+        Initiator::start(AkeMode::Unilateral, None, responder)
+    }
+
+    /// Kyber.AKE : both parties are authenticated by their static keys
+    pub fn mutual(
+        static_key: DecapsulationKey,
+        responder: &EncapsulationKey,
+    ) -> Result<Self, AkeError> {
+        if static_key.parameter_set() != responder.parameter_set() {
+            return Err(AkeError::ParameterSetMismatch);
+        }
+        Ok(Initiator::start(
+            AkeMode::Mutual,
+            Some(static_key),
+            responder,
+        ))
+    }
+
+    fn start(
+        mode: AkeMode,
+        static_key: Option<DecapsulationKey>,
+        responder: &EncapsulationKey,
+    ) -> Self {
+        let dk_e = DecapsulationKey::generate(responder.parameter_set());
+        let (k_b, c_b) = responder.encaps();
+        let message = InitiatorMessage {
+            ek_e: dk_e.encapsulation_key(),
+            c_b,
+        };
+        Initiator {
+            mode,
+            static_key,
+            responder: responder.clone(),
+            dk_e,
+            k_b,
+            message,
+        }
+    }
+
+    pub fn mode(&self) -> AkeMode {
+        self.mode
+    }
+
+    /// First flight, to send to the responder
+    pub fn message(&self) -> &InitiatorMessage {
+        &self.message
+    }
+
+    /// Processes the second flight and returns the session key
+    pub fn finish(self, message: &ResponderMessage) -> Result<[u8; SESSION_KEY_LEN], AkeError> {
+        // from_bytes checked the lengths against the parameter set and the mode of
+        // the message, which must be those of this exchange
+        if message.parameter_set != self.responder.parameter_set() {
+            return Err(AkeError::ParameterSetMismatch);
+        }
+        if message.mode != self.mode {
+            return Err(AkeError::ModeMismatch);
+        }
+        let k_e = self.dk_e.decaps(&message.c_e);
+        let key = match (&self.static_key, &message.c_a) {
+            (Some(static_key), Some(c_a)) => {
+                let k_a = static_key.decaps(c_a);
+                session_key(
+                    self.mode,
+                    Some(&static_key.encapsulation_key()),
+                    &self.responder,
+                    &self.message,
+                    message,
+                    &[&k_a, &self.k_b, &k_e],
+                )
+            }
+            _ => session_key(
+                self.mode,
+                None,
+                &self.responder,
+                &self.message,
+                message,
+                &[&self.k_b, &k_e],
+            ),
+        };
+        Ok(key)
+    }
+}
+
+/// Responder, holding its static key and, in the mutual mode, the initiator static key
+pub struct Responder {
+    mode: AkeMode,
+    static_key: DecapsulationKey,
+    initiator: Option<EncapsulationKey>,
+}
+
+impl Responder {
+    /// Kyber.UAKE : any initiator is accepted
+    pub fn unilateral(static_key: DecapsulationKey) -> Self {
+        Responder {
+            mode: AkeMode::Unilateral,
+            static_key,
+            initiator: None,
+        }
+    }
+
+    /// Kyber.AKE : the initiator must hold the decapsulation key of `initiator`
+    pub fn mutual(
+        static_key: DecapsulationKey,
+        initiator: &EncapsulationKey,
+    ) -> Result<Self, AkeError> {
+        if static_key.parameter_set() != initiator.parameter_set() {
+            return Err(AkeError::ParameterSetMismatch);
+        }
+        Ok(Responder {
+            mode: AkeMode::Mutual,
+            static_key,
+            initiator: Some(initiator.clone()),
+        })
+    }
+
+    pub fn mode(&self) -> AkeMode {
+        self.mode
+    }
+
+    /// Processes the first flight : returns the second flight and the session key
+    pub fn respond(
+        &self,
+        message: &InitiatorMessage,
+    ) -> Result<(ResponderMessage, [u8; SESSION_KEY_LEN]), AkeError> {
+        let parameter_set = self.static_key.parameter_set();
+        if message.ek_e.parameter_set() != parameter_set {
+            return Err(AkeError::ParameterSetMismatch);
+        }
+        let ek_b = self.static_key.encapsulation_key();
+        let k_b = self.static_key.decaps(&message.c_b);
+        let (k_e, c_e) = message.ek_e.encaps();
+        let (reply, key) = match &self.initiator {
+            Some(ek_a) => {
+                let (k_a, c_a) = ek_a.encaps();
+                let reply = ResponderMessage {
+                    parameter_set,
+                    mode: self.mode,
+                    c_e,
+                    c_a: Some(c_a),
+                };
+                let key = session_key(
+                    self.mode,
+                    Some(ek_a),
+                    &ek_b,
+                    message,
+                    &reply,
+                    &[&k_a, &k_b, &k_e],
+                );
+                (reply, key)
+            }
+            None => {
+                let reply = ResponderMessage {
+                    parameter_set,
+                    mode: self.mode,
+                    c_e,
+                    c_a: None,
+                };
+                let key = session_key(self.mode, None, &ek_b, message, &reply, &[&k_b, &k_e]);
+                (reply, key)
+            }
+        };
+        Ok((reply, key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        let set = ParameterSet::MlKem768;
+        let alice = DecapsulationKey::generate(set);
+        let bob = DecapsulationKey::generate(set);
+
+        let initiator = Initiator::mutual(alice.clone(), &bob.encapsulation_key()).unwrap();
+        let responder = Responder::mutual(bob.clone(), &alice.encapsulation_key()).unwrap();
+        let (reply, responder_key) = responder.respond(initiator.message()).unwrap();
+        assert_eq!(
+            reply.to_bytes().len(),
+            ResponderMessage::len(set, AkeMode::Mutual)
+        );
+        assert_eq!(initiator.finish(&reply).unwrap(), responder_key);
+
+        let initiator = Initiator::unilateral(&bob.encapsulation_key());
+        let (reply, responder_key) = Responder::unilateral(bob.clone())
+            .respond(initiator.message())
+            .unwrap();
+        assert_eq!(reply.to_bytes().len(), set.ciphertext_len());
+        assert_eq!(initiator.finish(&reply).unwrap(), responder_key);
+
+        let other = DecapsulationKey::generate(ParameterSet::MlKem512);
+        assert_eq!(
+            Initiator::mutual(other, &bob.encapsulation_key()).err(),
+            Some(AkeError::ParameterSetMismatch)
+        );
+
+        // A reply of another parameter set or mode is rejected, not decapsulated
+        let initiator = Initiator::unilateral(&bob.encapsulation_key());
+        let small = DecapsulationKey::generate(ParameterSet::MlKem512);
+        let foreign = Initiator::unilateral(&small.encapsulation_key());
+        let (reply, _) = Responder::unilateral(small)
+            .respond(foreign.message())
+            .unwrap();
+        assert_eq!(
+            initiator.finish(&reply),
+            Err(AkeError::ParameterSetMismatch)
+        );
+        let initiator = Initiator::unilateral(&bob.encapsulation_key());
+        let (reply, _) = Responder::mutual(bob.clone(), &alice.encapsulation_key())
+            .unwrap()
+            .respond(initiator.message())
+            .unwrap();
+        assert_eq!(initiator.finish(&reply), Err(AkeError::ModeMismatch));
+    }
+}
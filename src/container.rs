@@ -0,0 +1,470 @@
+use core::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::hash::h;
+use crate::kyber::kem_scheme::ParameterSet;
+use crate::kyber::keys::{DecapsulationKey, EncapsulationKey, KeyError};
+
+pub const MAGIC: [u8; 4] = *b"KYRS";
+pub const VERSION: u8 = 1;
+
+const FLAG_KEY_ID: u8 = 0x01;
+const CHECKSUM_LEN: usize = 4;
+
+/// Kind of object held by a container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectType {
+    /// Encapsulation key ek
+    PublicKey,
+    /// Expanded decapsulation key dk
+    ExpandedPrivateKey,
+    /// Seed d || z of a decapsulation key
+    SeedPrivateKey,
+    /// Ciphertext c
+    Ciphertext,
+}
+
+impl ObjectType {
+    fn id(&self) -> u8 {
+        match self {
+            ObjectType::PublicKey => 1,
+            ObjectType::ExpandedPrivateKey => 2,
+            ObjectType::SeedPrivateKey => 3,
+            ObjectType::Ciphertext => 4,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(ObjectType::PublicKey),
+            2 => Some(ObjectType::ExpandedPrivateKey),
+            3 => Some(ObjectType::SeedPrivateKey),
+            4 => Some(ObjectType::Ciphertext),
+            _ => None,
+        }
+    }
+
+    /// Length of the payload for the given parameter set
+    fn payload_len(&self, parameter_set: ParameterSet) -> usize {
+        match self {
+            ObjectType::PublicKey => parameter_set.ek_len(),
+            ObjectType::ExpandedPrivateKey => parameter_set.dk_len(),
+            ObjectType::SeedPrivateKey => 64,
+            ObjectType::Ciphertext => parameter_set.ciphertext_len(),
+        }
+    }
+}
+
+/// Errors raised while building or parsing a container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContainerError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnknownAlgorithm(u16),
+    UnknownObjectType(u8),
+    /// Flags other than the key ID flag are set
+    ReservedFlags(u8),
+    /// The key ID is empty or longer than 255 bytes
+    InvalidKeyId,
+    /// The payload length does not match the algorithm and object type
+    InvalidLength {
+        expected: usize,
+        found: usize,
+    },
+    ChecksumMismatch,
+    TrailingData,
+    /// The container holds another kind of object than the one requested
+    UnexpectedObjectType(ObjectType),
+    Key(KeyError),
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::Truncated => write!(f, "truncated container"),
+            ContainerError::BadMagic => write!(f, "not a key container"),
+            ContainerError::UnsupportedVersion(v) => write!(f, "unsupported container version {v}"),
+            ContainerError::UnknownAlgorithm(id) => write!(f, "unknown algorithm {id}"),
+            ContainerError::UnknownObjectType(id) => write!(f, "unknown object type {id}"),
+            ContainerError::ReservedFlags(flags) => write!(f, "reserved flags set: 0x{flags:02x}"),
+            ContainerError::InvalidKeyId => write!(f, "invalid key ID length"),
+            ContainerError::InvalidLength { expected, found } => {
+                write!(f, "invalid payload length {found}, expected {expected}")
+            }
+            ContainerError::ChecksumMismatch => write!(f, "container checksum mismatch"),
+            ContainerError::TrailingData => write!(f, "trailing data after container"),
+            ContainerError::UnexpectedObjectType(found) => {
+                write!(f, "unexpected object type {found:?}")
+            }
+            ContainerError::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {}
+
+impl From<KeyError> for ContainerError {
+    fn from(err: KeyError) -> Self {
+        ContainerError::Key(err)
+    }
+}
+
+/// Self-describing binary container : a key or ciphertext together with its
+/// algorithm, type and metadata
+#[derive(Clone, PartialEq, Eq)]
+pub struct Container {
+    parameter_set: ParameterSet,
+    object_type: ObjectType,
+    created: u64,
+    key_id: Option<Vec<u8>>,
+    payload: Vec<u8>,
+}
+
+impl Container {
+    /// Wraps a raw payload, stamped with the current time
+    pub fn new(
+        parameter_set: ParameterSet,
+        object_type: ObjectType,
+        payload: &[u8],
+    ) -> Result<Self, ContainerError> {
+        let expected = object_type.payload_len(parameter_set);
+        if payload.len() != expected {
+            return Err(ContainerError::InvalidLength {
+                expected,
+                found: payload.len(),
+            });
+        }
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Ok(Container {
+            parameter_set,
+            object_type,
+            created,
+            key_id: None,
+            payload: payload.to_vec(),
+        })
+    }
+
+    pub fn from_encapsulation_key(ek: &EncapsulationKey) -> Self {
+        Container::new(ek.parameter_set(), ObjectType::PublicKey, ek.as_bytes()).unwrap()
+    }
+
+    /// Stores the seed when it is known, the expanded key otherwise
+    pub fn from_decapsulation_key(dk: &DecapsulationKey) -> Self {
+        match dk.seed() {
+            Some(seed) => Container::new(dk.parameter_set(), ObjectType::SeedPrivateKey, seed),
+            None => Container::new(
+                dk.parameter_set(),
+                ObjectType::ExpandedPrivateKey,
+                dk.expanded(),
+            ),
+        }
+        .unwrap()
+    }
+
+    pub fn from_ciphertext(parameter_set: ParameterSet, c: &[u8]) -> Result<Self, ContainerError> {
+        Container::new(parameter_set, ObjectType::Ciphertext, c)
+    }
+
+    pub fn with_key_id(mut self, key_id: &[u8]) -> Result<Self, ContainerError> {
+        if key_id.is_empty() || key_id.len() > 255 {
+            return Err(ContainerError::InvalidKeyId);
+        }
+        self.key_id = Some(key_id.to_vec());
+        Ok(self)
+    }
+
+    pub fn with_created(mut self, created: u64) -> Self {
+        self.created = created;
+        self
+    }
+
+    pub fn parameter_set(&self) -> ParameterSet {
+        self.parameter_set
+    }
+
+    pub fn object_type(&self) -> ObjectType {
+        self.object_type
+    }
+
+    /// Creation time in seconds since the Unix epoch
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    pub fn key_id(&self) -> Option<&[u8]> {
+        self.key_id.as_deref()
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Encoding of version 1, integers in big-endian order : magic "KYRS" (4) ||
+    /// version 1 (1) || algorithm (2, `ParameterSet::id`) || object type (1) : 1 ek,
+    /// 2 expanded dk, 3 seed d || z, 4 ciphertext || flags (1) : bit 0 set when a
+    /// key ID is present || creation time in seconds since the Unix epoch (8) ||
+    /// key ID length n in [1, 255] and key ID (1+n), only if flagged || payload
+    /// length m and payload (4+m) || checksum (4) : first 4 bytes of SHA3-256 of all
+    /// the above.
+    ///
+    /// The checksum only detects accidental damage, it does not authenticate the
+    /// content.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.payload.len() + 300);
+        out.extend_from_slice(&MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&u16::from(self.parameter_set.id()).to_be_bytes());
+        out.push(self.object_type.id());
+        out.push(if self.key_id.is_some() {
+            FLAG_KEY_ID
+        } else {
+            0
+        });
+        out.extend_from_slice(&self.created.to_be_bytes());
+        if let Some(key_id) = &self.key_id {
+            out.push(key_id.len() as u8);
+            out.extend_from_slice(key_id);
+        }
+        out.extend_from_slice(&(self.payload.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.payload);
+        let checksum = h(&out);
+        out.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        out
+    }
+
+    /// Strict parsing : every field must hold a known value, the payload length must
+    /// match the algorithm and object type, and no byte may follow the checksum. New
+    /// algorithms get new identifiers, so a container is never ambiguous.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ContainerError> {
+        let mut input = Input(bytes);
+        if input.take(4)? != MAGIC {
+            return Err(ContainerError::BadMagic);
+        }
+        let version = input.take(1)?[0];
+        if version != VERSION {
+            return Err(ContainerError::UnsupportedVersion(version));
+        }
+        let algorithm = u16::from_be_bytes(input.take(2)?.try_into().unwrap());
+        let parameter_set = u8::try_from(algorithm)
+            .ok()
+            .and_then(ParameterSet::from_id)
+            .ok_or(ContainerError::UnknownAlgorithm(algorithm))?;
+        let type_id = input.take(1)?[0];
+        let object_type =
+            ObjectType::from_id(type_id).ok_or(ContainerError::UnknownObjectType(type_id))?;
+        let flags = input.take(1)?[0];
+        if flags & !FLAG_KEY_ID != 0 {
+            return Err(ContainerError::ReservedFlags(flags));
+        }
+        let created = u64::from_be_bytes(input.take(8)?.try_into().unwrap());
+        let key_id = if flags & FLAG_KEY_ID != 0 {
+            let len = input.take(1)?[0] as usize;
+            if len == 0 {
+                return Err(ContainerError::InvalidKeyId);
+            }
+            Some(input.take(len)?.to_vec())
+        } else {
+            None
+        };
+
+        let found = u32::from_be_bytes(input.take(4)?.try_into().unwrap()) as usize;
+        let expected = object_type.payload_len(parameter_set);
+        if found != expected {
+            return Err(ContainerError::InvalidLength { expected, found });
+        }
+        let payload = input.take(found)?.to_vec();
+
+        let body_len = bytes.len() - input.0.len();
+        let checksum = input.take(CHECKSUM_LEN)?;
+        if !input.0.is_empty() {
+            return Err(ContainerError::TrailingData);
+        }
+        if h(&bytes[..body_len])[..CHECKSUM_LEN] != *checksum {
+            return Err(ContainerError::ChecksumMismatch);
+        }
+
+        Ok(Container {
+            parameter_set,
+            object_type,
+            created,
+            key_id,
+            payload,
+        })
+    }
+
+    pub fn to_encapsulation_key(&self) -> Result<EncapsulationKey, ContainerError> {
+        match self.object_type {
+            ObjectType::PublicKey => Ok(EncapsulationKey::from_bytes(
+                self.parameter_set,
+                &self.payload,
+            )?),
+            found => Err(ContainerError::UnexpectedObjectType(found)),
+        }
+    }
+
+    pub fn to_decapsulation_key(&self) -> Result<DecapsulationKey, ContainerError> {
+        match self.object_type {
+            ObjectType::SeedPrivateKey => Ok(DecapsulationKey::from_seed(
+                self.parameter_set,
+                self.payload.as_slice().try_into().unwrap(),
+            )),
+            ObjectType::ExpandedPrivateKey => Ok(DecapsulationKey::from_expanded(
+                self.parameter_set,
+                &self.payload,
+            )?),
+            found => Err(ContainerError::UnexpectedObjectType(found)),
+        }
+    }
+}
+
+impl fmt::Debug for Container {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Container")
+            .field("parameter_set", &self.parameter_set)
+            .field("object_type", &self.object_type)
+            .field("created", &self.created)
+            .field("key_id", &self.key_id.as_ref().map(hex::encode))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Remaining input of the parser
+struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ContainerError> {
+        if self.0.len() < n {
+            return Err(ContainerError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for set in ParameterSet::ALL {
+            let dk = DecapsulationKey::generate(set);
+            let ek = dk.encapsulation_key();
+            let (_, c) = ek.encaps();
+            let expanded = DecapsulationKey::from_expanded(set, dk.expanded()).unwrap();
+
+            for container in [
+                Container::from_encapsulation_key(&ek),
+                Container::from_decapsulation_key(&dk),
+                Container::from_decapsulation_key(&expanded),
+                Container::from_ciphertext(set, &c).unwrap(),
+                Container::from_encapsulation_key(&ek)
+                    .with_key_id(b"service-a/2026")
+                    .unwrap(),
+            ] {
+                let bytes = container.to_bytes();
+                assert_eq!(Container::from_bytes(&bytes).unwrap(), container);
+            }
+
+            let restored =
+                Container::from_bytes(&Container::from_decapsulation_key(&dk).to_bytes())
+                    .unwrap()
+                    .to_decapsulation_key()
+                    .unwrap();
+            assert_eq!(restored.seed(), dk.seed());
+            assert_eq!(
+                Container::from_encapsulation_key(&ek)
+                    .to_encapsulation_key()
+                    .unwrap(),
+                ek
+            );
+        }
+
+        // fixed layout
+        let container =
+            Container::new(ParameterSet::MlKem512, ObjectType::SeedPrivateKey, &[7; 64])
+                .unwrap()
+                .with_created(0x0102030405060708)
+                .with_key_id(b"id")
+                .unwrap();
+        let bytes = container.to_bytes();
+        assert_eq!(
+            bytes[..24],
+            [
+                b'K', b'Y', b'R', b'S', 1, 0, 1, 3, 1, 1, 2, 3, 4, 5, 6, 7, 8, 2, b'i', b'd', 0, 0,
+                0, 64
+            ]
+        );
+        assert_eq!(bytes.len(), 24 + 64 + 4);
+
+        let mut bad = bytes.clone();
+        bad[30] ^= 1;
+        assert_eq!(
+            Container::from_bytes(&bad),
+            Err(ContainerError::ChecksumMismatch)
+        );
+        let mut bad = bytes.clone();
+        bad[4] = 2;
+        assert_eq!(
+            Container::from_bytes(&bad),
+            Err(ContainerError::UnsupportedVersion(2))
+        );
+        let mut bad = bytes.clone();
+        bad[6] = 9;
+        assert_eq!(
+            Container::from_bytes(&bad),
+            Err(ContainerError::UnknownAlgorithm(9))
+        );
+        let mut bad = bytes.clone();
+        bad[5] = 1;
+        assert_eq!(
+            Container::from_bytes(&bad),
+            Err(ContainerError::UnknownAlgorithm(0x0100 + bytes[6] as u16))
+        );
+        let mut bad = bytes.clone();
+        bad[7] = 0;
+        assert_eq!(
+            Container::from_bytes(&bad),
+            Err(ContainerError::UnknownObjectType(0))
+        );
+        let mut bad = bytes.clone();
+        bad[8] = 3;
+        assert_eq!(
+            Container::from_bytes(&bad),
+            Err(ContainerError::ReservedFlags(3))
+        );
+        let mut bad = bytes.clone();
+        bad[23] = 65;
+        assert_eq!(
+            Container::from_bytes(&bad),
+            Err(ContainerError::InvalidLength {
+                expected: 64,
+                found: 65
+            })
+        );
+        let mut bad = bytes.clone();
+        bad.push(0);
+        assert_eq!(
+            Container::from_bytes(&bad),
+            Err(ContainerError::TrailingData)
+        );
+        assert_eq!(
+            Container::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ContainerError::Truncated)
+        );
+        assert_eq!(
+            Container::from_bytes(b"KYBR"),
+            Err(ContainerError::BadMagic)
+        );
+        assert_eq!(
+            container.to_encapsulation_key(),
+            Err(ContainerError::UnexpectedObjectType(
+                ObjectType::SeedPrivateKey
+            ))
+        );
+    }
+}
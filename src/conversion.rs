@@ -55,6 +55,108 @@ pub fn bytes_to_bits(bytes: &[u8]) -> Vec<u8> {
 /// Input : integer array F in Z_m^N, where m = 2^d if d < 12, and m = Q if d = 12
 /// Output : B in B^(32*d)
 pub fn byte_encode(f: &[i64], d: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; f.len() * d / 8];
+    byte_encode_into(f, d, &mut bytes);
+    bytes
+}
+
+/// Algorithm 6 (FIPS 203) : ByteDecode_d(B)
+/// Decodes a byte array into an array of d-bit integers for 1 <= d <= 12
+///
+/// Input : B in B^(32*d)
+/// Output : integer array F in Z_m^N, where m = 2^d if d < 12, and m = Q if d = 12
+pub fn byte_decode(bytes: &[u8], d: usize, q: i64) -> Vec<i64> {
+    let mut f = vec![0i64; bytes.len() * 8 / d];
+    byte_decode_into(bytes, d, q, &mut f);
+    f
+}
+
+/// ByteEncode_d(F) written into a caller-provided buffer of f.len() * d / 8 bytes.
+///
+/// Groups of 8 coefficients are packed into a single word and written as d bytes,
+/// with one specialised routine per value of d. f.len() must be a multiple of 8.
+pub fn byte_encode_into(f: &[i64], d: usize, out: &mut [u8]) {
+    if !f.len().is_multiple_of(8) || out.len() != f.len() * d / 8 {
+        panic!("Unauthorized length for F or for the output buffer")
+    }
+    match d {
+        1 => pack::<1>(f, out),
+        2 => pack::<2>(f, out),
+        3 => pack::<3>(f, out),
+        4 => pack::<4>(f, out),
+        5 => pack::<5>(f, out),
+        6 => pack::<6>(f, out),
+        7 => pack::<7>(f, out),
+        8 => pack::<8>(f, out),
+        9 => pack::<9>(f, out),
+        10 => pack::<10>(f, out),
+        11 => pack::<11>(f, out),
+        12 => pack::<12>(f, out),
+        _ => panic!("Unauthorized value for d: {}", d),
+    }
+}
+
+/// ByteDecode_d(B) written into a caller-provided buffer of bytes.len() * 8 / d integers.
+///
+/// Each group of d bytes is read as a single word and split into 8 coefficients,
+/// with one specialised routine per value of d. bytes.len() must be a multiple of d.
+pub fn byte_decode_into(bytes: &[u8], d: usize, q: i64, out: &mut [i64]) {
+    if !bytes.len().is_multiple_of(d) || out.len() != bytes.len() * 8 / d {
+        panic!("Unauthorized length for B or for the output buffer")
+    }
+    match d {
+        1 => unpack::<1>(bytes, out),
+        2 => unpack::<2>(bytes, out),
+        3 => unpack::<3>(bytes, out),
+        4 => unpack::<4>(bytes, out),
+        5 => unpack::<5>(bytes, out),
+        6 => unpack::<6>(bytes, out),
+        7 => unpack::<7>(bytes, out),
+        8 => unpack::<8>(bytes, out),
+        9 => unpack::<9>(bytes, out),
+        10 => unpack::<10>(bytes, out),
+        11 => unpack::<11>(bytes, out),
+        12 => {
+            unpack::<12>(bytes, out);
+            // ByteDecode_12 reduces modulo Q : the values are below 4096 < 2Q
+            for x in out.iter_mut() {
+                let y = *x - q;
+                *x = y + ((y >> 63) & q);
+            }
+        }
+        _ => panic!("Unauthorized value for d: {}", d),
+    }
+}
+
+fn pack<const D: usize>(f: &[i64], out: &mut [u8]) {
+    let mask = (1u128 << D) - 1;
+    for (group, chunk) in f.chunks_exact(8).zip(out.chunks_exact_mut(D)) {
+        let mut word = 0u128;
+        for (i, &x) in group.iter().enumerate() {
+            word |= (x as u128 & mask) << (D * i);
+        }
+        chunk.copy_from_slice(&word.to_le_bytes()[..D]);
+    }
+}
+
+fn unpack<const D: usize>(bytes: &[u8], out: &mut [i64]) {
+    let mask = (1u128 << D) - 1;
+    for (chunk, group) in bytes.chunks_exact(D).zip(out.chunks_exact_mut(8)) {
+        let mut word_bytes = [0u8; 16];
+        word_bytes[..D].copy_from_slice(chunk);
+        let word = u128::from_le_bytes(word_bytes);
+        for (i, x) in group.iter_mut().enumerate() {
+            *x = ((word >> (D * i)) & mask) as i64;
+        }
+    }
+}
+
+/// Algorithm 5 (FIPS 203) : ByteEncode_d(F), bit by bit reference version
+/// Encodes an array of d-bit integers into a byte array for 1 <= d <= 12
+///
+/// Input : integer array F in Z_m^N, where m = 2^d if d < 12, and m = Q if d = 12
+/// Output : B in B^(32*d)
+pub fn byte_encode_reference(f: &[i64], d: usize) -> Vec<u8> {
     let mut bits = vec![0u8; f.len() * d];
     for (i, coeff) in f.iter().enumerate() {
         for j in 0..d {
@@ -64,12 +166,12 @@ pub fn byte_encode(f: &[i64], d: usize) -> Vec<u8> {
     bits_to_bytes(&bits)
 }
 
-/// Algorithm 6 (FIPS 203) : ByteEncode_d(F)
+/// Algorithm 6 (FIPS 203) : ByteDecode_d(B), bit by bit reference version
 /// Decodes a byte array into an array of d-bit integers for 1 <= d <= 12
 ///
 /// Input : B in B^(32*d)
 /// Output : integer array F in Z_m^N, where m = 2^d if d < 12, and m = Q if d = 12
-pub fn byte_decode(bytes: &[u8], d: usize, q: i64) -> Vec<i64> {
+pub fn byte_decode_reference(bytes: &[u8], d: usize, q: i64) -> Vec<i64> {
     let m = match d {
         12 => q,
         _ => 1i64 << d,
@@ -92,6 +194,7 @@ mod tests {
     use super::*;
     use crate::constants::{KyberParams, PolyParams};
     use crate::polynomial::PolynomialNTT;
+    use rand::{Rng, RngCore};
 
     #[test]
     fn basics() {
@@ -119,9 +222,34 @@ mod tests {
         ];
         assert_eq!(bytes_to_bits(&bits_to_bytes(&b)), b);
 
-        let f =
-            PolynomialNTT::<KyberParams>::sample_ntt(b"Salut de la part de moi meme le ka").coeffs;
+        let f = PolynomialNTT::<KyberParams>::sample_ntt(b"Salut de la part de moi meme le ka")
+            .values();
         let f_rev = byte_decode(&byte_encode(&f, 12), 12, q);
         assert_eq!(&f, &f_rev);
     }
+
+    #[test]
+    fn word_level_matches_reference() {
+        let q = KyberParams::Q;
+        let mut rng = rand::thread_rng();
+        for d in 1..=12 {
+            let m = if d == 12 { q } else { 1i64 << d };
+            for _ in 0..50 {
+                let f: Vec<i64> = (0..256).map(|_| rng.gen_range(0..m)).collect();
+                let encoded = byte_encode(&f, d);
+                assert_eq!(encoded, byte_encode_reference(&f, d));
+                assert_eq!(byte_decode(&encoded, d, q), f);
+
+                let mut bytes = vec![0u8; 32 * d];
+                rng.fill_bytes(&mut bytes);
+                let decoded = byte_decode(&bytes, d, q);
+                assert_eq!(decoded, byte_decode_reference(&bytes, d, q));
+                assert!(decoded.iter().all(|&x| (0..m).contains(&x)));
+
+                let mut out = vec![0u8; 32 * d];
+                byte_encode_into(&f, d, &mut out);
+                assert_eq!(out, encoded);
+            }
+        }
+    }
 }
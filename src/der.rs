@@ -0,0 +1,250 @@
+use core::fmt;
+
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_SEQUENCE: u8 = 0x30;
+
+/// Errors raised while parsing a DER structure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DerError {
+    /// The input ends in the middle of an element
+    Truncated,
+    /// A length is not encoded in its minimal form, or is too large
+    InvalidLength,
+    /// An element has an unexpected tag
+    UnexpectedTag { expected: u8, found: u8 },
+    /// Bytes remain after the end of the structure
+    TrailingData,
+    /// The content of an element is malformed
+    InvalidValue,
+}
+
+impl fmt::Display for DerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DerError::Truncated => write!(f, "truncated DER input"),
+            DerError::InvalidLength => write!(f, "invalid DER length"),
+            DerError::UnexpectedTag { expected, found } => {
+                write!(
+                    f,
+                    "unexpected DER tag 0x{found:02x}, expected 0x{expected:02x}"
+                )
+            }
+            DerError::TrailingData => write!(f, "trailing data after DER structure"),
+            DerError::InvalidValue => write!(f, "invalid DER value"),
+        }
+    }
+}
+
+impl std::error::Error for DerError {}
+
+/// Appends the element tag || length || content to out, with a definite minimal length
+pub fn write_tlv(out: &mut Vec<u8>, tag: u8, content: &[u8]) {
+    out.push(tag);
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+}
+
+/// Returns the encoding of a single element
+pub fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len() + 6);
+    write_tlv(&mut out, tag, content);
+    out
+}
+
+/// Appends a non-negative INTEGER in its minimal encoding
+pub fn write_uint(out: &mut Vec<u8>, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count().min(7);
+    let mut content = Vec::with_capacity(9);
+    if bytes[skip] >= 0x80 {
+        content.push(0);
+    }
+    content.extend_from_slice(&bytes[skip..]);
+    write_tlv(out, TAG_INTEGER, &content);
+}
+
+/// Cursor over a sequence of DER elements
+pub struct Reader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Reader { input }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    /// Tag of the next element, if any
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.input.first().copied()
+    }
+
+    /// Reads the next element, checks its tag and returns its content
+    pub fn read(&mut self, tag: u8) -> Result<&'a [u8], DerError> {
+        let (found, content) = self.read_any()?;
+        if found != tag {
+            return Err(DerError::UnexpectedTag {
+                expected: tag,
+                found,
+            });
+        }
+        Ok(content)
+    }
+
+    /// Reads the next element, whatever its tag
+    pub fn read_any(&mut self) -> Result<(u8, &'a [u8]), DerError> {
+        let (&tag, rest) = self.input.split_first().ok_or(DerError::Truncated)?;
+        let (&first, mut rest) = rest.split_first().ok_or(DerError::Truncated)?;
+
+        let len = if first < 0x80 {
+            first as usize
+        } else {
+            let n = (first & 0x7f) as usize;
+            if n == 0 || n > size_of::<usize>() {
+                return Err(DerError::InvalidLength);
+            }
+            if rest.len() < n {
+                return Err(DerError::Truncated);
+            }
+            let (len_bytes, tail) = rest.split_at(n);
+            rest = tail;
+            if len_bytes[0] == 0 {
+                return Err(DerError::InvalidLength);
+            }
+            let len = len_bytes
+                .iter()
+                .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+            if len < 0x80 {
+                return Err(DerError::InvalidLength);
+            }
+            len
+        };
+
+        if rest.len() < len {
+            return Err(DerError::Truncated);
+        }
+        let (content, tail) = rest.split_at(len);
+        self.input = tail;
+        Ok((tag, content))
+    }
+
+    /// Reads a small non-negative INTEGER
+    pub fn read_small_uint(&mut self) -> Result<u8, DerError> {
+        match self.read(TAG_INTEGER)? {
+            [value] if *value < 0x80 => Ok(*value),
+            _ => Err(DerError::InvalidValue),
+        }
+    }
+
+    /// Reads a non-negative INTEGER that fits in a u64
+    pub fn read_uint(&mut self) -> Result<u64, DerError> {
+        let content = self.read(TAG_INTEGER)?;
+        let digits = match content {
+            [] => return Err(DerError::InvalidValue),
+            [first, ..] if *first >= 0x80 => return Err(DerError::InvalidValue),
+            [0, next, ..] if *next < 0x80 => return Err(DerError::InvalidValue),
+            [0, rest @ ..] if !rest.is_empty() => rest,
+            _ => content,
+        };
+        if digits.len() > 8 {
+            return Err(DerError::InvalidValue);
+        }
+        Ok(digits.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    /// Reads a BIT STRING without unused bits and returns its bytes
+    pub fn read_bit_string(&mut self) -> Result<&'a [u8], DerError> {
+        match self.read(TAG_BIT_STRING)? {
+            [0, bytes @ ..] => Ok(bytes),
+            _ => Err(DerError::InvalidValue),
+        }
+    }
+
+    /// Checks that the whole input has been consumed
+    pub fn finish(self) -> Result<(), DerError> {
+        if self.input.is_empty() {
+            Ok(())
+        } else {
+            Err(DerError::TrailingData)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for len in [0usize, 1, 127, 128, 255, 256, 2400, 70000] {
+            let content = vec![0xa5u8; len];
+            let encoded = tlv(TAG_OCTET_STRING, &content);
+            let mut reader = Reader::new(&encoded);
+            assert_eq!(reader.read(TAG_OCTET_STRING).unwrap(), content.as_slice());
+            reader.finish().unwrap();
+        }
+        assert_eq!(tlv(TAG_OCTET_STRING, &[0u8; 200])[..3], [0x04, 0x81, 0xc8]);
+
+        // non minimal lengths
+        assert_eq!(
+            Reader::new(&[0x04, 0x81, 0x01, 0x00]).read_any(),
+            Err(DerError::InvalidLength)
+        );
+        assert_eq!(
+            Reader::new(&[0x04, 0x82, 0x00, 0x80]).read_any(),
+            Err(DerError::InvalidLength)
+        );
+        assert_eq!(
+            Reader::new(&[0x04, 0x80]).read_any(),
+            Err(DerError::InvalidLength)
+        );
+        assert_eq!(
+            Reader::new(&[0x04, 0x03, 0x00]).read_any(),
+            Err(DerError::Truncated)
+        );
+        assert_eq!(
+            Reader::new(&[0x02, 0x01, 0x00]).read(TAG_OCTET_STRING),
+            Err(DerError::UnexpectedTag {
+                expected: 0x04,
+                found: 0x02
+            })
+        );
+        for value in [0u64, 1, 127, 128, 255, 256, 2048, 1 << 31, u64::MAX] {
+            let mut encoded = Vec::new();
+            write_uint(&mut encoded, value);
+            assert_eq!(Reader::new(&encoded).read_uint(), Ok(value));
+        }
+        let mut encoded = Vec::new();
+        write_uint(&mut encoded, 128);
+        assert_eq!(encoded, [0x02, 0x02, 0x00, 0x80]);
+        for invalid in [
+            &[0x02, 0x00][..],
+            &[0x02, 0x01, 0xff],
+            &[0x02, 0x02, 0x00, 0x7f],
+        ] {
+            assert_eq!(
+                Reader::new(invalid).read_uint(),
+                Err(DerError::InvalidValue)
+            );
+        }
+
+        let mut reader = Reader::new(&[0x02, 0x01, 0x00, 0x05]);
+        assert_eq!(reader.read_small_uint(), Ok(0));
+        assert_eq!(reader.finish(), Err(DerError::TrailingData));
+    }
+}
@@ -0,0 +1,424 @@
+use core::fmt;
+use std::collections::HashSet;
+
+use aes::Aes256;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::Aead;
+use hkdf::Hkdf;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::hash::h;
+use crate::kyber::kem_scheme::ParameterSet;
+use crate::kyber::keys::{DecapsulationKey, EncapsulationKey};
+
+pub const VERSION: u8 = 1;
+pub const FINGERPRINT_LEN: usize = 32;
+/// The number of recipients is encoded on 16 bits
+pub const MAX_RECIPIENTS: usize = u16::MAX as usize;
+
+const KDF_LABEL: &[u8] = b"kyber-rs envelope KEK";
+/// Initial value of RFC 3394, section 2.2.3.1
+const AES_KW_IV: [u8; 8] = [0xa6; 8];
+
+/// Errors raised while building, unwrapping or parsing an envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeError {
+    /// DEKs are 16, 24 or 32 bytes long
+    InvalidDataKeyLength(usize),
+    /// No wrapped key has the fingerprint of the decapsulation key
+    UnknownRecipient,
+    /// The recipient is already in the envelope
+    DuplicateRecipient,
+    /// An envelope holds at most `MAX_RECIPIENTS` recipients
+    TooManyRecipients,
+    /// The wrapped key does not unwrap : wrong key or modified envelope
+    Unwrap,
+    Truncated,
+    UnsupportedVersion(u8),
+    UnknownAlgorithm(u8),
+    UnknownMethod(u8),
+    TrailingData,
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::InvalidDataKeyLength(len) => write!(f, "invalid data key length {len}"),
+            EnvelopeError::UnknownRecipient => write!(f, "no wrapped key for this recipient"),
+            EnvelopeError::DuplicateRecipient => write!(f, "recipient already in the envelope"),
+            EnvelopeError::TooManyRecipients => {
+                write!(f, "more than {MAX_RECIPIENTS} recipients")
+            }
+            EnvelopeError::Unwrap => write!(f, "key unwrapping failed"),
+            EnvelopeError::Truncated => write!(f, "truncated envelope"),
+            EnvelopeError::UnsupportedVersion(v) => write!(f, "unsupported envelope version {v}"),
+            EnvelopeError::UnknownAlgorithm(id) => write!(f, "unknown algorithm {id}"),
+            EnvelopeError::UnknownMethod(id) => write!(f, "unknown wrapping method {id}"),
+            EnvelopeError::TrailingData => write!(f, "trailing data after envelope"),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+/// Fingerprint of an encapsulation key : H(ek) = SHA3-256(ek)
+pub fn fingerprint(ek: &EncapsulationKey) -> [u8; FINGERPRINT_LEN] {
+    h(ek.as_bytes())
+}
+
+/// How the DEK is encrypted under the KEK
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMethod {
+    /// AES-256 key wrap (RFC 3394) : 8 bytes of overhead
+    AesKw,
+    /// AES-256-GCM : 16 bytes of overhead
+    Aes256Gcm,
+}
+
+impl WrapMethod {
+    pub const ALL: [WrapMethod; 2] = [WrapMethod::AesKw, WrapMethod::Aes256Gcm];
+
+    fn id(&self) -> u8 {
+        match self {
+            WrapMethod::AesKw => 1,
+            WrapMethod::Aes256Gcm => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        WrapMethod::ALL.into_iter().find(|method| method.id() == id)
+    }
+}
+
+/// AES-256 key wrap, RFC 3394 section 2.2.1, of a multiple of 8 bytes
+fn aes_kw_wrap(kek: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+    let cipher = Aes256::new(kek.into());
+    let n = plaintext.len() / 8;
+    let mut a = AES_KW_IV;
+    let mut r = plaintext.to_vec();
+    for j in 0..6 {
+        for i in 0..n {
+            let mut block = [0u8; 16];
+            block[..8].copy_from_slice(&a);
+            block[8..].copy_from_slice(&r[8 * i..8 * i + 8]);
+            cipher.encrypt_block((&mut block).into());
+            let t = (n * j + i + 1) as u64;
+            a = (u64::from_be_bytes(block[..8].try_into().unwrap()) ^ t).to_be_bytes();
+            r[8 * i..8 * i + 8].copy_from_slice(&block[8..]);
+        }
+    }
+    [a.as_slice(), &r].concat()
+}
+
+/// AES-256 key unwrap, RFC 3394 section 2.2.2, checking the initial value
+fn aes_kw_unwrap(kek: &[u8; 32], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    if ciphertext.len() < 24 || !ciphertext.len().is_multiple_of(8) {
+        return None;
+    }
+    let cipher = Aes256::new(kek.into());
+    let n = ciphertext.len() / 8 - 1;
+    let mut a: [u8; 8] = ciphertext[..8].try_into().unwrap();
+    let mut r = ciphertext[8..].to_vec();
+    for j in (0..6).rev() {
+        for i in (0..n).rev() {
+            let t = (n * j + i + 1) as u64;
+            let mut block = [0u8; 16];
+            block[..8].copy_from_slice(&(u64::from_be_bytes(a) ^ t).to_be_bytes());
+            block[8..].copy_from_slice(&r[8 * i..8 * i + 8]);
+            cipher.decrypt_block((&mut block).into());
+            a.copy_from_slice(&block[..8]);
+            r[8 * i..8 * i + 8].copy_from_slice(&block[8..]);
+        }
+    }
+    bool::from(a.ct_eq(&AES_KW_IV)).then_some(r)
+}
+
+/// Data-encryption key
+#[derive(Clone, PartialEq, Eq)]
+pub struct DataKey(Vec<u8>);
+
+impl fmt::Debug for DataKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DataKey({} bytes)", self.0.len())
+    }
+}
+
+impl DataKey {
+    /// Random 32-byte DEK
+    pub fn generate() -> Self {
+        let mut key = vec![0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        DataKey(key)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EnvelopeError> {
+        if ![16, 24, 32].contains(&bytes.len()) {
+            return Err(EnvelopeError::InvalidDataKeyLength(bytes.len()));
+        }
+        Ok(DataKey(bytes.to_vec()))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// DEK wrapped to one recipient
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedKey {
+    pub fingerprint: [u8; FINGERPRINT_LEN],
+    pub parameter_set: ParameterSet,
+    pub method: WrapMethod,
+    /// ML-KEM ciphertext c
+    pub ciphertext: Vec<u8>,
+    pub wrapped: Vec<u8>,
+}
+
+/// KEK = HKDF-SHA256(salt = fingerprint, ikm = K, info = "kyber-rs envelope KEK" ||
+/// method), where K is the ML-KEM shared key of the recipient. The KEK is used once,
+/// so AES-256-GCM wraps with a zero nonce.
+fn kek(fingerprint: &[u8], k: &[u8], method: WrapMethod) -> [u8; 32] {
+    let mut kek = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(fingerprint), k)
+        .expand_multi_info(&[KDF_LABEL, &[method.id()]], &mut kek)
+        .expect("32 is a valid length for HKDF-SHA256");
+    kek
+}
+
+impl WrappedKey {
+    fn wrap(dek: &DataKey, ek: &EncapsulationKey, method: WrapMethod) -> Self {
+        let fingerprint = fingerprint(ek);
+        let (k, ciphertext) = ek.encaps();
+        let kek = kek(&fingerprint, &k, method);
+        let wrapped = match method {
+            WrapMethod::AesKw => aes_kw_wrap(&kek, &dek.0),
+            WrapMethod::Aes256Gcm => Aes256Gcm::new(&kek.into())
+                .encrypt(&[0u8; 12].into(), dek.0.as_slice())
+                .expect("the DEK is short"),
+        };
+        WrappedKey {
+            fingerprint,
+            parameter_set: ek.parameter_set(),
+            method,
+            ciphertext,
+            wrapped,
+        }
+    }
+
+    fn unwrap(&self, dk: &DecapsulationKey) -> Result<DataKey, EnvelopeError> {
+        if dk.parameter_set() != self.parameter_set {
+            return Err(EnvelopeError::Unwrap);
+        }
+        let k = dk.decaps(&self.ciphertext);
+        let kek = kek(&self.fingerprint, &k, self.method);
+        let dek = match self.method {
+            WrapMethod::AesKw => aes_kw_unwrap(&kek, &self.wrapped),
+            WrapMethod::Aes256Gcm => Aes256Gcm::new(&kek.into())
+                .decrypt(&[0u8; 12].into(), self.wrapped.as_slice())
+                .ok(),
+        };
+        DataKey::from_bytes(&dek.ok_or(EnvelopeError::Unwrap)?).map_err(|_| EnvelopeError::Unwrap)
+    }
+}
+
+/// Envelope encryption : a data-encryption key (DEK) wrapped to several ML-KEM
+/// recipients, found by the fingerprint of their key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    recipients: Vec<WrappedKey>,
+}
+
+impl Envelope {
+    /// Wraps dek to each of the recipients; duplicates are wrapped once
+    pub fn new(
+        dek: &DataKey,
+        recipients: &[EncapsulationKey],
+        method: WrapMethod,
+    ) -> Result<Self, EnvelopeError> {
+        let mut seen = HashSet::new();
+        let recipients: Vec<_> = recipients
+            .iter()
+            .filter(|ek| seen.insert(ek.as_bytes()))
+            .collect();
+        if recipients.len() > MAX_RECIPIENTS {
+            return Err(EnvelopeError::TooManyRecipients);
+        }
+        Ok(Envelope {
+            recipients: recipients
+                .into_iter()
+                .map(|ek| WrappedKey::wrap(dek, ek, method))
+                .collect(),
+        })
+    }
+
+    pub fn recipients(&self) -> &[WrappedKey] {
+        &self.recipients
+    }
+
+    pub fn recipient(&self, fingerprint: &[u8; FINGERPRINT_LEN]) -> Option<&WrappedKey> {
+        self.recipients
+            .iter()
+            .find(|wrapped| &wrapped.fingerprint == fingerprint)
+    }
+
+    /// Unwraps the DEK with the wrapped key matching the fingerprint of dk
+    pub fn unwrap(&self, dk: &DecapsulationKey) -> Result<DataKey, EnvelopeError> {
+        self.recipient(&fingerprint(&dk.encapsulation_key()))
+            .ok_or(EnvelopeError::UnknownRecipient)?
+            .unwrap(dk)
+    }
+
+    /// Wraps the DEK to a new recipient, unwrapping it with dk; the DEK is never
+    /// returned
+    pub fn add_recipient(
+        &mut self,
+        dk: &DecapsulationKey,
+        ek: &EncapsulationKey,
+        method: WrapMethod,
+    ) -> Result<(), EnvelopeError> {
+        if self.recipient(&fingerprint(ek)).is_some() {
+            return Err(EnvelopeError::DuplicateRecipient);
+        }
+        if self.recipients.len() >= MAX_RECIPIENTS {
+            return Err(EnvelopeError::TooManyRecipients);
+        }
+        let dek = self.unwrap(dk)?;
+        self.recipients.push(WrappedKey::wrap(&dek, ek, method));
+        Ok(())
+    }
+
+    /// Removes a recipient, returning whether it was in the envelope
+    pub fn remove_recipient(&mut self, fingerprint: &[u8; FINGERPRINT_LEN]) -> bool {
+        let len = self.recipients.len();
+        self.recipients
+            .retain(|wrapped| &wrapped.fingerprint != fingerprint);
+        self.recipients.len() != len
+    }
+
+    /// Replaces the recipient of dk by the recipient ek, in place, keeping the
+    /// wrapping method
+    pub fn rewrap(
+        &mut self,
+        dk: &DecapsulationKey,
+        ek: &EncapsulationKey,
+    ) -> Result<(), EnvelopeError> {
+        let old = fingerprint(&dk.encapsulation_key());
+        let index = self
+            .recipients
+            .iter()
+            .position(|wrapped| wrapped.fingerprint == old)
+            .ok_or(EnvelopeError::UnknownRecipient)?;
+        let new = fingerprint(ek);
+        if new != old && self.recipient(&new).is_some() {
+            return Err(EnvelopeError::DuplicateRecipient);
+        }
+        let dek = self.recipients[index].unwrap(dk)?;
+        self.recipients[index] = WrappedKey::wrap(&dek, ek, self.recipients[index].method);
+        Ok(())
+    }
+
+    /// Encoding of version 1, integers in big-endian order : version 1 (1) ||
+    /// number of recipients (2) || for each recipient, fingerprint H(ek) (32) ||
+    /// algorithm (1, `ParameterSet::id`) || wrapping method (1) : 1 AES-KW,
+    /// 2 AES-256-GCM || ML-KEM ciphertext (c) || wrapped DEK length w (1) ||
+    /// wrapped DEK (w)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![VERSION];
+        out.extend_from_slice(&(self.recipients.len() as u16).to_be_bytes());
+        for wrapped in &self.recipients {
+            out.extend_from_slice(&wrapped.fingerprint);
+            out.push(wrapped.parameter_set.id());
+            out.push(wrapped.method.id());
+            out.extend_from_slice(&wrapped.ciphertext);
+            out.push(wrapped.wrapped.len() as u8);
+            out.extend_from_slice(&wrapped.wrapped);
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EnvelopeError> {
+        fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], EnvelopeError> {
+            if input.len() < len {
+                return Err(EnvelopeError::Truncated);
+            }
+            let (head, rest) = input.split_at(len);
+            *input = rest;
+            Ok(head)
+        }
+
+        let mut input = bytes;
+        let version = take(&mut input, 1)?[0];
+        if version != VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(version));
+        }
+        let count = u16::from_be_bytes(take(&mut input, 2)?.try_into().unwrap());
+        let mut recipients = Vec::new();
+        for _ in 0..count {
+            let fingerprint = take(&mut input, FINGERPRINT_LEN)?.try_into().unwrap();
+            let id = take(&mut input, 1)?[0];
+            let parameter_set =
+                ParameterSet::from_id(id).ok_or(EnvelopeError::UnknownAlgorithm(id))?;
+            let id = take(&mut input, 1)?[0];
+            let method = WrapMethod::from_id(id).ok_or(EnvelopeError::UnknownMethod(id))?;
+            let ciphertext = take(&mut input, parameter_set.ciphertext_len())?.to_vec();
+            let len = take(&mut input, 1)?[0] as usize;
+            let wrapped = take(&mut input, len)?.to_vec();
+            recipients.push(WrappedKey {
+                fingerprint,
+                parameter_set,
+                method,
+                ciphertext,
+                wrapped,
+            });
+        }
+        if !input.is_empty() {
+            return Err(EnvelopeError::TrailingData);
+        }
+        Ok(Envelope { recipients })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        // RFC 3394, section 4.6 : 256 bits of key data with a 256-bit KEK
+        let kek: [u8; 32] = core::array::from_fn(|i| i as u8);
+        let data = hex::decode("00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f")
+            .unwrap();
+        let wrapped = aes_kw_wrap(&kek, &data);
+        assert_eq!(
+            hex::encode(&wrapped),
+            "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326cbc7f0e71a99f43bfb988b9b7a02dd21"
+        );
+        assert_eq!(aes_kw_unwrap(&kek, &wrapped).unwrap(), data);
+        let mut modified = wrapped.clone();
+        modified[39] ^= 1;
+        assert_eq!(aes_kw_unwrap(&kek, &modified), None);
+
+        for method in WrapMethod::ALL {
+            assert_eq!(WrapMethod::from_id(method.id()), Some(method));
+        }
+
+        // A full envelope takes no new recipient
+        let dk = DecapsulationKey::generate(ParameterSet::MlKem512);
+        let mut envelope = Envelope::new(
+            &DataKey::generate(),
+            &[dk.encapsulation_key()],
+            WrapMethod::AesKw,
+        )
+        .unwrap();
+        envelope.recipients = vec![envelope.recipients[0].clone(); MAX_RECIPIENTS];
+        let other = DecapsulationKey::generate(ParameterSet::MlKem512).encapsulation_key();
+        assert_eq!(
+            envelope.add_recipient(&dk, &other, WrapMethod::AesKw),
+            Err(EnvelopeError::TooManyRecipients)
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use core::fmt;
+
+/// Maximum number of distinct chunks : one per element of GF(2^8)
+pub const MAX_CHUNKS: usize = 256;
+
+/// Errors raised by the erasure code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErasureError {
+    /// The message needs more than 256 data chunks, or the chunk length is zero
+    InvalidChunkLength(usize),
+    /// A received chunk does not have the chunk length of the decoder
+    ChunkLengthMismatch { expected: usize, found: usize },
+}
+
+impl fmt::Display for ErasureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErasureError::InvalidChunkLength(len) => write!(f, "invalid chunk length {len}"),
+            ErasureError::ChunkLengthMismatch { expected, found } => {
+                write!(f, "invalid chunk length {found}, expected {expected}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ErasureError {}
+
+/// Multiplication in GF(2^8) = GF(2)[X] / (X^8 + X^4 + X^3 + X + 1)
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Inverse in GF(2^8) : a^254, for a != 0
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut power = a;
+    let mut exponent = 254u8;
+    while exponent != 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, power);
+        }
+        power = gf_mul(power, power);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Evaluates at x, byte position by byte position, the polynomials of degree < n
+/// going through the n given (index, chunk) points (Lagrange interpolation)
+fn interpolate(points: &[(u8, &[u8])], x: u8, chunk_len: usize) -> Vec<u8> {
+    let mut out = vec![0u8; chunk_len];
+    for (i, &(x_i, y_i)) in points.iter().enumerate() {
+        let mut coefficient = 1u8;
+        for (m, &(x_m, _)) in points.iter().enumerate() {
+            if m != i {
+                coefficient = gf_mul(coefficient, gf_mul(x ^ x_m, gf_inv(x_i ^ x_m)));
+            }
+        }
+        if coefficient != 0 {
+            for (byte, y) in out.iter_mut().zip(y_i) {
+                *byte ^= gf_mul(coefficient, *y);
+            }
+        }
+    }
+    out
+}
+
+fn data_chunks(message_len: usize, chunk_len: usize) -> Result<usize, ErasureError> {
+    let count = message_len.div_ceil(chunk_len.max(1));
+    if chunk_len == 0 || count > MAX_CHUNKS {
+        return Err(ErasureError::InvalidChunkLength(chunk_len));
+    }
+    Ok(count)
+}
+
+/// Chunk of an erasure-coded message, with its index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub index: u8,
+    pub data: Vec<u8>,
+}
+
+/// Systematic Reed-Solomon erasure code over GF(2^8), for sending keys and
+/// ciphertexts over channels that drop messages.
+///
+/// The message, zero-padded, is cut into n data chunks, which are the chunks of
+/// index 0 to n-1. Each byte position is seen as a polynomial of degree < n over
+/// GF(2^8) and the chunk of index i >= n holds its values at i, so that any n
+/// distinct chunks give back the message.
+pub struct Encoder {
+    chunk_len: usize,
+    data: Vec<Vec<u8>>,
+}
+
+impl Encoder {
+    pub fn new(message: &[u8], chunk_len: usize) -> Result<Self, ErasureError> {
+        data_chunks(message.len(), chunk_len)?;
+        let data = message
+            .chunks(chunk_len)
+            .map(|chunk| {
+                let mut chunk = chunk.to_vec();
+                chunk.resize(chunk_len, 0);
+                chunk
+            })
+            .collect();
+        Ok(Encoder { chunk_len, data })
+    }
+
+    /// Number n of chunks needed to decode
+    pub fn data_chunks(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn chunk(&self, index: u8) -> Chunk {
+        let data = match self.data.get(index as usize) {
+            Some(chunk) => chunk.clone(),
+            None => {
+                let points: Vec<(u8, &[u8])> = self
+                    .data
+                    .iter()
+                    .enumerate()
+                    .map(|(i, chunk)| (i as u8, chunk.as_slice()))
+                    .collect();
+                interpolate(&points, index, self.chunk_len)
+            }
+        };
+        Chunk { index, data }
+    }
+}
+
+/// Collects chunks until any n distinct ones have arrived
+pub struct Decoder {
+    message_len: usize,
+    chunk_len: usize,
+    data_chunks: usize,
+    received: Vec<Chunk>,
+}
+
+impl Decoder {
+    pub fn new(message_len: usize, chunk_len: usize) -> Result<Self, ErasureError> {
+        Ok(Decoder {
+            message_len,
+            chunk_len,
+            data_chunks: data_chunks(message_len, chunk_len)?,
+            received: Vec::new(),
+        })
+    }
+
+    /// Adds a chunk; duplicates are ignored
+    pub fn add_chunk(&mut self, chunk: &Chunk) -> Result<(), ErasureError> {
+        if chunk.data.len() != self.chunk_len {
+            return Err(ErasureError::ChunkLengthMismatch {
+                expected: self.chunk_len,
+                found: chunk.data.len(),
+            });
+        }
+        if !self.is_complete()
+            && !self
+                .received
+                .iter()
+                .any(|received| received.index == chunk.index)
+        {
+            self.received.push(chunk.clone());
+        }
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.received.len() == self.data_chunks
+    }
+
+    /// The message, once enough chunks have arrived
+    pub fn decode(&self) -> Option<Vec<u8>> {
+        if !self.is_complete() {
+            return None;
+        }
+        let points: Vec<(u8, &[u8])> = self
+            .received
+            .iter()
+            .map(|chunk| (chunk.index, chunk.data.as_slice()))
+            .collect();
+        let mut message = Vec::with_capacity(self.data_chunks * self.chunk_len);
+        for index in 0..self.data_chunks {
+            match points.iter().find(|(x, _)| *x as usize == index) {
+                Some((_, data)) => message.extend_from_slice(data),
+                None => message.extend(interpolate(&points, index as u8, self.chunk_len)),
+            }
+        }
+        message.truncate(self.message_len);
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+
+        let message: Vec<u8> = (0..100u8).collect();
+        let encoder = Encoder::new(&message, 16).unwrap();
+        assert_eq!(encoder.data_chunks(), 7);
+        // Parity chunks only, then a mix of data and parity chunks
+        for indices in [[7u8, 8, 9, 10, 11, 12, 255], [0, 200, 2, 3, 50, 5, 9]] {
+            let mut decoder = Decoder::new(message.len(), 16).unwrap();
+            for index in indices {
+                assert_eq!(decoder.decode(), None);
+                decoder.add_chunk(&encoder.chunk(index)).unwrap();
+                decoder.add_chunk(&encoder.chunk(index)).unwrap();
+            }
+            assert_eq!(decoder.decode().unwrap(), message);
+        }
+        assert_eq!(
+            Encoder::new(&[0u8; 1000], 3).err(),
+            Some(ErasureError::InvalidChunkLength(3))
+        );
+    }
+}
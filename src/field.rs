@@ -0,0 +1,217 @@
+use core::fmt;
+use std::{
+    marker::PhantomData,
+    ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, CtOption};
+
+use crate::constants::PolyParams;
+
+/// Element of the field Z_Q
+///
+/// The stored value is always the canonical representative in [0, Q), so an
+/// out-of-range coefficient cannot be constructed. Reductions after add, sub and
+/// neg are performed with masks rather than branches. Values are stored on 16 bits
+/// so that a polynomial only takes 2*N bytes, which needs Q < 2^15 so that the
+/// products fit in u32 : this is checked at compile time.
+pub struct Zq<P: PolyParams> {
+    value: u16,
+    _marker: PhantomData<P>,
+}
+
+impl<P: PolyParams> Zq<P> {
+    /// Builds the element x mod Q, for any x in Z
+    pub fn new(x: i64) -> Self {
+        let r = x % P::Q;
+        Zq::<P>::from_reduced((r + ((r >> 63) & P::Q)) as u16)
+    }
+
+    fn from_reduced(value: u16) -> Self {
+        const { assert!(1 < P::Q && P::Q < 1 << 15, "Zq needs 1 < Q < 2^15") };
+        Zq::<P> {
+            value,
+            _marker: PhantomData::<P>,
+        }
+    }
+
+    pub fn zero() -> Self {
+        Zq::<P>::from_reduced(0)
+    }
+
+    pub fn one() -> Self {
+        Zq::<P>::from_reduced(1)
+    }
+
+    /// Canonical representative in [0, Q)
+    pub fn value(&self) -> i64 {
+        self.value as i64
+    }
+
+    /// Centered representative in [-(Q-1)/2, (Q-1)/2]
+    pub fn centered(&self) -> i64 {
+        let value = self.value as i64;
+        let mask = ((P::Q - 1) / 2 - value) >> 63;
+        value - (mask & P::Q)
+    }
+
+    /// Computes self^exp by square-and-multiply. The exponent is treated as public.
+    pub fn pow(&self, exp: u64) -> Self {
+        let mut result = Zq::<P>::one();
+        let mut base = *self;
+        let mut e = exp;
+        while e > 0 {
+            if e & 1 == 1 {
+                result *= base;
+            }
+            base *= base;
+            e >>= 1;
+        }
+        result
+    }
+
+    /// Multiplicative inverse, computed as self^(Q-2) (Fermat).
+    /// The result is none when self is zero.
+    pub fn inverse(&self) -> CtOption<Self> {
+        let inv = self.pow((P::Q - 2) as u64);
+        CtOption::new(inv, !self.ct_eq(&Zq::<P>::zero()))
+    }
+}
+
+impl<P: PolyParams> Clone for Zq<P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P: PolyParams> Copy for Zq<P> {}
+
+impl<P: PolyParams> Default for Zq<P> {
+    fn default() -> Self {
+        Zq::<P>::zero()
+    }
+}
+
+impl<P: PolyParams> From<i64> for Zq<P> {
+    fn from(value: i64) -> Self {
+        Zq::<P>::new(value)
+    }
+}
+
+impl<P: PolyParams> ConstantTimeEq for Zq<P> {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.value.ct_eq(&other.value)
+    }
+}
+
+impl<P: PolyParams> ConditionallySelectable for Zq<P> {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Zq::<P>::from_reduced(u16::conditional_select(&a.value, &b.value, choice))
+    }
+}
+
+impl<P: PolyParams> PartialEq for Zq<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<P: PolyParams> Eq for Zq<P> {}
+
+impl<P: PolyParams> Add for Zq<P> {
+    type Output = Zq<P>;
+    fn add(self, rhs: Self) -> Zq<P> {
+        let q = P::Q as i32;
+        let s = self.value as i32 + rhs.value as i32 - q;
+        Zq::<P>::from_reduced((s + ((s >> 31) & q)) as u16)
+    }
+}
+
+impl<P: PolyParams> AddAssign for Zq<P> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<P: PolyParams> Sub for Zq<P> {
+    type Output = Zq<P>;
+    fn sub(self, rhs: Self) -> Zq<P> {
+        let q = P::Q as i32;
+        let s = self.value as i32 - rhs.value as i32;
+        Zq::<P>::from_reduced((s + ((s >> 31) & q)) as u16)
+    }
+}
+
+impl<P: PolyParams> SubAssign for Zq<P> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<P: PolyParams> Mul for Zq<P> {
+    type Output = Zq<P>;
+    fn mul(self, rhs: Self) -> Zq<P> {
+        Zq::<P>::from_reduced(((self.value as u32 * rhs.value as u32) % P::Q as u32) as u16)
+    }
+}
+
+impl<P: PolyParams> MulAssign for Zq<P> {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl<P: PolyParams> Neg for Zq<P> {
+    type Output = Zq<P>;
+    fn neg(self) -> Zq<P> {
+        Zq::<P>::zero() - self
+    }
+}
+
+impl<P: PolyParams> fmt::Debug for Zq<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Zq({})", self.value)
+    }
+}
+
+impl<P: PolyParams> fmt::Display for Zq<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::KyberParams;
+
+    type F = Zq<KyberParams>;
+
+    #[test]
+    fn basics() {
+        assert_eq!(F::new(-1).value(), 3328);
+        assert_eq!(F::new(3329 * 5 + 7).value(), 7);
+        assert_eq!((F::new(3000) + F::new(1000)).value(), 671);
+        assert_eq!((F::new(10) - F::new(20)).value(), 3319);
+        assert_eq!((F::new(1729) * F::new(2580)).value(), (1729 * 2580) % 3329);
+        assert_eq!((-F::new(5)).value(), 3324);
+        assert_eq!((-F::zero()).value(), 0);
+
+        assert_eq!(F::new(1664).centered(), 1664);
+        assert_eq!(F::new(1665).centered(), -1664);
+        assert_eq!(F::new(3328).centered(), -1);
+
+        assert_eq!(F::new(17).pow(128).value(), 3328);
+        assert_eq!(F::new(17).pow(256), F::one());
+        for x in 1..KyberParams::Q {
+            let a = F::new(x);
+            assert_eq!(a * a.inverse().unwrap(), F::one());
+        }
+        assert!(bool::from(F::zero().inverse().is_none()));
+
+        let (a, b) = (F::new(12), F::new(34));
+        assert!(bool::from(a.ct_eq(&F::new(3341))));
+        assert!(!bool::from(a.ct_eq(&b)));
+        assert_eq!(F::conditional_select(&a, &b, Choice::from(0)), a);
+        assert_eq!(F::conditional_select(&a, &b, Choice::from(1)), b);
+    }
+}
@@ -1,5 +1,98 @@
+use sha3::digest::core_api::CoreWrapper;
 use sha3::digest::{ExtendableOutput, Update, XofReader};
-use sha3::{Digest, Sha3_256, Sha3_512, Shake256};
+use sha3::{
+    CShake128Core, CShake256Core, Digest, Sha3_256, Sha3_512, Shake128, Shake128Reader, Shake256,
+};
+
+use crate::keccak::{SHAKE128_RATE, Shake128x4};
+
+/// Set of hash functions used by ML-KEM (4.1 FIPS 203)
+///
+/// Implement this trait to plug in another Keccak provider (FIPS-validated module,
+/// hardware accelerator...). `Sha3Suite` is the default, backed by the `sha3` crate.
+pub trait HashSuite {
+    /// Reader returned by the XOF once B has been absorbed
+    type Xof: XofReader;
+
+    /// H : B* -> B^32 (SHA3-256)
+    fn h(s: &[u8]) -> [u8; 32];
+
+    /// G : B* -> B^32 x B^32 (SHA3-512)
+    fn g(c: &[u8]) -> ([u8; 32], [u8; 32]);
+
+    /// J : B* -> B^32 (SHAKE256)
+    fn j(s: &[u8]) -> [u8; 32];
+
+    /// PRF : {2, 3} x B^32 x B -> B^(64*eta) (SHAKE256)
+    fn prf(eta: usize, s: &[u8; 32], b: &[u8; 1]) -> Vec<u8>;
+
+    /// XOF used by SampleNTT (SHAKE128), already absorbed with the 34 input bytes
+    fn xof(bytes: &[u8; 34]) -> Self::Xof;
+
+    /// Four XOF instances for SampleNTT, squeezed together block by block
+    type XofX4: XofX4;
+
+    /// Four instances of `xof`, already absorbed with their 34 input bytes
+    fn xof_x4(bytes: [&[u8; 34]; 4]) -> Self::XofX4;
+}
+
+/// Four XOF instances squeezed in lockstep, one rate block (168 bytes) at a time
+pub trait XofX4 {
+    fn squeeze_blocks(&mut self, out: &mut [[u8; SHAKE128_RATE]; 4]);
+}
+
+impl XofX4 for Shake128x4 {
+    fn squeeze_blocks(&mut self, out: &mut [[u8; SHAKE128_RATE]; 4]) {
+        Shake128x4::squeeze_blocks(self, out)
+    }
+}
+
+/// `XofX4` running four independent readers one after the other, for hash suites
+/// without a batched implementation
+pub struct SerialXofX4<R: XofReader>(pub [R; 4]);
+
+impl<R: XofReader> XofX4 for SerialXofX4<R> {
+    fn squeeze_blocks(&mut self, out: &mut [[u8; SHAKE128_RATE]; 4]) {
+        for (reader, block) in self.0.iter_mut().zip(out.iter_mut()) {
+            reader.read(block);
+        }
+    }
+}
+
+/// Default hash suite, using the `sha3` crate
+pub struct Sha3Suite;
+
+impl HashSuite for Sha3Suite {
+    type Xof = Shake128Reader;
+
+    fn h(s: &[u8]) -> [u8; 32] {
+        h(s)
+    }
+
+    fn g(c: &[u8]) -> ([u8; 32], [u8; 32]) {
+        g(c)
+    }
+
+    fn j(s: &[u8]) -> [u8; 32] {
+        j(s)
+    }
+
+    fn prf(eta: usize, s: &[u8; 32], b: &[u8; 1]) -> Vec<u8> {
+        prf(eta, s, b)
+    }
+
+    fn xof(bytes: &[u8; 34]) -> Self::Xof {
+        let mut hasher = Shake128::default();
+        hasher.update(bytes);
+        hasher.finalize_xof()
+    }
+
+    type XofX4 = Shake128x4;
+
+    fn xof_x4(bytes: [&[u8; 34]; 4]) -> Self::XofX4 {
+        Shake128x4::new(bytes.map(|b| b.as_slice()))
+    }
+}
 
 /// Matches the definition in (4.2) and in (4.3)
 /// PRF : {2, 3} x B^32 x B -> B^(64*eta)
@@ -56,6 +149,189 @@ pub fn g(c: &[u8]) -> ([u8; 32], [u8; 32]) {
     (a, b)
 }
 
+/// Security strength of the SP 800-185 functions, selecting the underlying cSHAKE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strength {
+    /// cSHAKE128, rate of 168 bytes
+    S128,
+    /// cSHAKE256, rate of 136 bytes
+    S256,
+}
+
+impl Strength {
+    pub fn rate(&self) -> usize {
+        match self {
+            Strength::S128 => 168,
+            Strength::S256 => 136,
+        }
+    }
+}
+
+/// left_encode(x) (2.3.1 SP 800-185)
+pub fn left_encode(x: u64) -> Vec<u8> {
+    let bytes = x.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count().min(7);
+    let mut out = vec![(8 - skip) as u8];
+    out.extend_from_slice(&bytes[skip..]);
+    out
+}
+
+/// right_encode(x) (2.3.1 SP 800-185)
+pub fn right_encode(x: u64) -> Vec<u8> {
+    let bytes = x.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count().min(7);
+    let mut out = bytes[skip..].to_vec();
+    out.push((8 - skip) as u8);
+    out
+}
+
+/// encode_string(S) (2.3.2 SP 800-185)
+pub fn encode_string(s: &[u8]) -> Vec<u8> {
+    let mut out = left_encode(8 * s.len() as u64);
+    out.extend_from_slice(s);
+    out
+}
+
+/// bytepad(X, w) (2.3.3 SP 800-185)
+pub fn bytepad(x: &[u8], w: usize) -> Vec<u8> {
+    let mut out = left_encode(w as u64);
+    out.extend_from_slice(x);
+    out.resize(out.len().div_ceil(w) * w, 0);
+    out
+}
+
+/// cSHAKE(X, L, N, S) (3 SP 800-185) over the concatenation of the parts of X
+fn cshake_parts(strength: Strength, parts: &[&[u8]], l: usize, n: &[u8], s: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; l];
+    match strength {
+        Strength::S128 => {
+            let mut hasher = CoreWrapper::from_core(CShake128Core::new_with_function_name(n, s));
+            parts.iter().for_each(|part| hasher.update(part));
+            hasher.finalize_xof().read(&mut out);
+        }
+        Strength::S256 => {
+            let mut hasher = CoreWrapper::from_core(CShake256Core::new_with_function_name(n, s));
+            parts.iter().for_each(|part| hasher.update(part));
+            hasher.finalize_xof().read(&mut out);
+        }
+    }
+    out
+}
+
+/// cSHAKE (3 SP 800-185)
+/// cSHAKE : B* x N x B* x B* -> B^L, with L in bytes
+pub fn cshake(strength: Strength, x: &[u8], l: usize, n: &[u8], s: &[u8]) -> Vec<u8> {
+    cshake_parts(strength, &[x], l, n, s)
+}
+
+pub fn cshake128(x: &[u8], l: usize, n: &[u8], s: &[u8]) -> Vec<u8> {
+    cshake(Strength::S128, x, l, n, s)
+}
+
+pub fn cshake256(x: &[u8], l: usize, n: &[u8], s: &[u8]) -> Vec<u8> {
+    cshake(Strength::S256, x, l, n, s)
+}
+
+/// KMAC and KMACXOF (4 SP 800-185)
+/// KMAC : B* x B* x N x B* -> B^L, with L in bytes
+///
+/// For KMACXOF (xof = true), the output length is not bound in the input,
+/// so any prefix of a longer output is a valid shorter output.
+pub fn kmac(strength: Strength, k: &[u8], x: &[u8], l: usize, s: &[u8], xof: bool) -> Vec<u8> {
+    let encoded_key = bytepad(&encode_string(k), strength.rate());
+    let encoded_l = right_encode(if xof { 0 } else { 8 * l as u64 });
+    cshake_parts(strength, &[&encoded_key, x, &encoded_l], l, b"KMAC", s)
+}
+
+pub fn kmac128(k: &[u8], x: &[u8], l: usize, s: &[u8]) -> Vec<u8> {
+    kmac(Strength::S128, k, x, l, s, false)
+}
+
+pub fn kmac256(k: &[u8], x: &[u8], l: usize, s: &[u8]) -> Vec<u8> {
+    kmac(Strength::S256, k, x, l, s, false)
+}
+
+pub fn kmac_xof128(k: &[u8], x: &[u8], l: usize, s: &[u8]) -> Vec<u8> {
+    kmac(Strength::S128, k, x, l, s, true)
+}
+
+pub fn kmac_xof256(k: &[u8], x: &[u8], l: usize, s: &[u8]) -> Vec<u8> {
+    kmac(Strength::S256, k, x, l, s, true)
+}
+
+/// TupleHash and TupleHashXOF (5 SP 800-185)
+/// TupleHash : (B*)^n x N x B* -> B^L, with L in bytes
+pub fn tuple_hash(strength: Strength, x: &[&[u8]], l: usize, s: &[u8], xof: bool) -> Vec<u8> {
+    let mut z = Vec::new();
+    for item in x {
+        z.extend(encode_string(item));
+    }
+    z.extend(right_encode(if xof { 0 } else { 8 * l as u64 }));
+    cshake_parts(strength, &[&z], l, b"TupleHash", s)
+}
+
+pub fn tuple_hash128(x: &[&[u8]], l: usize, s: &[u8]) -> Vec<u8> {
+    tuple_hash(Strength::S128, x, l, s, false)
+}
+
+pub fn tuple_hash256(x: &[&[u8]], l: usize, s: &[u8]) -> Vec<u8> {
+    tuple_hash(Strength::S256, x, l, s, false)
+}
+
+pub fn tuple_hash_xof128(x: &[&[u8]], l: usize, s: &[u8]) -> Vec<u8> {
+    tuple_hash(Strength::S128, x, l, s, true)
+}
+
+pub fn tuple_hash_xof256(x: &[&[u8]], l: usize, s: &[u8]) -> Vec<u8> {
+    tuple_hash(Strength::S256, x, l, s, true)
+}
+
+/// ParallelHash and ParallelHashXOF (6 SP 800-185)
+/// ParallelHash : B* x N x N x B* -> B^L, with block size b and L in bytes
+///
+/// The blocks are hashed one after the other; the output only depends on b.
+pub fn parallel_hash(
+    strength: Strength,
+    x: &[u8],
+    b: usize,
+    l: usize,
+    s: &[u8],
+    xof: bool,
+) -> Vec<u8> {
+    if b == 0 {
+        panic!("Unauthorized value for the block size b")
+    }
+    let chain_len = match strength {
+        Strength::S128 => 32,
+        Strength::S256 => 64,
+    };
+
+    let mut z = left_encode(b as u64);
+    let n = x.len().div_ceil(b);
+    for block in x.chunks(b) {
+        z.extend(cshake(strength, block, chain_len, b"", b""));
+    }
+    z.extend(right_encode(n as u64));
+    z.extend(right_encode(if xof { 0 } else { 8 * l as u64 }));
+    cshake_parts(strength, &[&z], l, b"ParallelHash", s)
+}
+
+pub fn parallel_hash128(x: &[u8], b: usize, l: usize, s: &[u8]) -> Vec<u8> {
+    parallel_hash(Strength::S128, x, b, l, s, false)
+}
+
+pub fn parallel_hash256(x: &[u8], b: usize, l: usize, s: &[u8]) -> Vec<u8> {
+    parallel_hash(Strength::S256, x, b, l, s, false)
+}
+
+pub fn parallel_hash_xof128(x: &[u8], b: usize, l: usize, s: &[u8]) -> Vec<u8> {
+    parallel_hash(Strength::S128, x, b, l, s, true)
+}
+
+pub fn parallel_hash_xof256(x: &[u8], b: usize, l: usize, s: &[u8]) -> Vec<u8> {
+    parallel_hash(Strength::S256, x, b, l, s, true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,5 +368,131 @@ mod tests {
                     .unwrap()
             )
         );
+
+        assert_eq!(Sha3Suite::prf(2, seed_s, nonce_b), prf_result);
+        assert_eq!(Sha3Suite::h(seed_s), h_result);
+        assert_eq!(Sha3Suite::j(seed_s), j_result);
+        assert_eq!(Sha3Suite::g(seed_s), (g_a, g_b));
+
+        let mut xof_out = [0u8; 32];
+        Sha3Suite::xof(b"Salut de la part de moi meme le ka").read(&mut xof_out);
+        assert_eq!(
+            xof_out.to_vec(),
+            hex::decode("4621bc8774b275b75522274e349da60cc14f143dbc1a3c39491438e20848b8d1")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn sp800_185() {
+        let unhex = |s: &str| hex::decode(s.replace(' ', "")).unwrap();
+        let x4 = unhex("00010203");
+        let x200: Vec<u8> = (0..200).collect();
+
+        // cSHAKE samples
+        assert_eq!(
+            cshake128(&x4, 32, b"", b"Email Signature"),
+            unhex("C1C36925B6409A04F1B504FCBCA9D82B4017277CB5ED2B2065FC1D3814D5AAF5")
+        );
+        assert_eq!(
+            cshake128(&x200, 32, b"", b"Email Signature"),
+            unhex("C5221D50E4F822D96A2E8881A961420F294B7B24FE3D2094BAED2C6524CC166B")
+        );
+        assert_eq!(
+            cshake256(&x4, 64, b"", b"Email Signature"),
+            unhex(
+                "D008828E2B80AC9D2218FFEE1D070C48B8E4C87BFF32C9699D5B6896EEE0EDD164020E2BE0560858D9C00C037E34A96937C561A74C412BB4C746469527281C8C"
+            )
+        );
+        assert_eq!(cshake128(&x4, 32, b"", b""), {
+            let mut out = vec![0u8; 32];
+            let mut hasher = Shake128::default();
+            hasher.update(&x4);
+            hasher.finalize_xof().read(&mut out);
+            out
+        });
+
+        // KMAC samples
+        let key: Vec<u8> = (0x40..0x60).collect();
+        assert_eq!(
+            kmac128(&key, &x4, 32, b""),
+            unhex("E5780B0D3EA6F7D3A429C5706AA43A00FADBD7D49628839E3187243F456EE14E")
+        );
+        assert_eq!(
+            kmac128(&key, &x4, 32, b"My Tagged Application"),
+            unhex("3B1FBA963CD8B0B59E8C1A6D71888B7143651AF8BA0A7070C0979E2811324AA5")
+        );
+        assert_eq!(
+            kmac128(&key, &x200, 32, b"My Tagged Application"),
+            unhex("1F5B4E6CCA02209E0DCB5CA635B89A15E271ECC760071DFD805FAA38F9729230")
+        );
+        assert_eq!(
+            kmac256(&key, &x4, 64, b"My Tagged Application"),
+            unhex(
+                "20C570C31346F703C9AC36C61C03CB64C3970D0CFC787E9B79599D273A68D2F7F69D4CC3DE9D104A351689F27CF6F5951F0103F33F4F24871024D9C27773A8DD"
+            )
+        );
+        assert_eq!(
+            kmac256(&key, &x200, 64, b""),
+            unhex(
+                "75358CF39E41494E949707927CEE0AF20A3FF553904C86B08F21CC414BCFD691589D27CF5E15369CBBFF8B9A4C2EB17800855D0235FF635DA82533EC6B759B69"
+            )
+        );
+        assert_eq!(
+            kmac256(&key, &x200, 64, b"My Tagged Application"),
+            unhex(
+                "B58618F71F92E1D56C1B8C55DDD7CD188B97B4CA4D99831EB2699A837DA2E4D970FBACFDE50033AEA585F1A2708510C32D07880801BD182898FE476876FC8965"
+            )
+        );
+        assert_eq!(
+            kmac_xof128(&key, &x4, 32, b""),
+            unhex("CD83740BBD92CCC8CF032B1481A0F4460E7CA9DD12B08A0C4031178BACD6EC35")
+        );
+        assert_eq!(
+            kmac_xof256(&key, &x4, 64, b"My Tagged Application"),
+            unhex(
+                "1755133F1534752AAD0748F2C706FB5C784512CAB835CD15676B16C0C6647FA96FAA7AF634A0BF8FF6DF39374FA00FAD9A39E322A7C92065A64EB1FB0801EB2B"
+            )
+        );
+
+        // TupleHash samples
+        let t1 = unhex("000102");
+        let t2 = unhex("101112131415");
+        let t3 = unhex("202122232425262728");
+        assert_eq!(
+            tuple_hash128(&[&t1, &t2], 32, b""),
+            unhex("C5D8786C1AFB9B82111AB34B65B2C0048FA64E6D48E263264CE1707D3FFC8ED1")
+        );
+        assert_eq!(
+            tuple_hash128(&[&t1, &t2], 32, b"My Tuple App"),
+            unhex("75CDB20FF4DB1154E841D758E24160C54BAE86EB8C13E7F5F40EB35588E96DFB")
+        );
+        assert_eq!(
+            tuple_hash128(&[&t1, &t2, &t3], 32, b"My Tuple App"),
+            unhex("E60F202C89A2631EDA8D4C588CA5FD07F39E5151998DECCF973ADB3804BB6E84")
+        );
+        assert_eq!(
+            tuple_hash256(&[&t1, &t2], 64, b""),
+            unhex(
+                "CFB7058CACA5E668F81A12A20A2195CE97A925F1DBA3E7449A56F82201EC607311AC2696B1AB5EA2352DF1423BDE7BD4BB78C9AED1A853C78672F9EB23BBE194"
+            )
+        );
+
+        // ParallelHash samples
+        let xp = unhex("000102030405060710111213141516172021222324252627");
+        assert_eq!(
+            parallel_hash128(&xp, 8, 32, b""),
+            unhex("BA8DC1D1D979331D3F813603C67F72609AB5E44B94A0B8F9AF46514454A2B4F5")
+        );
+        assert_eq!(
+            parallel_hash128(&xp, 8, 32, b"Parallel Data"),
+            unhex("FC484DCB3F84DCEEDC353438151BEE58157D6EFED0445A81F165E495795B7206")
+        );
+        assert_eq!(
+            parallel_hash256(&xp, 8, 64, b""),
+            unhex(
+                "BC1EF124DA34495E948EAD207DD9842235DA432D2BBC54B4C110E64C451105531B7F2A3E0CE055C02805E7C2DE1FB746AF97A1DD01F43B824E31B87612410429"
+            )
+        );
     }
 }
@@ -0,0 +1,354 @@
+use crate::hpke::kem::HpkeKem;
+use crate::hpke::primitives::{HpkeAead, HpkeError, HpkeKdf};
+
+/// HPKE modes (Section 5 RFC 9180). The authenticated modes need a DHKEM and are
+/// not provided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// mode_base = 0x00
+    Base,
+    /// mode_psk = 0x01
+    Psk,
+}
+
+impl Mode {
+    pub fn id(&self) -> u8 {
+        match self {
+            Mode::Base => 0x00,
+            Mode::Psk => 0x01,
+        }
+    }
+}
+
+/// Pre-shared key of the PSK mode, with its identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Psk<'a> {
+    pub psk: &'a [u8],
+    pub psk_id: &'a [u8],
+}
+
+/// Encryption context shared by the sender and the recipient (Section 5.2 RFC 9180)
+struct Context {
+    suite_id: Vec<u8>,
+    kdf: HpkeKdf,
+    aead: HpkeAead,
+    key: Vec<u8>,
+    base_nonce: Vec<u8>,
+    seq: u64,
+    exporter_secret: Vec<u8>,
+}
+
+impl Context {
+    /// ComputeNonce(seq) = base_nonce XOR I2OSP(seq, Nn)
+    fn nonce(&self) -> Result<Vec<u8>, HpkeError> {
+        // The nonce of u64::MAX is never used, so that the next sequence number
+        // always fits
+        if self.seq == u64::MAX {
+            return Err(HpkeError::MessageLimitReached);
+        }
+        let mut nonce = self.base_nonce.clone();
+        let offset = nonce.len() - 8;
+        for (byte, seq_byte) in nonce[offset..].iter_mut().zip(self.seq.to_be_bytes()) {
+            *byte ^= seq_byte;
+        }
+        Ok(nonce)
+    }
+
+    /// Export(exporter_context, L) = LabeledExpand(exporter_secret, "sec", exporter_context, L)
+    fn export(&self, exporter_context: &[u8], len: usize) -> Result<Vec<u8>, HpkeError> {
+        self.kdf.labeled_expand(
+            &self.suite_id,
+            &self.exporter_secret,
+            b"sec",
+            exporter_context,
+            len,
+        )
+    }
+}
+
+/// Sender side of an HPKE context
+pub struct SenderContext(Context);
+
+impl SenderContext {
+    /// ContextS.Seal(aad, pt)
+    pub fn seal(&mut self, aad: &[u8], pt: &[u8]) -> Result<Vec<u8>, HpkeError> {
+        let nonce = self.0.nonce()?;
+        let ct = self.0.aead.seal(&self.0.key, &nonce, aad, pt);
+        self.0.seq += 1;
+        Ok(ct)
+    }
+
+    /// Secret of the given length bound to the context
+    pub fn export(&self, exporter_context: &[u8], len: usize) -> Result<Vec<u8>, HpkeError> {
+        self.0.export(exporter_context, len)
+    }
+}
+
+/// Recipient side of an HPKE context
+pub struct ReceiverContext(Context);
+
+impl ReceiverContext {
+    /// ContextR.Open(aad, ct). The sequence number only moves on success.
+    pub fn open(&mut self, aad: &[u8], ct: &[u8]) -> Result<Vec<u8>, HpkeError> {
+        let nonce = self.0.nonce()?;
+        let pt = self.0.aead.open(&self.0.key, &nonce, aad, ct)?;
+        self.0.seq += 1;
+        Ok(pt)
+    }
+
+    /// Secret of the given length bound to the context
+    pub fn export(&self, exporter_context: &[u8], len: usize) -> Result<Vec<u8>, HpkeError> {
+        self.0.export(exporter_context, len)
+    }
+}
+
+/// HPKE cipher suite (RFC 9180). The PSK mode is selected by passing a `Psk`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hpke {
+    pub kem: HpkeKem,
+    pub kdf: HpkeKdf,
+    pub aead: HpkeAead,
+}
+
+impl Hpke {
+    pub fn new(kem: HpkeKem, kdf: HpkeKdf, aead: HpkeAead) -> Self {
+        Hpke { kem, kdf, aead }
+    }
+
+    /// suite_id = "HPKE" || I2OSP(kem_id, 2) || I2OSP(kdf_id, 2) || I2OSP(aead_id, 2)
+    fn suite_id(&self) -> Vec<u8> {
+        [
+            b"HPKE".as_slice(),
+            &self.kem.id().to_be_bytes(),
+            &self.kdf.id().to_be_bytes(),
+            &self.aead.id().to_be_bytes(),
+        ]
+        .concat()
+    }
+
+    /// KeySchedule<ROLE>(mode, shared_secret, info, psk, psk_id)
+    fn key_schedule(
+        &self,
+        shared_secret: &[u8],
+        info: &[u8],
+        psk: Option<Psk>,
+    ) -> Result<Context, HpkeError> {
+        let (mode, psk) = match psk {
+            None => (
+                Mode::Base,
+                Psk {
+                    psk: b"",
+                    psk_id: b"",
+                },
+            ),
+            Some(psk) if !psk.psk.is_empty() && !psk.psk_id.is_empty() => (Mode::Psk, psk),
+            Some(_) => return Err(HpkeError::InconsistentPsk),
+        };
+
+        let suite_id = self.suite_id();
+        let kdf = self.kdf;
+        let psk_id_hash = kdf.labeled_extract(&suite_id, b"", b"psk_id_hash", psk.psk_id);
+        let info_hash = kdf.labeled_extract(&suite_id, b"", b"info_hash", info);
+        let key_schedule_context = [[mode.id()].as_slice(), &psk_id_hash, &info_hash].concat();
+
+        let secret = kdf.labeled_extract(&suite_id, shared_secret, b"secret", psk.psk);
+        let expand = |label: &[u8], len| {
+            kdf.labeled_expand(&suite_id, &secret, label, &key_schedule_context, len)
+        };
+        Ok(Context {
+            key: expand(b"key", self.aead.nk())?,
+            base_nonce: expand(b"base_nonce", self.aead.nn())?,
+            exporter_secret: expand(b"exp", kdf.nh())?,
+            seq: 0,
+            suite_id,
+            kdf,
+            aead: self.aead,
+        })
+    }
+
+    /// SetupBaseS / SetupPSKS : returns (enc, context)
+    pub fn setup_sender(
+        &self,
+        pk_r: &[u8],
+        info: &[u8],
+        psk: Option<Psk>,
+    ) -> Result<(Vec<u8>, SenderContext), HpkeError> {
+        let (shared_secret, enc) = self.kem.encap(pk_r)?;
+        Ok((
+            enc,
+            SenderContext(self.key_schedule(&shared_secret, info, psk)?),
+        ))
+    }
+
+    /// Deterministic `setup_sender`, with the randomness of `HpkeKem::encap_derand`
+    pub fn setup_sender_derand(
+        &self,
+        pk_r: &[u8],
+        info: &[u8],
+        psk: Option<Psk>,
+        randomness: &[u8],
+    ) -> Result<(Vec<u8>, SenderContext), HpkeError> {
+        let (shared_secret, enc) = self.kem.encap_derand(pk_r, randomness)?;
+        Ok((
+            enc,
+            SenderContext(self.key_schedule(&shared_secret, info, psk)?),
+        ))
+    }
+
+    /// SetupBaseR / SetupPSKR
+    pub fn setup_receiver(
+        &self,
+        enc: &[u8],
+        sk_r: &[u8],
+        info: &[u8],
+        psk: Option<Psk>,
+    ) -> Result<ReceiverContext, HpkeError> {
+        let shared_secret = self.kem.decap(enc, sk_r)?;
+        Ok(ReceiverContext(self.key_schedule(
+            &shared_secret,
+            info,
+            psk,
+        )?))
+    }
+
+    /// Single-shot SealBase / SealPSK : returns (enc, ct)
+    pub fn seal(
+        &self,
+        pk_r: &[u8],
+        info: &[u8],
+        aad: &[u8],
+        pt: &[u8],
+        psk: Option<Psk>,
+    ) -> Result<(Vec<u8>, Vec<u8>), HpkeError> {
+        let (enc, mut context) = self.setup_sender(pk_r, info, psk)?;
+        Ok((enc, context.seal(aad, pt)?))
+    }
+
+    /// Single-shot OpenBase / OpenPSK
+    pub fn open(
+        &self,
+        enc: &[u8],
+        sk_r: &[u8],
+        info: &[u8],
+        aad: &[u8],
+        ct: &[u8],
+        psk: Option<Psk>,
+    ) -> Result<Vec<u8>, HpkeError> {
+        self.setup_receiver(enc, sk_r, info, psk)?.open(aad, ct)
+    }
+
+    /// Single-shot SendExport : returns (enc, exported secret)
+    pub fn send_export(
+        &self,
+        pk_r: &[u8],
+        info: &[u8],
+        exporter_context: &[u8],
+        len: usize,
+        psk: Option<Psk>,
+    ) -> Result<(Vec<u8>, Vec<u8>), HpkeError> {
+        let (enc, context) = self.setup_sender(pk_r, info, psk)?;
+        Ok((enc, context.export(exporter_context, len)?))
+    }
+
+    /// Single-shot ReceiveExport
+    pub fn receive_export(
+        &self,
+        enc: &[u8],
+        sk_r: &[u8],
+        info: &[u8],
+        exporter_context: &[u8],
+        len: usize,
+        psk: Option<Psk>,
+    ) -> Result<Vec<u8>, HpkeError> {
+        self.setup_receiver(enc, sk_r, info, psk)?
+            .export(exporter_context, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        let psk = Psk {
+            psk: &[0x42; 32],
+            psk_id: b"psk id",
+        };
+        for kem in HpkeKem::ALL {
+            let hpke = Hpke::new(kem, HpkeKdf::HkdfSha384, HpkeAead::ChaCha20Poly1305);
+            let (sk, pk) = kem.generate_key_pair();
+
+            let (enc, ct) = hpke.seal(&pk, b"info", b"aad", b"message", None).unwrap();
+            assert_eq!(
+                hpke.open(&enc, &sk, b"info", b"aad", &ct, None).unwrap(),
+                b"message"
+            );
+            assert_eq!(
+                hpke.open(&enc, &sk, b"other", b"aad", &ct, None),
+                Err(HpkeError::Open)
+            );
+            assert_eq!(
+                hpke.open(&enc, &sk, b"info", b"aad", &ct, Some(psk)),
+                Err(HpkeError::Open)
+            );
+
+            let (enc, ct) = hpke
+                .seal(&pk, b"info", b"aad", b"message", Some(psk))
+                .unwrap();
+            assert_eq!(
+                hpke.open(&enc, &sk, b"info", b"aad", &ct, Some(psk))
+                    .unwrap(),
+                b"message"
+            );
+            assert_eq!(
+                hpke.open(&enc, &sk, b"info", b"aad", &ct, None),
+                Err(HpkeError::Open)
+            );
+
+            let (enc, secret) = hpke
+                .send_export(&pk, b"info", b"context", 64, None)
+                .unwrap();
+            assert_eq!(
+                hpke.receive_export(&enc, &sk, b"info", b"context", 64, None)
+                    .unwrap(),
+                secret
+            );
+        }
+
+        let hpke = Hpke::new(HpkeKem::MlKem768, HpkeKdf::HkdfSha256, HpkeAead::Aes256Gcm);
+        let (sk, pk) = hpke.kem.generate_key_pair();
+        let (enc, mut sender) = hpke.setup_sender(&pk, b"", None).unwrap();
+        let mut receiver = hpke.setup_receiver(&enc, &sk, b"", None).unwrap();
+        let first = sender.seal(b"", b"first").unwrap();
+        let second = sender.seal(b"", b"second").unwrap();
+        // out of order : the sequence number is not consumed by a failure
+        assert_eq!(receiver.open(b"", &second), Err(HpkeError::Open));
+        assert_eq!(receiver.open(b"", &first).unwrap(), b"first");
+        assert_eq!(receiver.open(b"", &second).unwrap(), b"second");
+        assert_eq!(
+            sender.export(b"", 255 * 32 + 1),
+            Err(HpkeError::ExportTooLong)
+        );
+
+        sender.0.seq = u64::MAX;
+        assert_eq!(sender.seal(b"", b""), Err(HpkeError::MessageLimitReached));
+
+        for inconsistent in [
+            Psk {
+                psk: b"",
+                psk_id: b"id",
+            },
+            Psk {
+                psk: b"psk",
+                psk_id: b"",
+            },
+        ] {
+            assert_eq!(
+                hpke.seal(&pk, b"", b"", b"", Some(inconsistent))
+                    .unwrap_err(),
+                HpkeError::InconsistentPsk
+            );
+        }
+    }
+}
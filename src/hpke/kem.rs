@@ -0,0 +1,336 @@
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha3::Shake256;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+
+use crate::constants::KyberParams;
+use crate::hpke::primitives::{HpkeError, HpkeKdf};
+use crate::hybrid::ecdh::{Curve, EcdhError, EcdhKeyPair};
+use crate::hybrid::xwing::{self, XWing};
+use crate::kyber::kem_scheme::{MlKem, ParameterSet};
+use crate::kyber::keys::{DecapsulationKey, EncapsulationKey};
+
+/// KEM identifiers : the DHKEMs of RFC 9180 (Section 7.1) and the post-quantum
+/// KEMs of draft-ietf-hpke-pq
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HpkeKem {
+    /// 0x0010 : DHKEM(P-256, HKDF-SHA256)
+    DhKemP256HkdfSha256,
+    /// 0x0011 : DHKEM(P-384, HKDF-SHA384)
+    DhKemP384HkdfSha384,
+    /// 0x0020 : DHKEM(X25519, HKDF-SHA256)
+    DhKemX25519HkdfSha256,
+    /// 0x0040 : ML-KEM-512
+    MlKem512,
+    /// 0x0041 : ML-KEM-768
+    MlKem768,
+    /// 0x0042 : ML-KEM-1024
+    MlKem1024,
+    /// 0x647a : ML-KEM-768 + X25519, i.e. X-Wing
+    MlKem768X25519,
+}
+
+impl HpkeKem {
+    pub const ALL: [HpkeKem; 7] = [
+        HpkeKem::DhKemP256HkdfSha256,
+        HpkeKem::DhKemP384HkdfSha384,
+        HpkeKem::DhKemX25519HkdfSha256,
+        HpkeKem::MlKem512,
+        HpkeKem::MlKem768,
+        HpkeKem::MlKem1024,
+        HpkeKem::MlKem768X25519,
+    ];
+
+    pub fn id(&self) -> u16 {
+        match self {
+            HpkeKem::DhKemP256HkdfSha256 => 0x0010,
+            HpkeKem::DhKemP384HkdfSha384 => 0x0011,
+            HpkeKem::DhKemX25519HkdfSha256 => 0x0020,
+            HpkeKem::MlKem512 => 0x0040,
+            HpkeKem::MlKem768 => 0x0041,
+            HpkeKem::MlKem1024 => 0x0042,
+            HpkeKem::MlKem768X25519 => 0x647a,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        HpkeKem::ALL.into_iter().find(|kem| kem.id() == id)
+    }
+
+    /// Curve and KDF of the DHKEMs
+    fn dh(&self) -> Option<(Curve, HpkeKdf)> {
+        match self {
+            HpkeKem::DhKemP256HkdfSha256 => Some((Curve::P256, HpkeKdf::HkdfSha256)),
+            HpkeKem::DhKemP384HkdfSha384 => Some((Curve::P384, HpkeKdf::HkdfSha384)),
+            HpkeKem::DhKemX25519HkdfSha256 => Some((Curve::X25519, HpkeKdf::HkdfSha256)),
+            _ => None,
+        }
+    }
+
+    fn parameter_set(&self) -> Option<ParameterSet> {
+        match self {
+            HpkeKem::MlKem512 => Some(ParameterSet::MlKem512),
+            HpkeKem::MlKem768 => Some(ParameterSet::MlKem768),
+            HpkeKem::MlKem1024 => Some(ParameterSet::MlKem1024),
+            _ => None,
+        }
+    }
+
+    /// Length of the KEM shared secret
+    pub fn n_secret(&self) -> usize {
+        match self.dh() {
+            Some((_, kdf)) => kdf.nh(),
+            None => 32,
+        }
+    }
+
+    /// Length of the encapsulated key
+    pub fn n_enc(&self) -> usize {
+        match (self.dh(), self.parameter_set()) {
+            (Some((curve, _)), _) => curve.public_len(),
+            (_, Some(set)) => set.ciphertext_len(),
+            _ => xwing::CIPHERTEXT_LEN,
+        }
+    }
+
+    /// Length of a serialized public key
+    pub fn n_pk(&self) -> usize {
+        match (self.dh(), self.parameter_set()) {
+            (Some((curve, _)), _) => curve.public_len(),
+            (_, Some(set)) => set.ek_len(),
+            _ => xwing::PUBLIC_KEY_LEN,
+        }
+    }
+
+    /// Length of a serialized private key : the scalar of the DHKEMs, the seed d || z
+    /// of ML-KEM and the 32-byte seed of X-Wing
+    pub fn n_sk(&self) -> usize {
+        match (self.dh(), self.parameter_set()) {
+            (Some((curve, _)), _) => curve.secret_len(),
+            (_, Some(_)) => 64,
+            _ => xwing::SECRET_KEY_LEN,
+        }
+    }
+
+    /// Length of the randomness consumed by `encap_derand` : the ephemeral ikm of the
+    /// DHKEMs, the message m of ML-KEM and the eseed of X-Wing
+    pub fn n_encap_randomness(&self) -> usize {
+        match (self.dh(), self.parameter_set()) {
+            (Some((curve, _)), _) => curve.secret_len(),
+            (_, Some(_)) => 32,
+            _ => 64,
+        }
+    }
+
+    /// suite_id = "KEM" || I2OSP(kem_id, 2)
+    fn suite_id(&self) -> Vec<u8> {
+        [b"KEM".as_slice(), &self.id().to_be_bytes()].concat()
+    }
+
+    /// GenerateKeyPair() : returns (sk, pk)
+    pub fn generate_key_pair(&self) -> (Vec<u8>, Vec<u8>) {
+        let mut ikm = vec![0u8; self.n_sk()];
+        OsRng.fill_bytes(&mut ikm);
+        self.derive_key_pair(&ikm)
+            .expect("DeriveKeyPair fails with negligible probability")
+    }
+
+    /// DeriveKeyPair(ikm) : returns (sk, pk)
+    ///
+    /// Section 7.1.3 RFC 9180 for the DHKEMs. ML-KEM runs KeyGen_internal on
+    /// SHAKE256(ikm, 64) = d || z, and X-Wing runs GenerateKeyPairDerand on
+    /// SHAKE256(ikm, 32).
+    pub fn derive_key_pair(&self, ikm: &[u8]) -> Result<(Vec<u8>, Vec<u8>), HpkeError> {
+        if let Some((curve, kdf)) = self.dh() {
+            let suite_id = self.suite_id();
+            let dkp_prk = kdf.labeled_extract(&suite_id, b"", b"dkp_prk", ikm);
+            let key_pair = if curve == Curve::X25519 {
+                let sk = kdf.labeled_expand(&suite_id, &dkp_prk, b"sk", b"", 32)?;
+                EcdhKeyPair::from_secret_bytes(curve, &sk).map_err(|_| HpkeError::DeriveKeyPair)?
+            } else {
+                // Rejection sampling, bitmask 0xff for P-256 and P-384
+                (0..=255u8)
+                    .find_map(|counter| {
+                        let candidate = kdf
+                            .labeled_expand(
+                                &suite_id,
+                                &dkp_prk,
+                                b"candidate",
+                                &[counter],
+                                curve.secret_len(),
+                            )
+                            .ok()?;
+                        EcdhKeyPair::from_secret_bytes(curve, &candidate).ok()
+                    })
+                    .ok_or(HpkeError::DeriveKeyPair)?
+            };
+            return Ok((key_pair.secret_bytes(), key_pair.public_bytes().to_vec()));
+        }
+
+        let mut shake = Shake256::default();
+        shake.update(ikm);
+        let mut reader = shake.finalize_xof();
+        match self.parameter_set() {
+            Some(set) => {
+                let mut seed = [0u8; 64];
+                reader.read(&mut seed);
+                let dk = DecapsulationKey::from_seed(set, &seed);
+                Ok((seed.to_vec(), dk.encapsulation_key().as_bytes().to_vec()))
+            }
+            None => {
+                let mut sk = [0u8; 32];
+                reader.read(&mut sk);
+                let (sk, pk) = XWing::new().key_gen_derand(&sk);
+                Ok((sk.to_vec(), pk))
+            }
+        }
+    }
+
+    /// Public key of a serialized private key
+    pub fn public_key(&self, sk: &[u8]) -> Result<Vec<u8>, HpkeError> {
+        if sk.len() != self.n_sk() {
+            return Err(HpkeError::InvalidPrivateKey);
+        }
+        if let Some((curve, _)) = self.dh() {
+            let key_pair = EcdhKeyPair::from_secret_bytes(curve, sk)
+                .map_err(|_| HpkeError::InvalidPrivateKey)?;
+            return Ok(key_pair.public_bytes().to_vec());
+        }
+        match self.parameter_set() {
+            Some(set) => {
+                let dk = DecapsulationKey::from_seed(set, sk.try_into().unwrap());
+                Ok(dk.encapsulation_key().as_bytes().to_vec())
+            }
+            None => Ok(XWing::new().key_gen_derand(sk.try_into().unwrap()).1),
+        }
+    }
+
+    /// Encap(pkR) : returns (shared_secret, enc)
+    pub fn encap(&self, pk_r: &[u8]) -> Result<(Vec<u8>, Vec<u8>), HpkeError> {
+        let mut randomness = vec![0u8; self.n_encap_randomness()];
+        OsRng.fill_bytes(&mut randomness);
+        self.encap_derand(pk_r, &randomness)
+    }
+
+    /// Deterministic Encap(pkR) with the randomness described in `n_encap_randomness`
+    pub fn encap_derand(
+        &self,
+        pk_r: &[u8],
+        randomness: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), HpkeError> {
+        if randomness.len() != self.n_encap_randomness() {
+            return Err(HpkeError::InvalidRandomness);
+        }
+        if pk_r.len() != self.n_pk() {
+            return Err(HpkeError::InvalidPublicKey);
+        }
+        if let Some((curve, _)) = self.dh() {
+            let (sk_e, pk_e) = self.derive_key_pair(randomness)?;
+            let dh = EcdhKeyPair::from_secret_bytes(curve, &sk_e)
+                .unwrap()
+                .diffie_hellman(pk_r)
+                .map_err(|_| HpkeError::InvalidPublicKey)?;
+            let kem_context = [pk_e.as_slice(), pk_r].concat();
+            return Ok((self.extract_and_expand(&dh, &kem_context), pk_e));
+        }
+        match self.parameter_set() {
+            Some(set) => {
+                let ek = EncapsulationKey::from_bytes(set, pk_r)
+                    .map_err(|_| HpkeError::InvalidPublicKey)?;
+                let m: [u8; 32] = randomness.try_into().unwrap();
+                Ok(
+                    MlKem::<KyberParams>::from_parameter_set(set)
+                        .encaps_internal(ek.as_bytes(), &m),
+                )
+            }
+            None => XWing::new()
+                .encaps_derand(pk_r, randomness.try_into().unwrap())
+                .map_err(|_| HpkeError::InvalidPublicKey),
+        }
+    }
+
+    /// Decap(enc, skR) : returns shared_secret
+    pub fn decap(&self, enc: &[u8], sk_r: &[u8]) -> Result<Vec<u8>, HpkeError> {
+        if enc.len() != self.n_enc() {
+            return Err(HpkeError::InvalidEncapsulatedKey);
+        }
+        if sk_r.len() != self.n_sk() {
+            return Err(HpkeError::InvalidPrivateKey);
+        }
+        if let Some((curve, _)) = self.dh() {
+            let key_pair = EcdhKeyPair::from_secret_bytes(curve, sk_r)
+                .map_err(|_| HpkeError::InvalidPrivateKey)?;
+            let dh = key_pair.diffie_hellman(enc).map_err(|err| match err {
+                EcdhError::InvalidSecret => HpkeError::InvalidPrivateKey,
+                EcdhError::InvalidPublicKey => HpkeError::InvalidEncapsulatedKey,
+            })?;
+            let kem_context = [enc, key_pair.public_bytes()].concat();
+            return Ok(self.extract_and_expand(&dh, &kem_context));
+        }
+        match self.parameter_set() {
+            Some(set) => Ok(DecapsulationKey::from_seed(set, sk_r.try_into().unwrap()).decaps(enc)),
+            None => XWing::new()
+                .decaps(sk_r.try_into().unwrap(), enc)
+                .map_err(|_| HpkeError::InvalidEncapsulatedKey),
+        }
+    }
+
+    /// ExtractAndExpand(dh, kem_context) of the DHKEMs
+    fn extract_and_expand(&self, dh: &[u8], kem_context: &[u8]) -> Vec<u8> {
+        let (_, kdf) = self.dh().unwrap();
+        let suite_id = self.suite_id();
+        let eae_prk = kdf.labeled_extract(&suite_id, b"", b"eae_prk", dh);
+        kdf.labeled_expand(
+            &suite_id,
+            &eae_prk,
+            b"shared_secret",
+            kem_context,
+            self.n_secret(),
+        )
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for kem in HpkeKem::ALL {
+            assert_eq!(HpkeKem::from_id(kem.id()), Some(kem));
+
+            let (sk, pk) = kem.derive_key_pair(b"input keying material").unwrap();
+            assert_eq!((sk.len(), pk.len()), (kem.n_sk(), kem.n_pk()));
+            assert_eq!(
+                kem.derive_key_pair(b"input keying material").unwrap(),
+                (sk.clone(), pk.clone())
+            );
+            assert_eq!(kem.public_key(&sk).unwrap(), pk);
+
+            let (ss, enc) = kem.encap(&pk).unwrap();
+            assert_eq!((ss.len(), enc.len()), (kem.n_secret(), kem.n_enc()));
+            assert_eq!(kem.decap(&enc, &sk).unwrap(), ss);
+
+            assert_eq!(kem.encap(&pk[1..]), Err(HpkeError::InvalidPublicKey));
+            let randomness = vec![0u8; kem.n_encap_randomness() + 1];
+            assert_eq!(
+                kem.encap_derand(&pk, &randomness),
+                Err(HpkeError::InvalidRandomness)
+            );
+            assert_eq!(
+                kem.decap(&enc[1..], &sk),
+                Err(HpkeError::InvalidEncapsulatedKey)
+            );
+            assert_eq!(kem.decap(&enc, &sk[1..]), Err(HpkeError::InvalidPrivateKey));
+        }
+
+        // X25519 low-order point (RFC 7748 : all-zero shared secret)
+        let kem = HpkeKem::DhKemX25519HkdfSha256;
+        let (sk, _) = kem.generate_key_pair();
+        assert_eq!(
+            kem.decap(&[0u8; 32], &sk),
+            Err(HpkeError::InvalidEncapsulatedKey)
+        );
+    }
+}
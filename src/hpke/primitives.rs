@@ -0,0 +1,258 @@
+use core::fmt;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use sha2::{Sha256, Sha384};
+
+/// Prefix of every labeled KDF input (Section 4 RFC 9180)
+const HPKE_VERSION: &[u8; 7] = b"HPKE-v1";
+
+/// Errors raised by HPKE (Section 5 RFC 9180)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HpkeError {
+    /// The recipient public key has the wrong length or is invalid
+    InvalidPublicKey,
+    /// The private key has the wrong length or is invalid
+    InvalidPrivateKey,
+    /// The encapsulated key has the wrong length or is invalid
+    InvalidEncapsulatedKey,
+    /// DeriveKeyPair found no valid private key
+    DeriveKeyPair,
+    /// The randomness given to Encap does not have the length of the KEM
+    InvalidRandomness,
+    /// psk and psk_id must both be non-empty
+    InconsistentPsk,
+    /// AEAD decryption failed
+    Open,
+    /// The sequence number is exhausted
+    MessageLimitReached,
+    /// The requested exported secret is longer than 255 * Nh bytes
+    ExportTooLong,
+    /// The pseudorandom key given to Expand is shorter than Nh bytes
+    InvalidPrk,
+}
+
+impl fmt::Display for HpkeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HpkeError::InvalidPublicKey => write!(f, "invalid HPKE public key"),
+            HpkeError::InvalidPrivateKey => write!(f, "invalid HPKE private key"),
+            HpkeError::InvalidEncapsulatedKey => write!(f, "invalid HPKE encapsulated key"),
+            HpkeError::DeriveKeyPair => write!(f, "HPKE key pair derivation failed"),
+            HpkeError::InvalidRandomness => write!(f, "invalid HPKE encapsulation randomness"),
+            HpkeError::InconsistentPsk => write!(f, "inconsistent HPKE PSK inputs"),
+            HpkeError::Open => write!(f, "HPKE decryption failed"),
+            HpkeError::MessageLimitReached => write!(f, "HPKE message limit reached"),
+            HpkeError::ExportTooLong => write!(f, "HPKE exported secret too long"),
+            HpkeError::InvalidPrk => write!(f, "HPKE pseudorandom key too short"),
+        }
+    }
+}
+
+impl std::error::Error for HpkeError {}
+
+/// KDF identifiers (Section 7.2 RFC 9180)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HpkeKdf {
+    /// 0x0001
+    HkdfSha256,
+    /// 0x0002
+    HkdfSha384,
+}
+
+impl HpkeKdf {
+    pub const ALL: [HpkeKdf; 2] = [HpkeKdf::HkdfSha256, HpkeKdf::HkdfSha384];
+
+    pub fn id(&self) -> u16 {
+        match self {
+            HpkeKdf::HkdfSha256 => 0x0001,
+            HpkeKdf::HkdfSha384 => 0x0002,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        HpkeKdf::ALL.into_iter().find(|kdf| kdf.id() == id)
+    }
+
+    /// Output size of the extraction
+    pub fn nh(&self) -> usize {
+        match self {
+            HpkeKdf::HkdfSha256 => 32,
+            HpkeKdf::HkdfSha384 => 48,
+        }
+    }
+
+    pub fn extract(&self, salt: &[u8], ikm: &[u8]) -> Vec<u8> {
+        match self {
+            HpkeKdf::HkdfSha256 => Hkdf::<Sha256>::extract(Some(salt), ikm).0.to_vec(),
+            HpkeKdf::HkdfSha384 => Hkdf::<Sha384>::extract(Some(salt), ikm).0.to_vec(),
+        }
+    }
+
+    pub fn expand(&self, prk: &[u8], info: &[u8], len: usize) -> Result<Vec<u8>, HpkeError> {
+        let mut okm = vec![0u8; len];
+        let result = match self {
+            HpkeKdf::HkdfSha256 => Hkdf::<Sha256>::from_prk(prk)
+                .map_err(|_| HpkeError::InvalidPrk)?
+                .expand(info, &mut okm),
+            HpkeKdf::HkdfSha384 => Hkdf::<Sha384>::from_prk(prk)
+                .map_err(|_| HpkeError::InvalidPrk)?
+                .expand(info, &mut okm),
+        };
+        result.map_err(|_| HpkeError::ExportTooLong)?;
+        Ok(okm)
+    }
+
+    /// LabeledExtract(salt, label, ikm) = Extract(salt, "HPKE-v1" || suite_id || label || ikm)
+    pub fn labeled_extract(
+        &self,
+        suite_id: &[u8],
+        salt: &[u8],
+        label: &[u8],
+        ikm: &[u8],
+    ) -> Vec<u8> {
+        let labeled_ikm = [HPKE_VERSION.as_slice(), suite_id, label, ikm].concat();
+        self.extract(salt, &labeled_ikm)
+    }
+
+    /// LabeledExpand(prk, label, info, L) =
+    /// Expand(prk, I2OSP(L, 2) || "HPKE-v1" || suite_id || label || info, L)
+    pub fn labeled_expand(
+        &self,
+        suite_id: &[u8],
+        prk: &[u8],
+        label: &[u8],
+        info: &[u8],
+        len: usize,
+    ) -> Result<Vec<u8>, HpkeError> {
+        let len_bytes = u16::try_from(len)
+            .map_err(|_| HpkeError::ExportTooLong)?
+            .to_be_bytes();
+        let labeled_info = [&len_bytes, HPKE_VERSION.as_slice(), suite_id, label, info].concat();
+        self.expand(prk, &labeled_info, len)
+    }
+}
+
+/// AEAD identifiers (Section 7.3 RFC 9180)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HpkeAead {
+    /// 0x0001
+    Aes128Gcm,
+    /// 0x0002
+    Aes256Gcm,
+    /// 0x0003
+    ChaCha20Poly1305,
+}
+
+impl HpkeAead {
+    pub const ALL: [HpkeAead; 3] = [
+        HpkeAead::Aes128Gcm,
+        HpkeAead::Aes256Gcm,
+        HpkeAead::ChaCha20Poly1305,
+    ];
+
+    pub fn id(&self) -> u16 {
+        match self {
+            HpkeAead::Aes128Gcm => 0x0001,
+            HpkeAead::Aes256Gcm => 0x0002,
+            HpkeAead::ChaCha20Poly1305 => 0x0003,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<Self> {
+        HpkeAead::ALL.into_iter().find(|aead| aead.id() == id)
+    }
+
+    /// Key length
+    pub fn nk(&self) -> usize {
+        match self {
+            HpkeAead::Aes128Gcm => 16,
+            HpkeAead::Aes256Gcm | HpkeAead::ChaCha20Poly1305 => 32,
+        }
+    }
+
+    /// Nonce length
+    pub fn nn(&self) -> usize {
+        12
+    }
+
+    /// Tag length
+    pub fn nt(&self) -> usize {
+        16
+    }
+
+    /// Seal(key, nonce, aad, pt) : returns ct || tag
+    pub fn seal(&self, key: &[u8], nonce: &[u8], aad: &[u8], pt: &[u8]) -> Vec<u8> {
+        let payload = Payload { msg: pt, aad };
+        let ct = match self {
+            HpkeAead::Aes128Gcm => Aes128Gcm::new_from_slice(key)
+                .expect("key of length Nk")
+                .encrypt(nonce.into(), payload),
+            HpkeAead::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+                .expect("key of length Nk")
+                .encrypt(nonce.into(), payload),
+            HpkeAead::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)
+                .expect("key of length Nk")
+                .encrypt(nonce.into(), payload),
+        };
+        ct.expect("plaintext too long for the AEAD")
+    }
+
+    /// Open(key, nonce, aad, ct)
+    pub fn open(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        ct: &[u8],
+    ) -> Result<Vec<u8>, HpkeError> {
+        let payload = Payload { msg: ct, aad };
+        let pt = match self {
+            HpkeAead::Aes128Gcm => Aes128Gcm::new_from_slice(key)
+                .expect("key of length Nk")
+                .decrypt(nonce.into(), payload),
+            HpkeAead::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+                .expect("key of length Nk")
+                .decrypt(nonce.into(), payload),
+            HpkeAead::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)
+                .expect("key of length Nk")
+                .decrypt(nonce.into(), payload),
+        };
+        pt.map_err(|_| HpkeError::Open)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for kdf in HpkeKdf::ALL {
+            assert_eq!(HpkeKdf::from_id(kdf.id()), Some(kdf));
+            let prk = kdf.labeled_extract(b"KEM\x00\x20", b"", b"dkp_prk", b"ikm");
+            assert_eq!(prk.len(), kdf.nh());
+            assert!(
+                kdf.labeled_expand(b"", &prk, b"sec", b"", 255 * kdf.nh())
+                    .is_ok()
+            );
+            assert_eq!(
+                kdf.labeled_expand(b"", &prk, b"sec", b"", 255 * kdf.nh() + 1),
+                Err(HpkeError::ExportTooLong)
+            );
+            assert_eq!(kdf.expand(&prk[1..], b"", 32), Err(HpkeError::InvalidPrk));
+        }
+
+        for aead in HpkeAead::ALL {
+            assert_eq!(HpkeAead::from_id(aead.id()), Some(aead));
+            let key = vec![7u8; aead.nk()];
+            let nonce = [1u8; 12];
+            let ct = aead.seal(&key, &nonce, b"aad", b"plaintext");
+            assert_eq!(ct.len(), 9 + aead.nt());
+            assert_eq!(aead.open(&key, &nonce, b"aad", &ct).unwrap(), b"plaintext");
+            assert_eq!(aead.open(&key, &nonce, b"bad", &ct), Err(HpkeError::Open));
+        }
+    }
+}
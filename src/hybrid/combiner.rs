@@ -0,0 +1,297 @@
+use core::fmt;
+
+use crate::constants::PolyParams;
+use crate::hash::HashSuite;
+use crate::hybrid::ecdh::{Curve, EcdhError, EcdhKeyPair};
+use crate::kdf::{FixedInfo, Kdf, derive_keys};
+use crate::kyber::kem_scheme::MlKem;
+use crate::kyber::keys::check_modulus;
+
+/// Length in bytes of the key produced by the combined KEM
+pub const COMBINED_KEY_LEN: usize = 32;
+
+/// Errors raised by a KEM on malformed inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KemError {
+    InvalidEncapsulationKey,
+    InvalidDecapsulationKey,
+    InvalidCiphertext,
+}
+
+impl fmt::Display for KemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KemError::InvalidEncapsulationKey => write!(f, "invalid encapsulation key"),
+            KemError::InvalidDecapsulationKey => write!(f, "invalid decapsulation key"),
+            KemError::InvalidCiphertext => write!(f, "invalid ciphertext"),
+        }
+    }
+}
+
+impl std::error::Error for KemError {}
+
+/// Key encapsulation mechanism with fixed-length keys and ciphertexts, following the
+/// key_gen/encaps/decaps API of `MlKem`
+pub trait Kem {
+    fn ek_len(&self) -> usize;
+    fn dk_len(&self) -> usize;
+    fn ciphertext_len(&self) -> usize;
+
+    /// Returns (ek, dk)
+    fn key_gen(&self) -> (Vec<u8>, Vec<u8>);
+
+    /// Returns (K, c)
+    fn encaps(&self, ek: &[u8]) -> Result<(Vec<u8>, Vec<u8>), KemError>;
+
+    /// Returns K
+    fn decaps(&self, dk: &[u8], c: &[u8]) -> Result<Vec<u8>, KemError>;
+}
+
+/// ML-KEM with the input checks of Section 7 FIPS 203
+impl<P: PolyParams, H: HashSuite> Kem for MlKem<P, H> {
+    fn ek_len(&self) -> usize {
+        self.0.ek_len()
+    }
+
+    fn dk_len(&self) -> usize {
+        768 * self.0.k + 96
+    }
+
+    fn ciphertext_len(&self) -> usize {
+        self.0.ciphertext_len()
+    }
+
+    fn key_gen(&self) -> (Vec<u8>, Vec<u8>) {
+        MlKem::key_gen(self)
+    }
+
+    fn encaps(&self, ek: &[u8]) -> Result<(Vec<u8>, Vec<u8>), KemError> {
+        if ek.len() != Kem::ek_len(self) {
+            return Err(KemError::InvalidEncapsulationKey);
+        }
+        check_modulus(&ek[..384 * self.0.k]).map_err(|_| KemError::InvalidEncapsulationKey)?;
+        Ok(MlKem::encaps(self, ek))
+    }
+
+    fn decaps(&self, dk: &[u8], c: &[u8]) -> Result<Vec<u8>, KemError> {
+        if c.len() != Kem::ciphertext_len(self) {
+            return Err(KemError::InvalidCiphertext);
+        }
+        let k = self.0.k;
+        if dk.len() != Kem::dk_len(self)
+            || H::h(&dk[384 * k..768 * k + 32]) != dk[768 * k + 32..768 * k + 64]
+        {
+            return Err(KemError::InvalidDecapsulationKey);
+        }
+        Ok(MlKem::decaps(self, dk, c))
+    }
+}
+
+/// Ephemeral-static ECDH seen as a KEM : ek is the static public key, c the ephemeral
+/// public key and K the raw ECDH shared secret. K must go through a KDF, which is
+/// what `CombinedKem` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcdhKem {
+    pub curve: Curve,
+}
+
+impl EcdhKem {
+    pub fn new(curve: Curve) -> Self {
+        EcdhKem { curve }
+    }
+}
+
+impl Kem for EcdhKem {
+    fn ek_len(&self) -> usize {
+        self.curve.public_len()
+    }
+
+    fn dk_len(&self) -> usize {
+        self.curve.secret_len()
+    }
+
+    fn ciphertext_len(&self) -> usize {
+        self.curve.public_len()
+    }
+
+    fn key_gen(&self) -> (Vec<u8>, Vec<u8>) {
+        let key_pair = EcdhKeyPair::generate(self.curve);
+        (key_pair.public_bytes().to_vec(), key_pair.secret_bytes())
+    }
+
+    fn encaps(&self, ek: &[u8]) -> Result<(Vec<u8>, Vec<u8>), KemError> {
+        let ephemeral = EcdhKeyPair::generate(self.curve);
+        let k = ephemeral
+            .diffie_hellman(ek)
+            .map_err(|_| KemError::InvalidEncapsulationKey)?;
+        Ok((k, ephemeral.public_bytes().to_vec()))
+    }
+
+    fn decaps(&self, dk: &[u8], c: &[u8]) -> Result<Vec<u8>, KemError> {
+        let key_pair = EcdhKeyPair::from_secret_bytes(self.curve, dk)
+            .map_err(|_| KemError::InvalidDecapsulationKey)?;
+        key_pair.diffie_hellman(c).map_err(|err| match err {
+            EcdhError::InvalidSecret => KemError::InvalidDecapsulationKey,
+            EcdhError::InvalidPublicKey => KemError::InvalidCiphertext,
+        })
+    }
+}
+
+/// Hybrid of two KEMs, combined with a key derivation method of SP 800-56C as
+/// recommended by SP 800-227 :
+///
+/// K = KDF(K_1 || K_2, FixedInfo(label, ek_1 || ek_2, c_1 || c_2))
+///
+/// so that the key is bound to both ciphertexts and both encapsulation keys.
+/// Keys and ciphertexts are the concatenations of the components, and the
+/// decapsulation key is dk_1 || dk_2 || ek_1 || ek_2, so that decapsulation can
+/// rebuild the FixedInfo. The combined KEM is itself a `Kem`.
+pub struct CombinedKem<A: Kem, B: Kem> {
+    pub first: A,
+    pub second: B,
+    pub kdf: Kdf,
+    pub label: Vec<u8>,
+}
+
+impl<A: Kem, B: Kem> CombinedKem<A, B> {
+    pub fn new(first: A, second: B, kdf: Kdf, label: &[u8]) -> Self {
+        CombinedKem {
+            first,
+            second,
+            kdf,
+            label: label.to_vec(),
+        }
+    }
+
+    fn combine(&self, k_1: &[u8], k_2: &[u8], ek: &[u8], c: &[u8]) -> Vec<u8> {
+        let info = FixedInfo {
+            ek,
+            ciphertext: c,
+            ..FixedInfo::new(&self.label)
+        };
+        let z = [k_1, k_2].concat();
+        derive_keys(self.kdf, &z, &[], &info, &[COMBINED_KEY_LEN])
+            .expect("every KDF outputs at least 32 bytes")
+            .remove(0)
+    }
+}
+
+impl<A: Kem, B: Kem> Kem for CombinedKem<A, B> {
+    fn ek_len(&self) -> usize {
+        self.first.ek_len() + self.second.ek_len()
+    }
+
+    fn dk_len(&self) -> usize {
+        self.first.dk_len() + self.second.dk_len() + self.ek_len()
+    }
+
+    fn ciphertext_len(&self) -> usize {
+        self.first.ciphertext_len() + self.second.ciphertext_len()
+    }
+
+    fn key_gen(&self) -> (Vec<u8>, Vec<u8>) {
+        let (ek_1, dk_1) = self.first.key_gen();
+        let (ek_2, dk_2) = self.second.key_gen();
+        let ek = [ek_1, ek_2].concat();
+        let dk = [dk_1.as_slice(), &dk_2, &ek].concat();
+        (ek, dk)
+    }
+
+    fn encaps(&self, ek: &[u8]) -> Result<(Vec<u8>, Vec<u8>), KemError> {
+        if ek.len() != self.ek_len() {
+            return Err(KemError::InvalidEncapsulationKey);
+        }
+        let (ek_1, ek_2) = ek.split_at(self.first.ek_len());
+        let (k_1, c_1) = self.first.encaps(ek_1)?;
+        let (k_2, c_2) = self.second.encaps(ek_2)?;
+        let c = [c_1, c_2].concat();
+        Ok((self.combine(&k_1, &k_2, ek, &c), c))
+    }
+
+    fn decaps(&self, dk: &[u8], c: &[u8]) -> Result<Vec<u8>, KemError> {
+        if dk.len() != self.dk_len() {
+            return Err(KemError::InvalidDecapsulationKey);
+        }
+        if c.len() != self.ciphertext_len() {
+            return Err(KemError::InvalidCiphertext);
+        }
+        let (dk_1, rest) = dk.split_at(self.first.dk_len());
+        let (dk_2, ek) = rest.split_at(self.second.dk_len());
+        let (c_1, c_2) = c.split_at(self.first.ciphertext_len());
+        let k_1 = self.first.decaps(dk_1, c_1)?;
+        let k_2 = self.second.decaps(dk_2, c_2)?;
+        Ok(self.combine(&k_1, &k_2, ek, c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::KyberParams;
+    use crate::kyber::kem_scheme::ParameterSet;
+
+    #[test]
+    fn basics() {
+        let hybrid = CombinedKem::new(
+            MlKem::<KyberParams>::from_parameter_set(ParameterSet::MlKem1024),
+            EcdhKem::new(Curve::P384),
+            Kdf::OneStepKmac256,
+            b"ML-KEM-1024+P-384",
+        );
+        let (ek, dk) = hybrid.key_gen();
+        assert_eq!((ek.len(), dk.len()), (1568 + 97, 3168 + 48 + 1568 + 97));
+        let (k, c) = hybrid.encaps(&ek).unwrap();
+        assert_eq!(c.len(), 1568 + 97);
+        assert_eq!(k.len(), COMBINED_KEY_LEN);
+        assert_eq!(hybrid.decaps(&dk, &c).unwrap(), k);
+
+        // the key depends on both ciphertexts
+        let mut tampered = c.clone();
+        tampered[0] ^= 1;
+        assert_ne!(hybrid.decaps(&dk, &tampered).unwrap(), k);
+        let mut off_curve = c.clone();
+        off_curve[c.len() - 1] ^= 1;
+        assert_eq!(
+            hybrid.decaps(&dk, &off_curve),
+            Err(KemError::InvalidCiphertext)
+        );
+
+        // the key depends on the label and on the KDF
+        let other = CombinedKem::new(
+            MlKem::<KyberParams>::from_parameter_set(ParameterSet::MlKem1024),
+            EcdhKem::new(Curve::P384),
+            Kdf::HkdfSha384,
+            b"ML-KEM-1024+P-384",
+        );
+        assert_ne!(other.decaps(&dk, &c).unwrap(), k);
+
+        // combiners nest, and the classical side can come first
+        let nested = CombinedKem::new(
+            EcdhKem::new(Curve::X25519),
+            CombinedKem::new(
+                MlKem::<KyberParams>::from_parameter_set(ParameterSet::MlKem512),
+                EcdhKem::new(Curve::P256),
+                Kdf::OneStepSha3_256,
+                b"inner",
+            ),
+            Kdf::HkdfSha256,
+            b"outer",
+        );
+        let (ek, dk) = nested.key_gen();
+        let (k, c) = nested.encaps(&ek).unwrap();
+        assert_eq!(nested.decaps(&dk, &c).unwrap(), k);
+        assert_eq!(
+            nested.encaps(&ek[1..]),
+            Err(KemError::InvalidEncapsulationKey)
+        );
+
+        let ml_kem = MlKem::<KyberParams>::from_parameter_set(ParameterSet::MlKem768);
+        let (mut ek, _) = Kem::key_gen(&ml_kem);
+        ek[0] = 0xff;
+        ek[1] |= 0x0f;
+        assert_eq!(
+            Kem::encaps(&ml_kem, &ek),
+            Err(KemError::InvalidEncapsulationKey)
+        );
+    }
+}
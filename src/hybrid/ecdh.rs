@@ -0,0 +1,221 @@
+use core::fmt;
+
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rand::rngs::OsRng;
+use subtle::ConstantTimeEq;
+
+/// Elliptic curves used as the classical half of the hybrid constructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Curve {
+    X25519,
+    /// NIST P-256, public keys as uncompressed SEC1 points
+    P256,
+    /// NIST P-384, public keys as uncompressed SEC1 points
+    P384,
+}
+
+impl Curve {
+    pub fn secret_len(&self) -> usize {
+        match self {
+            Curve::X25519 | Curve::P256 => 32,
+            Curve::P384 => 48,
+        }
+    }
+
+    pub fn public_len(&self) -> usize {
+        match self {
+            Curve::X25519 => 32,
+            Curve::P256 => 65,
+            Curve::P384 => 97,
+        }
+    }
+
+    pub fn shared_secret_len(&self) -> usize {
+        self.secret_len()
+    }
+}
+
+/// Errors raised by the ECDH operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcdhError {
+    /// The private scalar is zero, too large or has the wrong length
+    InvalidSecret,
+    /// The peer public key has the wrong length, is not on the curve, or yields
+    /// an all-zero X25519 shared secret
+    InvalidPublicKey,
+}
+
+impl fmt::Display for EcdhError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EcdhError::InvalidSecret => write!(f, "invalid ECDH private key"),
+            EcdhError::InvalidPublicKey => write!(f, "invalid ECDH public key"),
+        }
+    }
+}
+
+impl std::error::Error for EcdhError {}
+
+#[derive(Clone)]
+enum Secret {
+    X25519(x25519_dalek::StaticSecret),
+    P256(p256::SecretKey),
+    P384(p384::SecretKey),
+}
+
+/// Ephemeral or static ECDH key pair
+#[derive(Clone)]
+pub struct EcdhKeyPair {
+    curve: Curve,
+    secret: Secret,
+    public: Vec<u8>,
+}
+
+impl EcdhKeyPair {
+    pub fn generate(curve: Curve) -> Self {
+        let secret = match curve {
+            Curve::X25519 => Secret::X25519(x25519_dalek::StaticSecret::random_from_rng(OsRng)),
+            Curve::P256 => Secret::P256(p256::SecretKey::random(&mut OsRng)),
+            Curve::P384 => Secret::P384(p384::SecretKey::random(&mut OsRng)),
+        };
+        EcdhKeyPair::from_secret(curve, secret)
+    }
+
+    /// Imports a private key : the 32-byte X25519 scalar, or the big-endian
+    /// scalar of P-256/P-384
+    pub fn from_secret_bytes(curve: Curve, bytes: &[u8]) -> Result<Self, EcdhError> {
+        if bytes.len() != curve.secret_len() {
+            return Err(EcdhError::InvalidSecret);
+        }
+        let secret = match curve {
+            Curve::X25519 => {
+                let scalar: [u8; 32] = bytes.try_into().unwrap();
+                Secret::X25519(x25519_dalek::StaticSecret::from(scalar))
+            }
+            Curve::P256 => Secret::P256(
+                p256::SecretKey::from_slice(bytes).map_err(|_| EcdhError::InvalidSecret)?,
+            ),
+            Curve::P384 => Secret::P384(
+                p384::SecretKey::from_slice(bytes).map_err(|_| EcdhError::InvalidSecret)?,
+            ),
+        };
+        Ok(EcdhKeyPair::from_secret(curve, secret))
+    }
+
+    fn from_secret(curve: Curve, secret: Secret) -> Self {
+        let public = match &secret {
+            Secret::X25519(s) => x25519_dalek::PublicKey::from(s).as_bytes().to_vec(),
+            Secret::P256(s) => s.public_key().to_encoded_point(false).as_bytes().to_vec(),
+            Secret::P384(s) => s.public_key().to_encoded_point(false).as_bytes().to_vec(),
+        };
+        EcdhKeyPair {
+            curve,
+            secret,
+            public,
+        }
+    }
+
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    pub fn public_bytes(&self) -> &[u8] {
+        &self.public
+    }
+
+    /// Private key in the encoding accepted by `from_secret_bytes`
+    pub fn secret_bytes(&self) -> Vec<u8> {
+        match &self.secret {
+            Secret::X25519(s) => s.to_bytes().to_vec(),
+            Secret::P256(s) => s.to_bytes().to_vec(),
+            Secret::P384(s) => s.to_bytes().to_vec(),
+        }
+    }
+
+    /// Computes the shared secret with the peer public key : the X25519 output, or
+    /// the x-coordinate of the shared point for P-256/P-384
+    pub fn diffie_hellman(&self, peer: &[u8]) -> Result<Vec<u8>, EcdhError> {
+        if peer.len() != self.curve.public_len() {
+            return Err(EcdhError::InvalidPublicKey);
+        }
+        match &self.secret {
+            Secret::X25519(s) => {
+                let peer: [u8; 32] = peer.try_into().unwrap();
+                let shared = s.diffie_hellman(&x25519_dalek::PublicKey::from(peer));
+                if bool::from(shared.as_bytes().ct_eq(&[0u8; 32])) {
+                    return Err(EcdhError::InvalidPublicKey);
+                }
+                Ok(shared.as_bytes().to_vec())
+            }
+            Secret::P256(s) => {
+                let peer = p256::PublicKey::from_sec1_bytes(peer)
+                    .map_err(|_| EcdhError::InvalidPublicKey)?;
+                let shared = p256::ecdh::diffie_hellman(s.to_nonzero_scalar(), peer.as_affine());
+                Ok(shared.raw_secret_bytes().to_vec())
+            }
+            Secret::P384(s) => {
+                let peer = p384::PublicKey::from_sec1_bytes(peer)
+                    .map_err(|_| EcdhError::InvalidPublicKey)?;
+                let shared = p384::ecdh::diffie_hellman(s.to_nonzero_scalar(), peer.as_affine());
+                Ok(shared.raw_secret_bytes().to_vec())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        // RFC 7748 Section 6.1
+        let alice = EcdhKeyPair::from_secret_bytes(
+            Curve::X25519,
+            &hex::decode("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a")
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(alice.public_bytes()),
+            "8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"
+        );
+        let bob_public =
+            hex::decode("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f")
+                .unwrap();
+        assert_eq!(
+            hex::encode(alice.diffie_hellman(&bob_public).unwrap()),
+            "4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742"
+        );
+        assert_eq!(
+            alice.diffie_hellman(&[0u8; 32]),
+            Err(EcdhError::InvalidPublicKey)
+        );
+
+        for curve in [Curve::X25519, Curve::P256, Curve::P384] {
+            let a = EcdhKeyPair::generate(curve);
+            let b = EcdhKeyPair::generate(curve);
+            assert_eq!(a.public_bytes().len(), curve.public_len());
+            let shared = a.diffie_hellman(b.public_bytes()).unwrap();
+            assert_eq!(shared.len(), curve.shared_secret_len());
+            assert_eq!(b.diffie_hellman(a.public_bytes()).unwrap(), shared);
+
+            let copy = EcdhKeyPair::from_secret_bytes(curve, &a.secret_bytes()).unwrap();
+            assert_eq!(copy.public_bytes(), a.public_bytes());
+            assert_eq!(
+                a.diffie_hellman(&a.public_bytes()[1..]),
+                Err(EcdhError::InvalidPublicKey)
+            );
+        }
+        let mut off_curve = EcdhKeyPair::generate(Curve::P256).public_bytes().to_vec();
+        off_curve[64] ^= 1;
+        assert_eq!(
+            EcdhKeyPair::generate(Curve::P256).diffie_hellman(&off_curve),
+            Err(EcdhError::InvalidPublicKey)
+        );
+        assert_eq!(
+            EcdhKeyPair::from_secret_bytes(Curve::P256, &[0u8; 32]).err(),
+            Some(EcdhError::InvalidSecret)
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod combiner;
+pub mod ecdh;
+pub mod tls;
+pub mod xwing;
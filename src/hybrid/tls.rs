@@ -0,0 +1,289 @@
+use core::fmt;
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+use crate::constants::KyberParams;
+use crate::hybrid::ecdh::{Curve, EcdhError, EcdhKeyPair};
+use crate::kyber::kem_scheme::{MlKem, ParameterSet};
+use crate::kyber::keys::{DecapsulationKey, EncapsulationKey, KeyError};
+
+/// Hybrid ECDHE-MLKEM key exchange groups for TLS 1.3 (draft-ietf-tls-ecdhe-mlkem)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HybridGroup {
+    /// 0x11EC : ML-KEM-768 first, then X25519
+    X25519MlKem768,
+    /// 0x11EB : P-256 first, then ML-KEM-768
+    SecP256r1MlKem768,
+    /// 0x11ED : P-384 first, then ML-KEM-1024
+    SecP384r1MlKem1024,
+}
+
+impl HybridGroup {
+    pub const ALL: [HybridGroup; 3] = [
+        HybridGroup::X25519MlKem768,
+        HybridGroup::SecP256r1MlKem768,
+        HybridGroup::SecP384r1MlKem1024,
+    ];
+
+    /// NamedGroup codepoint
+    pub fn codepoint(&self) -> u16 {
+        match self {
+            HybridGroup::X25519MlKem768 => 0x11ec,
+            HybridGroup::SecP256r1MlKem768 => 0x11eb,
+            HybridGroup::SecP384r1MlKem1024 => 0x11ed,
+        }
+    }
+
+    pub fn from_codepoint(codepoint: u16) -> Option<Self> {
+        HybridGroup::ALL
+            .into_iter()
+            .find(|group| group.codepoint() == codepoint)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HybridGroup::X25519MlKem768 => "X25519MLKEM768",
+            HybridGroup::SecP256r1MlKem768 => "SecP256r1MLKEM768",
+            HybridGroup::SecP384r1MlKem1024 => "SecP384r1MLKEM1024",
+        }
+    }
+
+    pub fn parameter_set(&self) -> ParameterSet {
+        match self {
+            HybridGroup::X25519MlKem768 | HybridGroup::SecP256r1MlKem768 => ParameterSet::MlKem768,
+            HybridGroup::SecP384r1MlKem1024 => ParameterSet::MlKem1024,
+        }
+    }
+
+    pub fn curve(&self) -> Curve {
+        match self {
+            HybridGroup::X25519MlKem768 => Curve::X25519,
+            HybridGroup::SecP256r1MlKem768 => Curve::P256,
+            HybridGroup::SecP384r1MlKem1024 => Curve::P384,
+        }
+    }
+
+    /// Only X25519MLKEM768 puts the ML-KEM component first
+    fn mlkem_first(&self) -> bool {
+        *self == HybridGroup::X25519MlKem768
+    }
+
+    /// Length of the key_exchange field of the client KeyShareEntry
+    pub fn client_share_len(&self) -> usize {
+        self.parameter_set().ek_len() + self.curve().public_len()
+    }
+
+    /// Length of the key_exchange field of the server KeyShareEntry
+    pub fn server_share_len(&self) -> usize {
+        self.parameter_set().ciphertext_len() + self.curve().public_len()
+    }
+
+    pub fn shared_secret_len(&self) -> usize {
+        32 + self.curve().shared_secret_len()
+    }
+
+    /// Concatenates the ML-KEM and ECDH components in the order of the group
+    fn concat(&self, mlkem: &[u8], ecdh: &[u8]) -> Vec<u8> {
+        let (first, second) = if self.mlkem_first() {
+            (mlkem, ecdh)
+        } else {
+            (ecdh, mlkem)
+        };
+        [first, second].concat()
+    }
+
+    /// Splits a share into its (ML-KEM, ECDH) components
+    fn split<'a>(&self, share: &'a [u8]) -> (&'a [u8], &'a [u8]) {
+        if self.mlkem_first() {
+            let (mlkem, ecdh) = share.split_at(share.len() - self.curve().public_len());
+            (mlkem, ecdh)
+        } else {
+            let (ecdh, mlkem) = share.split_at(self.curve().public_len());
+            (mlkem, ecdh)
+        }
+    }
+}
+
+/// Errors raised while processing a peer key share
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsError {
+    /// The key share does not have the length fixed by the group
+    InvalidShareLength { expected: usize, found: usize },
+    /// The ECDH component is invalid
+    Ecdh(EcdhError),
+    /// The ML-KEM encapsulation key fails the modulus check
+    Key(KeyError),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::InvalidShareLength { expected, found } => {
+                write!(f, "invalid key share length {found}, expected {expected}")
+            }
+            TlsError::Ecdh(err) => write!(f, "{err}"),
+            TlsError::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+impl From<EcdhError> for TlsError {
+    fn from(err: EcdhError) -> Self {
+        TlsError::Ecdh(err)
+    }
+}
+
+impl From<KeyError> for TlsError {
+    fn from(err: KeyError) -> Self {
+        TlsError::Key(err)
+    }
+}
+
+fn check_length(expected: usize, found: usize) -> Result<(), TlsError> {
+    if expected != found {
+        return Err(TlsError::InvalidShareLength { expected, found });
+    }
+    Ok(())
+}
+
+/// Client side of the key exchange : the ephemeral secrets behind the ClientHello share
+pub struct ClientKeyShare {
+    group: HybridGroup,
+    dk: DecapsulationKey,
+    ecdh: EcdhKeyPair,
+    share: Vec<u8>,
+}
+
+impl ClientKeyShare {
+    pub fn generate(group: HybridGroup) -> Self {
+        let dk = DecapsulationKey::generate(group.parameter_set());
+        ClientKeyShare::from_parts(group, dk, EcdhKeyPair::generate(group.curve()))
+    }
+
+    /// Deterministic client share from the ML-KEM seed d || z and the ECDH private key
+    pub fn from_secrets(
+        group: HybridGroup,
+        mlkem_seed: &[u8; 64],
+        ecdh_secret: &[u8],
+    ) -> Result<Self, TlsError> {
+        let dk = DecapsulationKey::from_seed(group.parameter_set(), mlkem_seed);
+        let ecdh = EcdhKeyPair::from_secret_bytes(group.curve(), ecdh_secret)?;
+        Ok(ClientKeyShare::from_parts(group, dk, ecdh))
+    }
+
+    fn from_parts(group: HybridGroup, dk: DecapsulationKey, ecdh: EcdhKeyPair) -> Self {
+        let share = group.concat(dk.encapsulation_key().as_bytes(), ecdh.public_bytes());
+        ClientKeyShare {
+            group,
+            dk,
+            ecdh,
+            share,
+        }
+    }
+
+    pub fn group(&self) -> HybridGroup {
+        self.group
+    }
+
+    /// key_exchange field of the client KeyShareEntry
+    pub fn share(&self) -> &[u8] {
+        &self.share
+    }
+
+    /// Processes the server key_exchange field and returns the shared secret
+    pub fn finish(&self, server_share: &[u8]) -> Result<Vec<u8>, TlsError> {
+        check_length(self.group.server_share_len(), server_share.len())?;
+        let (c, ecdh_public) = self.group.split(server_share);
+        let ecdh_secret = self.ecdh.diffie_hellman(ecdh_public)?;
+        let mlkem_secret = self.dk.decaps(c);
+        Ok(self.group.concat(&mlkem_secret, &ecdh_secret))
+    }
+}
+
+/// Server side of the key exchange : returns the key_exchange field of the server
+/// KeyShareEntry and the shared secret
+pub fn server_respond(
+    group: HybridGroup,
+    client_share: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>), TlsError> {
+    let mut m = [0u8; 32];
+    OsRng.fill_bytes(&mut m);
+    server_respond_with(
+        group,
+        client_share,
+        &m,
+        &EcdhKeyPair::generate(group.curve()),
+    )
+}
+
+/// Deterministic server response from the ML-KEM randomness m and the ECDH key pair
+pub fn server_respond_with(
+    group: HybridGroup,
+    client_share: &[u8],
+    m: &[u8; 32],
+    ecdh: &EcdhKeyPair,
+) -> Result<(Vec<u8>, Vec<u8>), TlsError> {
+    check_length(group.client_share_len(), client_share.len())?;
+    let (ek, ecdh_public) = group.split(client_share);
+    let ek = EncapsulationKey::from_bytes(group.parameter_set(), ek)?;
+    let ecdh_secret = ecdh.diffie_hellman(ecdh_public)?;
+    let (mlkem_secret, c) = MlKem::<KyberParams>::from_parameter_set(group.parameter_set())
+        .encaps_internal(ek.as_bytes(), m);
+
+    Ok((
+        group.concat(&c, ecdh.public_bytes()),
+        group.concat(&mlkem_secret, &ecdh_secret),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for group in HybridGroup::ALL {
+            assert_eq!(HybridGroup::from_codepoint(group.codepoint()), Some(group));
+
+            let client = ClientKeyShare::generate(group);
+            assert_eq!(client.share().len(), group.client_share_len());
+            let (server_share, server_secret) = server_respond(group, client.share()).unwrap();
+            assert_eq!(server_share.len(), group.server_share_len());
+            assert_eq!(server_secret.len(), group.shared_secret_len());
+            assert_eq!(client.finish(&server_share).unwrap(), server_secret);
+
+            let short = &client.share()[1..];
+            assert_eq!(
+                server_respond(group, short).unwrap_err(),
+                TlsError::InvalidShareLength {
+                    expected: group.client_share_len(),
+                    found: short.len()
+                }
+            );
+        }
+        assert_eq!(
+            (
+                HybridGroup::X25519MlKem768.client_share_len(),
+                HybridGroup::X25519MlKem768.server_share_len()
+            ),
+            (1216, 1120)
+        );
+        assert_eq!(
+            (
+                HybridGroup::SecP256r1MlKem768.client_share_len(),
+                HybridGroup::SecP256r1MlKem768.server_share_len()
+            ),
+            (1249, 1153)
+        );
+        assert_eq!(
+            (
+                HybridGroup::SecP384r1MlKem1024.client_share_len(),
+                HybridGroup::SecP384r1MlKem1024.server_share_len()
+            ),
+            (1665, 1665)
+        );
+    }
+}
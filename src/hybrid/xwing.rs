@@ -0,0 +1,192 @@
+use core::fmt;
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha3::digest::{Digest, ExtendableOutput, Update, XofReader};
+use sha3::{Sha3_256, Shake256};
+use x25519_dalek::{X25519_BASEPOINT_BYTES, x25519};
+
+use crate::constants::KyberParams;
+use crate::kyber::kem_scheme::{MlKem, ParameterSet};
+use crate::kyber::keys::{EncapsulationKey, KeyError};
+
+/// XWingLabel = "\./" || "/^\"
+const XWING_LABEL: &[u8; 6] = b"\\.//^\\";
+
+pub const SECRET_KEY_LEN: usize = 32;
+pub const PUBLIC_KEY_LEN: usize = 1216;
+pub const CIPHERTEXT_LEN: usize = 1120;
+pub const SHARED_SECRET_LEN: usize = 32;
+
+const MLKEM_EK_LEN: usize = 1184;
+const MLKEM_CIPHERTEXT_LEN: usize = 1088;
+
+/// Errors raised by X-Wing on malformed inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XWingError {
+    InvalidPublicKeyLength(usize),
+    InvalidCiphertextLength(usize),
+    /// The ML-KEM-768 half of the public key fails the modulus check
+    Key(KeyError),
+}
+
+impl fmt::Display for XWingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XWingError::InvalidPublicKeyLength(len) => {
+                write!(f, "invalid X-Wing public key length {len}")
+            }
+            XWingError::InvalidCiphertextLength(len) => {
+                write!(f, "invalid X-Wing ciphertext length {len}")
+            }
+            XWingError::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for XWingError {}
+
+impl From<KeyError> for XWingError {
+    fn from(err: KeyError) -> Self {
+        XWingError::Key(err)
+    }
+}
+
+/// X-Wing hybrid KEM (draft-connolly-cfrg-xwing-kem) : ML-KEM-768 and X25519,
+/// combined with SHA3-256. The decapsulation key is a 32-byte seed.
+///
+/// This follows the current revisions of the draft, with the seed expanded by
+/// SHAKE256 and XWingLabel hashed last by the combiner. Early revisions hash the
+/// label first; the test vectors of current revisions only match with the label last.
+pub struct XWing {
+    ml_kem: MlKem<KyberParams>,
+}
+
+impl Default for XWing {
+    fn default() -> Self {
+        XWing::new()
+    }
+}
+
+impl XWing {
+    pub fn new() -> Self {
+        XWing {
+            ml_kem: MlKem::<KyberParams>::from_parameter_set(ParameterSet::MlKem768),
+        }
+    }
+
+    /// expandDecapsulationKey(sk) : returns (sk_M, sk_X, pk_M, pk_X)
+    fn expand_decapsulation_key(&self, sk: &[u8; 32]) -> (Vec<u8>, [u8; 32], Vec<u8>, [u8; 32]) {
+        let mut shake = Shake256::default();
+        shake.update(sk);
+        let mut expanded = [0u8; 96];
+        shake.finalize_xof().read(&mut expanded);
+
+        let d: [u8; 32] = expanded[0..32].try_into().unwrap();
+        let z: [u8; 32] = expanded[32..64].try_into().unwrap();
+        let (pk_m, sk_m) = self.ml_kem.key_gen_internal(&d, &z);
+        let sk_x: [u8; 32] = expanded[64..96].try_into().unwrap();
+        let pk_x = x25519(sk_x, X25519_BASEPOINT_BYTES);
+        (sk_m, sk_x, pk_m, pk_x)
+    }
+
+    /// GenerateKeyPairDerand(sk) : returns (sk, pk)
+    pub fn key_gen_derand(&self, sk: &[u8; 32]) -> ([u8; 32], Vec<u8>) {
+        let (_, _, pk_m, pk_x) = self.expand_decapsulation_key(sk);
+        (*sk, [pk_m.as_slice(), &pk_x].concat())
+    }
+
+    /// GenerateKeyPair() : returns (sk, pk)
+    pub fn key_gen(&self) -> ([u8; 32], Vec<u8>) {
+        let mut sk = [0u8; 32];
+        OsRng.fill_bytes(&mut sk);
+        self.key_gen_derand(&sk)
+    }
+
+    /// EncapsulateDerand(pk, eseed) with eseed = m || ek_X : returns (ss, ct)
+    pub fn encaps_derand(
+        &self,
+        pk: &[u8],
+        eseed: &[u8; 64],
+    ) -> Result<(Vec<u8>, Vec<u8>), XWingError> {
+        if pk.len() != PUBLIC_KEY_LEN {
+            return Err(XWingError::InvalidPublicKeyLength(pk.len()));
+        }
+        let pk_m = EncapsulationKey::from_bytes(ParameterSet::MlKem768, &pk[..MLKEM_EK_LEN])?;
+        let pk_x: [u8; 32] = pk[MLKEM_EK_LEN..].try_into().unwrap();
+
+        let m: [u8; 32] = eseed[..32].try_into().unwrap();
+        let ek_x: [u8; 32] = eseed[32..].try_into().unwrap();
+        let ct_x = x25519(ek_x, X25519_BASEPOINT_BYTES);
+        let ss_x = x25519(ek_x, pk_x);
+        let (ss_m, ct_m) = self.ml_kem.encaps_internal(pk_m.as_bytes(), &m);
+
+        let ss = combiner(&ss_m, &ss_x, &ct_x, &pk_x);
+        Ok((ss, [ct_m.as_slice(), &ct_x].concat()))
+    }
+
+    /// Encapsulate(pk) : returns (ss, ct)
+    pub fn encaps(&self, pk: &[u8]) -> Result<(Vec<u8>, Vec<u8>), XWingError> {
+        let mut eseed = [0u8; 64];
+        OsRng.fill_bytes(&mut eseed);
+        self.encaps_derand(pk, &eseed)
+    }
+
+    /// Decapsulate(sk, ct) : returns ss
+    pub fn decaps(&self, sk: &[u8; 32], ct: &[u8]) -> Result<Vec<u8>, XWingError> {
+        if ct.len() != CIPHERTEXT_LEN {
+            return Err(XWingError::InvalidCiphertextLength(ct.len()));
+        }
+        let (sk_m, sk_x, _, pk_x) = self.expand_decapsulation_key(sk);
+        let (ct_m, ct_x) = ct.split_at(MLKEM_CIPHERTEXT_LEN);
+        let ct_x: [u8; 32] = ct_x.try_into().unwrap();
+
+        let ss_m = self.ml_kem.decaps(&sk_m, ct_m);
+        let ss_x = x25519(sk_x, ct_x);
+        Ok(combiner(&ss_m, &ss_x, &ct_x, &pk_x))
+    }
+}
+
+/// Combiner(ss_M, ss_X, ct_X, pk_X) = SHA3-256(ss_M || ss_X || ct_X || pk_X || XWingLabel),
+/// the order of the current draft
+fn combiner(ss_m: &[u8], ss_x: &[u8; 32], ct_x: &[u8; 32], pk_x: &[u8; 32]) -> Vec<u8> {
+    let mut hasher = Sha3_256::new();
+    Digest::update(&mut hasher, ss_m);
+    Digest::update(&mut hasher, ss_x);
+    Digest::update(&mut hasher, ct_x);
+    Digest::update(&mut hasher, pk_x);
+    Digest::update(&mut hasher, XWING_LABEL);
+    hasher.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        let xwing = XWing::new();
+        let (sk, pk) = xwing.key_gen();
+        assert_eq!(pk.len(), PUBLIC_KEY_LEN);
+        let (ss, ct) = xwing.encaps(&pk).unwrap();
+        assert_eq!(ct.len(), CIPHERTEXT_LEN);
+        assert_eq!(xwing.decaps(&sk, &ct).unwrap(), ss);
+
+        // implicit rejection on the ML-KEM half, and the X25519 half is bound too
+        for position in [0, CIPHERTEXT_LEN - 1] {
+            let mut tampered = ct.clone();
+            tampered[position] ^= 1;
+            assert_ne!(xwing.decaps(&sk, &tampered).unwrap(), ss);
+        }
+
+        assert_eq!(
+            xwing.encaps(&pk[1..]).unwrap_err(),
+            XWingError::InvalidPublicKeyLength(PUBLIC_KEY_LEN - 1)
+        );
+        assert_eq!(
+            xwing.decaps(&sk, &ct[1..]).unwrap_err(),
+            XWingError::InvalidCiphertextLength(CIPHERTEXT_LEN - 1)
+        );
+        assert_eq!(XWING_LABEL, &[0x5c, 0x2e, 0x2f, 0x2f, 0x5e, 0x5c]);
+    }
+}
@@ -0,0 +1,410 @@
+use core::fmt;
+
+use hkdf::Hkdf;
+use hkdf::hmac::SimpleHmac;
+use sha2::{Sha256, Sha384, Sha512};
+use sha3::digest::Digest;
+use sha3::digest::core_api::BlockSizeUser;
+use sha3::{Sha3_256, Sha3_512};
+
+use crate::constants::PolyParams;
+use crate::hash::{HashSuite, Strength, kmac};
+use crate::kyber::kem_scheme::MlKem;
+
+/// Key derivation methods of NIST SP 800-56C Rev. 2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    /// One-step KDF (4.1) with H = SHA3-256
+    OneStepSha3_256,
+    /// One-step KDF (4.1) with H = SHA3-512
+    OneStepSha3_512,
+    /// One-step KDF (4.1) with H = KMAC128, S = "KDF"
+    OneStepKmac128,
+    /// One-step KDF (4.1) with H = KMAC256, S = "KDF"
+    OneStepKmac256,
+    /// Two-step KDF (5) : HMAC-SHA-256 extraction then HKDF expansion (RFC 5869)
+    HkdfSha256,
+    /// Two-step KDF (5) : HMAC-SHA-384 extraction then HKDF expansion (RFC 5869)
+    HkdfSha384,
+    /// Two-step KDF (5) : HMAC-SHA-512 extraction then HKDF expansion (RFC 5869)
+    HkdfSha512,
+}
+
+impl Kdf {
+    /// Largest output length in bytes : L is encoded on 32 bits in the FixedInfo, and
+    /// HKDF-Expand is limited to 255 blocks (RFC 5869)
+    pub fn max_output_len(&self) -> usize {
+        let max_bits = u32::MAX as usize / 8;
+        match self {
+            Kdf::HkdfSha256 => 255 * 32,
+            Kdf::HkdfSha384 => 255 * 48,
+            Kdf::HkdfSha512 => 255 * 64,
+            _ => max_bits,
+        }
+    }
+}
+
+/// Errors raised by the key derivation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfError {
+    /// The total length of the requested keys is above `Kdf::max_output_len`
+    OutputTooLong { max: usize, found: usize },
+    /// A key or ciphertext does not have the length of the parameter set
+    InvalidLength { expected: usize, found: usize },
+}
+
+impl fmt::Display for KdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KdfError::OutputTooLong { max, found } => {
+                write!(f, "requested {found} bytes of keys, at most {max} allowed")
+            }
+            KdfError::InvalidLength { expected, found } => {
+                write!(f, "invalid length {found}, expected {expected}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KdfError {}
+
+fn check_length(expected: usize, found: usize) -> Result<(), KdfError> {
+    if expected != found {
+        return Err(KdfError::InvalidLength { expected, found });
+    }
+    Ok(())
+}
+
+/// FixedInfo of SP 800-56C, binding the derived keys to their context
+///
+/// Following SP 800-227, the encapsulation key and the ciphertext should always be
+/// included: `MlKem::encaps_derive` and `MlKem::decaps_derive` fill them in.
+///
+/// Encoding : each field, in declaration order, as len (32-bit big-endian) || bytes,
+/// followed by the total output length L in bits (32-bit big-endian).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FixedInfo<'a> {
+    /// AlgorithmID / application label
+    pub label: &'a [u8],
+    /// Identifier of the party U (encapsulator)
+    pub party_u_id: &'a [u8],
+    /// Identifier of the party V (decapsulator)
+    pub party_v_id: &'a [u8],
+    /// Encapsulation key ek of the party V
+    pub ek: &'a [u8],
+    /// Ciphertext c
+    pub ciphertext: &'a [u8],
+    /// Any other public information (SuppPubInfo)
+    pub supp_pub_info: &'a [u8],
+}
+
+impl<'a> FixedInfo<'a> {
+    pub fn new(label: &'a [u8]) -> Self {
+        FixedInfo {
+            label,
+            ..Default::default()
+        }
+    }
+
+    pub fn encode(&self, l_bits: u32) -> Vec<u8> {
+        let fields = [
+            self.label,
+            self.party_u_id,
+            self.party_v_id,
+            self.ek,
+            self.ciphertext,
+            self.supp_pub_info,
+        ];
+        let mut out = Vec::new();
+        for field in fields {
+            out.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            out.extend_from_slice(field);
+        }
+        out.extend_from_slice(&l_bits.to_be_bytes());
+        out
+    }
+}
+
+/// Derives keys of the given lengths (in bytes) from the shared secret z.
+/// A single output of the total length is derived, then split in order. The total
+/// length must not exceed `Kdf::max_output_len`.
+///
+/// The salt is used by the KMAC and HKDF variants, and ignored by the hash variants.
+/// An empty salt selects the default salt of SP 800-56C.
+pub fn derive_keys(
+    kdf: Kdf,
+    z: &[u8],
+    salt: &[u8],
+    fixed_info: &FixedInfo,
+    lengths: &[usize],
+) -> Result<Vec<Vec<u8>>, KdfError> {
+    let max = kdf.max_output_len();
+    let l = lengths
+        .iter()
+        .try_fold(0usize, |l, &len| l.checked_add(len))
+        .filter(|&l| l <= max)
+        .ok_or(KdfError::OutputTooLong {
+            max,
+            found: lengths.iter().fold(0usize, |l, &len| l.saturating_add(len)),
+        })?;
+    let info = fixed_info.encode((8 * l) as u32);
+    let okm = match kdf {
+        Kdf::OneStepSha3_256 => one_step_hash::<Sha3_256>(z, &info, l),
+        Kdf::OneStepSha3_512 => one_step_hash::<Sha3_512>(z, &info, l),
+        Kdf::OneStepKmac128 => one_step_kmac(Strength::S128, z, salt, &info, l),
+        Kdf::OneStepKmac256 => one_step_kmac(Strength::S256, z, salt, &info, l),
+        Kdf::HkdfSha256 => two_step_hkdf::<Sha256>(z, salt, &info, l)?,
+        Kdf::HkdfSha384 => two_step_hkdf::<Sha384>(z, salt, &info, l)?,
+        Kdf::HkdfSha512 => two_step_hkdf::<Sha512>(z, salt, &info, l)?,
+    };
+
+    let mut keys = Vec::with_capacity(lengths.len());
+    let mut offset = 0;
+    for &len in lengths {
+        keys.push(okm[offset..offset + len].to_vec());
+        offset += len;
+    }
+    Ok(keys)
+}
+
+/// One-step KDF (4.1 SP 800-56C) with a hash function H :
+/// K(i) = H(counter_i || Z || FixedInfo), for i = 1, ..., ceil(L / H_outputLen)
+pub fn one_step_hash<D: Digest>(z: &[u8], fixed_info: &[u8], l: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(l);
+    let mut counter = 1u32;
+    while out.len() < l {
+        let mut hasher = D::new();
+        Digest::update(&mut hasher, counter.to_be_bytes());
+        Digest::update(&mut hasher, z);
+        Digest::update(&mut hasher, fixed_info);
+        out.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+    out.truncate(l);
+    out
+}
+
+/// One-step KDF (4.1 SP 800-56C) with H = KMAC and H_outputBits = L :
+/// K = KMAC(salt, 0x00000001 || Z || FixedInfo, L, "KDF")
+fn one_step_kmac(
+    strength: Strength,
+    z: &[u8],
+    salt: &[u8],
+    fixed_info: &[u8],
+    l: usize,
+) -> Vec<u8> {
+    // Default salt : all-zero string of the length of the KMAC input block, minus 4 bytes
+    let default_salt = vec![0u8; strength.rate() - 4];
+    let salt = if salt.is_empty() { &default_salt } else { salt };
+
+    let mut x = 1u32.to_be_bytes().to_vec();
+    x.extend_from_slice(z);
+    x.extend_from_slice(fixed_info);
+
+    kmac(strength, salt, &x, l, b"KDF", false)
+}
+
+/// Two-step KDF (5 SP 800-56C) instantiated with HKDF (RFC 5869) :
+/// PRK = HMAC(salt, Z), then K = HKDF-Expand(PRK, FixedInfo, L), with L at most
+/// 255 * HashLen
+pub fn two_step_hkdf<D>(
+    z: &[u8],
+    salt: &[u8],
+    fixed_info: &[u8],
+    l: usize,
+) -> Result<Vec<u8>, KdfError>
+where
+    D: Digest + BlockSizeUser + Clone,
+{
+    let hkdf = Hkdf::<D, SimpleHmac<D>>::new(Some(salt), z);
+    let mut out = vec![0u8; l];
+    hkdf.expand(fixed_info, &mut out)
+        .map_err(|_| KdfError::OutputTooLong {
+            max: 255 * <D as Digest>::output_size(),
+            found: l,
+        })?;
+    Ok(out)
+}
+
+impl<P: PolyParams, H: HashSuite> MlKem<P, H> {
+    /// ML-KEM.Encaps(ek) followed by the derivation of keys of the given lengths from
+    /// the shared secret, with ek and c bound in the FixedInfo.
+    ///
+    /// Input : encapsulation key ek in B^(384*k + 32)
+    /// Output : derived keys
+    /// Output : ciphertext c in B^(32 * (d_u*k + d_v))
+    pub fn encaps_derive(
+        &self,
+        ek: &[u8],
+        kdf: Kdf,
+        salt: &[u8],
+        fixed_info: &FixedInfo,
+        lengths: &[usize],
+    ) -> Result<(Vec<Vec<u8>>, Vec<u8>), KdfError> {
+        check_length(self.0.ek_len(), ek.len())?;
+        let (k, c) = self.encaps(ek);
+        let info = FixedInfo {
+            ek,
+            ciphertext: &c,
+            ..*fixed_info
+        };
+        Ok((derive_keys(kdf, &k, salt, &info, lengths)?, c))
+    }
+
+    /// ML-KEM.Decaps(dk, c) followed by the same derivation as `encaps_derive`.
+    /// The encapsulation key bound in the FixedInfo is read from dk.
+    ///
+    /// Input : decapsulation key dk in B^(768*k + 96)
+    /// Input : ciphertext c in B^(32 * (d_u*k + d_v))
+    /// Output : derived keys
+    pub fn decaps_derive(
+        &self,
+        dk: &[u8],
+        c: &[u8],
+        kdf: Kdf,
+        salt: &[u8],
+        fixed_info: &FixedInfo,
+        lengths: &[usize],
+    ) -> Result<Vec<Vec<u8>>, KdfError> {
+        check_length(768 * self.0.k + 96, dk.len())?;
+        check_length(self.0.ciphertext_len(), c.len())?;
+        let k = self.decaps(dk, c);
+        let info = FixedInfo {
+            ek: &dk[384 * self.0.k..768 * self.0.k + 32],
+            ciphertext: c,
+            ..*fixed_info
+        };
+        derive_keys(kdf, &k, salt, &info, lengths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::KyberParams;
+
+    #[test]
+    fn basics() {
+        let z: Vec<u8> = (0..32).collect();
+        let (ek, c) = ([0xaau8; 8], [0xccu8; 8]);
+        let fixed_info = FixedInfo {
+            party_u_id: b"alice",
+            party_v_id: b"bob",
+            ek: &ek,
+            ciphertext: &c,
+            ..FixedInfo::new(b"kyber-rs kdf test")
+        };
+        assert_eq!(
+            hex::encode(fixed_info.encode(384)),
+            "000000116b796265722d7273206b6466207465737400000005616c69636500000003626f6200000008aaaaaaaaaaaaaaaa00000008cccccccccccccccc0000000000000180"
+        );
+
+        let vectors = [
+            (
+                Kdf::OneStepSha3_256,
+                &b"salt"[..],
+                "79cef1a86ea46f076d9da6180915feaa776b08d3d1a7a6c21640c0a4278fa287f35ad440ee5ff4baf2d2719f6b1f791d",
+            ),
+            (
+                Kdf::OneStepSha3_512,
+                b"salt",
+                "11721ec7f4c1691b891ddfa5fd1f5a9b24e2054bb0f8b68cbb1174bd708a58a1125c90dc575ea8adf5b9130c67a6d375",
+            ),
+            (
+                Kdf::OneStepKmac128,
+                b"salt",
+                "5101734c89c3c72ceb7eb95ddc7aef99ab2dbbe04ed229fcc420ce18531d2cd403163c5b267185843ecb1917b803b606",
+            ),
+            (
+                Kdf::OneStepKmac128,
+                b"",
+                "b43a962c7994b11c1cdf64185a5f2dbedfb795d16e903097bcd3374c21627a6e854a0db1276021a252b63b85b2ef2212",
+            ),
+            (
+                Kdf::OneStepKmac256,
+                b"salt",
+                "75133219843d22a1ae9019ab19a5eb6c51c6540d7ac41c5478fc4f43bb25fd589569ba719e7a691b133cf716341063bf",
+            ),
+            (
+                Kdf::OneStepKmac256,
+                b"",
+                "c350cc6224b4ccd0330049388ac5c5a4e82ea6252fd1d310b5f04080b9e9cde073cef0ce88584eff1708541fa4d456e2",
+            ),
+            (
+                Kdf::HkdfSha256,
+                b"salt",
+                "1c22f8f76e7792dfe5fe3ddbd91f14316d59ed516fbc468b07f80f7e9a59abfef4159c58220961f22128dc02d6f23a8d",
+            ),
+            (
+                Kdf::HkdfSha512,
+                b"salt",
+                "8b0c0e43bfe60e7a5189afc409c8872b19594ee7a58b8bcd43a68512cea8c25d1746076f6e8b9945d5b3d89eed096be7",
+            ),
+        ];
+        for (kdf, salt, expected) in vectors {
+            let keys = derive_keys(kdf, &z, salt, &fixed_info, &[16, 32]).unwrap();
+            assert_eq!(keys.len(), 2);
+            assert_eq!(hex::encode(keys.concat()), expected);
+        }
+
+        let kem_scheme = MlKem::<KyberParams>::new(3, 2, 2, 10, 4);
+        let (ek, dk) = kem_scheme.key_gen();
+        let info = FixedInfo {
+            party_u_id: b"alice",
+            party_v_id: b"bob",
+            ..FixedInfo::new(b"AES-256-GCM keys")
+        };
+        let lengths = [32, 12, 32];
+        let (keys, c) = kem_scheme
+            .encaps_derive(&ek, Kdf::HkdfSha256, b"", &info, &lengths)
+            .unwrap();
+        let keys_decaps = kem_scheme
+            .decaps_derive(&dk, &c, Kdf::HkdfSha256, b"", &info, &lengths)
+            .unwrap();
+        assert_eq!(keys, keys_decaps);
+        assert_eq!(keys.iter().map(Vec::len).collect::<Vec<_>>(), lengths);
+
+        let other_info = FixedInfo {
+            party_v_id: b"eve",
+            ..info
+        };
+        let keys_other = kem_scheme
+            .decaps_derive(&dk, &c, Kdf::HkdfSha256, b"", &other_info, &lengths)
+            .unwrap();
+        assert_ne!(keys, keys_other);
+
+        // HKDF-Expand stops at 255 blocks, and L must fit in 32 bits
+        let max = Kdf::HkdfSha256.max_output_len();
+        assert_eq!(max, 8160);
+        assert!(derive_keys(Kdf::HkdfSha256, &z, b"", &info, &[max - 32, 32]).is_ok());
+        assert_eq!(
+            derive_keys(Kdf::HkdfSha256, &z, b"", &info, &[max, 1]),
+            Err(KdfError::OutputTooLong {
+                max,
+                found: max + 1
+            })
+        );
+        assert!(matches!(
+            derive_keys(Kdf::OneStepSha3_256, &z, b"", &info, &[usize::MAX, 2]),
+            Err(KdfError::OutputTooLong { .. })
+        ));
+        assert!(matches!(
+            derive_keys(Kdf::OneStepKmac128, &z, b"", &info, &[1 << 29]),
+            Err(KdfError::OutputTooLong { .. })
+        ));
+        assert_eq!(
+            kem_scheme.decaps_derive(&dk[1..], &c, Kdf::HkdfSha256, b"", &info, &lengths),
+            Err(KdfError::InvalidLength {
+                expected: dk.len(),
+                found: dk.len() - 1
+            })
+        );
+        assert_eq!(
+            kem_scheme.decaps_derive(&dk, &c[..10], Kdf::HkdfSha256, b"", &info, &lengths),
+            Err(KdfError::InvalidLength {
+                expected: c.len(),
+                found: 10
+            })
+        );
+    }
+}
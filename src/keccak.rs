@@ -0,0 +1,266 @@
+/// Four Keccak-f[1600] states, interleaved lane by lane : `state[l][i]` is the lane l
+/// of the instance i, so that a single AVX2 register holds the same lane of the four states.
+pub type StateX4 = [[u64; 4]; 25];
+
+/// Rate of SHAKE128 in bytes
+pub const SHAKE128_RATE: usize = 168;
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// Rotation offsets of the rho step, indexed by x + 5*y
+const RHO_OFFSETS: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+/// Applies Keccak-f[1600] to the four interleaved states, using AVX2 when available
+pub fn f1600_x4(state: &mut StateX4) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if std::is_x86_feature_detected!("avx2") {
+            // SAFETY: the avx2 target feature has just been detected at runtime
+            unsafe { avx2::f1600_x4(state) };
+            return;
+        }
+    }
+    f1600_x4_portable(state);
+}
+
+/// Portable Keccak-f[1600] on four interleaved states
+pub fn f1600_x4_portable(state: &mut StateX4) {
+    for rc in ROUND_CONSTANTS {
+        // theta
+        let mut c = [[0u64; 4]; 5];
+        for x in 0..5 {
+            for i in 0..4 {
+                c[x][i] = state[x][i]
+                    ^ state[x + 5][i]
+                    ^ state[x + 10][i]
+                    ^ state[x + 15][i]
+                    ^ state[x + 20][i];
+            }
+        }
+        for x in 0..5 {
+            for i in 0..4 {
+                let d = c[(x + 4) % 5][i] ^ c[(x + 1) % 5][i].rotate_left(1);
+                for y in 0..5 {
+                    state[x + 5 * y][i] ^= d;
+                }
+            }
+        }
+
+        // rho and pi
+        let mut b = [[0u64; 4]; 25];
+        for x in 0..5 {
+            for y in 0..5 {
+                for i in 0..4 {
+                    b[y + 5 * ((2 * x + 3 * y) % 5)][i] =
+                        state[x + 5 * y][i].rotate_left(RHO_OFFSETS[x + 5 * y]);
+                }
+            }
+        }
+
+        // chi
+        for y in 0..5 {
+            for x in 0..5 {
+                for i in 0..4 {
+                    state[x + 5 * y][i] =
+                        b[x + 5 * y][i] ^ (!b[(x + 1) % 5 + 5 * y][i] & b[(x + 2) % 5 + 5 * y][i]);
+                }
+            }
+        }
+
+        // iota
+        for lane in state[0].iter_mut() {
+            *lane ^= rc;
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::{RHO_OFFSETS, ROUND_CONSTANTS, StateX4};
+    use core::arch::x86_64::*;
+
+    #[inline(always)]
+    unsafe fn rotl(x: __m256i, n: u32) -> __m256i {
+        unsafe {
+            let left = _mm256_sllv_epi64(x, _mm256_set1_epi64x(n as i64));
+            let right = _mm256_srlv_epi64(x, _mm256_set1_epi64x(64 - n as i64));
+            _mm256_or_si256(left, right)
+        }
+    }
+
+    /// Keccak-f[1600] on four interleaved states, one AVX2 register per lane
+    ///
+    /// # Safety
+    /// The caller must ensure that the CPU supports AVX2.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn f1600_x4(state: &mut StateX4) {
+        unsafe {
+            let mut a = [_mm256_setzero_si256(); 25];
+            for (reg, lanes) in a.iter_mut().zip(state.iter()) {
+                *reg = _mm256_loadu_si256(lanes.as_ptr() as *const __m256i);
+            }
+
+            for rc in ROUND_CONSTANTS {
+                let mut c = [_mm256_setzero_si256(); 5];
+                for x in 0..5 {
+                    c[x] = _mm256_xor_si256(
+                        _mm256_xor_si256(_mm256_xor_si256(a[x], a[x + 5]), a[x + 10]),
+                        _mm256_xor_si256(a[x + 15], a[x + 20]),
+                    );
+                }
+                for x in 0..5 {
+                    let d = _mm256_xor_si256(c[(x + 4) % 5], rotl(c[(x + 1) % 5], 1));
+                    for y in 0..5 {
+                        a[x + 5 * y] = _mm256_xor_si256(a[x + 5 * y], d);
+                    }
+                }
+
+                let mut b = [_mm256_setzero_si256(); 25];
+                for x in 0..5 {
+                    for y in 0..5 {
+                        b[y + 5 * ((2 * x + 3 * y) % 5)] =
+                            rotl(a[x + 5 * y], RHO_OFFSETS[x + 5 * y]);
+                    }
+                }
+
+                for y in 0..5 {
+                    for x in 0..5 {
+                        a[x + 5 * y] = _mm256_xor_si256(
+                            b[x + 5 * y],
+                            _mm256_andnot_si256(b[(x + 1) % 5 + 5 * y], b[(x + 2) % 5 + 5 * y]),
+                        );
+                    }
+                }
+
+                a[0] = _mm256_xor_si256(a[0], _mm256_set1_epi64x(rc as i64));
+            }
+
+            for (reg, lanes) in a.iter().zip(state.iter_mut()) {
+                _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, *reg);
+            }
+        }
+    }
+}
+
+/// Four SHAKE128 instances absorbing inputs of the same length, squeezed block by block
+pub struct Shake128x4 {
+    state: StateX4,
+}
+
+impl Shake128x4 {
+    /// Absorbs the four inputs, which must have the same length
+    pub fn new(inputs: [&[u8]; 4]) -> Self {
+        let len = inputs[0].len();
+        if inputs.iter().any(|input| input.len() != len) {
+            panic!("The four inputs must have the same length")
+        }
+
+        let mut state = [[0u64; 4]; 25];
+        let mut block = [0u8; SHAKE128_RATE];
+        let mut offset = 0;
+        loop {
+            let chunk_len = (len - offset).min(SHAKE128_RATE);
+            let last = chunk_len < SHAKE128_RATE;
+            for (i, input) in inputs.iter().enumerate() {
+                block.fill(0);
+                block[..chunk_len].copy_from_slice(&input[offset..offset + chunk_len]);
+                if last {
+                    block[chunk_len] ^= 0x1f;
+                    block[SHAKE128_RATE - 1] ^= 0x80;
+                }
+                for (l, word) in block.chunks_exact(8).enumerate() {
+                    state[l][i] ^= u64::from_le_bytes(word.try_into().unwrap());
+                }
+            }
+            f1600_x4(&mut state);
+            if last {
+                break;
+            }
+            offset += SHAKE128_RATE;
+        }
+
+        Shake128x4 { state }
+    }
+
+    /// Writes the next block of output of each instance, then permutes the states
+    pub fn squeeze_blocks(&mut self, out: &mut [[u8; SHAKE128_RATE]; 4]) {
+        for (i, block) in out.iter_mut().enumerate() {
+            for (l, word) in block.chunks_exact_mut(8).enumerate() {
+                word.copy_from_slice(&self.state[l][i].to_le_bytes());
+            }
+        }
+        f1600_x4(&mut self.state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha3::Shake128;
+    use sha3::digest::{ExtendableOutput, Update, XofReader};
+
+    #[test]
+    fn basics() {
+        let mut portable = [[0u64; 4]; 25];
+        for (l, lanes) in portable.iter_mut().enumerate() {
+            for (i, lane) in lanes.iter_mut().enumerate() {
+                *lane = ((l as u64) << 32 | i as u64).wrapping_mul(0x9e3779b97f4a7c15);
+            }
+        }
+        let mut dispatched = portable;
+        f1600_x4_portable(&mut portable);
+        f1600_x4(&mut dispatched);
+        assert_eq!(portable, dispatched);
+
+        for len in [0usize, 34, 167, 168, 200, 400] {
+            let inputs: Vec<Vec<u8>> = (0..4)
+                .map(|i| (0..len).map(|b| (b * 7 + i * 13) as u8).collect())
+                .collect();
+            let mut shake = Shake128x4::new([&inputs[0], &inputs[1], &inputs[2], &inputs[3]]);
+
+            let mut blocks = [[0u8; SHAKE128_RATE]; 4];
+            let mut outputs = vec![Vec::new(); 4];
+            for _ in 0..3 {
+                shake.squeeze_blocks(&mut blocks);
+                for (output, block) in outputs.iter_mut().zip(blocks.iter()) {
+                    output.extend_from_slice(block);
+                }
+            }
+
+            for (input, output) in inputs.iter().zip(outputs.iter()) {
+                let mut hasher = Shake128::default();
+                hasher.update(input);
+                let mut expected = vec![0u8; 3 * SHAKE128_RATE];
+                hasher.finalize_xof().read(&mut expected);
+                assert_eq!(output, &expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use core::fmt;
+
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use crate::hash::h;
+use crate::kyber::kem_scheme::ParameterSet;
+use crate::kyber::keys::{DecapsulationKey, EncapsulationKey};
+
+pub const VERSION: u8 = 1;
+/// Length of the header version || algorithm || DEM
+pub const HEADER_LEN: usize = 3;
+pub const TAG_LEN: usize = 16;
+
+const KDF_LABEL: &[u8] = b"kyber-rs KEM-DEM v1";
+const NONCE: [u8; 12] = [0; 12];
+
+/// AEAD used as DEM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dem {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl Dem {
+    pub const ALL: [Dem; 2] = [Dem::Aes256Gcm, Dem::ChaCha20Poly1305];
+
+    fn id(&self) -> u8 {
+        match self {
+            Dem::Aes256Gcm => 1,
+            Dem::ChaCha20Poly1305 => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        Dem::ALL.into_iter().find(|dem| dem.id() == id)
+    }
+}
+
+/// Errors raised while opening a sealed message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KemDemError {
+    /// The input is shorter than the header, ML-KEM ciphertext and tag
+    Truncated,
+    UnsupportedVersion(u8),
+    UnknownAlgorithm(u8),
+    UnknownDem(u8),
+    /// The message was sealed for another parameter set than the one of dk
+    ParameterSetMismatch,
+    /// The AEAD tag does not verify : wrong key, aad, or modified message
+    Decryption,
+}
+
+impl fmt::Display for KemDemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KemDemError::Truncated => write!(f, "truncated sealed message"),
+            KemDemError::UnsupportedVersion(v) => write!(f, "unsupported version {v}"),
+            KemDemError::UnknownAlgorithm(id) => write!(f, "unknown algorithm {id}"),
+            KemDemError::UnknownDem(id) => write!(f, "unknown DEM {id}"),
+            KemDemError::ParameterSetMismatch => {
+                write!(f, "message sealed for another parameter set")
+            }
+            KemDemError::Decryption => write!(f, "decryption failed"),
+        }
+    }
+}
+
+impl std::error::Error for KemDemError {}
+
+/// AEAD key : HKDF-SHA256 with salt the header, ikm the shared key K and info
+/// "kyber-rs KEM-DEM v1" || H(ek), so that it is bound to the algorithms and to the
+/// recipient. Each key encrypts a single message, so the nonce is zero.
+fn dem_key(header: &[u8; HEADER_LEN], k: &[u8], ek: &EncapsulationKey) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(header), k)
+        .expand_multi_info(&[KDF_LABEL, &h(ek.as_bytes())], &mut key)
+        .expect("32 is a valid length for HKDF-SHA256");
+    key
+}
+
+/// Seals plaintext to ek with AES-256-GCM
+pub fn seal(ek: &EncapsulationKey, plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+    seal_with(ek, Dem::Aes256Gcm, plaintext, aad)
+}
+
+/// Seals plaintext of any length to ek, with ML-KEM as KEM and the given AEAD as
+/// DEM. The associated data is only authenticated, it is not part of the output.
+///
+/// Encoding of version 1 : version 1 (1) || algorithm (1, `ParameterSet::id`) ||
+/// DEM (1) : 1 AES-256-GCM, 2 ChaCha20-Poly1305 || ML-KEM ciphertext (c) || AEAD
+/// ciphertext of the plaintext with its tag (n+16).
+pub fn seal_with(ek: &EncapsulationKey, dem: Dem, plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+    let header = [VERSION, ek.parameter_set().id(), dem.id()];
+    let (k, c) = ek.encaps();
+    let key = dem_key(&header, &k, ek);
+    let payload = Payload {
+        msg: plaintext,
+        aad,
+    };
+    let nonce = NONCE.into();
+    let ct = match dem {
+        Dem::Aes256Gcm => Aes256Gcm::new(&key.into()).encrypt(&nonce, payload),
+        Dem::ChaCha20Poly1305 => ChaCha20Poly1305::new(&key.into()).encrypt(&nonce, payload),
+    }
+    .expect("plaintext too long for the AEAD");
+    [header.as_slice(), &c, &ct].concat()
+}
+
+/// Opens a message sealed to the encapsulation key of dk
+pub fn open(dk: &DecapsulationKey, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, KemDemError> {
+    let header: [u8; HEADER_LEN] = sealed
+        .get(..HEADER_LEN)
+        .ok_or(KemDemError::Truncated)?
+        .try_into()
+        .unwrap();
+    if header[0] != VERSION {
+        return Err(KemDemError::UnsupportedVersion(header[0]));
+    }
+    let parameter_set =
+        ParameterSet::from_id(header[1]).ok_or(KemDemError::UnknownAlgorithm(header[1]))?;
+    let dem = Dem::from_id(header[2]).ok_or(KemDemError::UnknownDem(header[2]))?;
+    if parameter_set != dk.parameter_set() {
+        return Err(KemDemError::ParameterSetMismatch);
+    }
+    let c_len = parameter_set.ciphertext_len();
+    if sealed.len() < HEADER_LEN + c_len + TAG_LEN {
+        return Err(KemDemError::Truncated);
+    }
+    let (c, ct) = sealed[HEADER_LEN..].split_at(c_len);
+
+    let k = dk.decaps(c);
+    let key = dem_key(&header, &k, &dk.encapsulation_key());
+    let payload = Payload { msg: ct, aad };
+    let nonce = NONCE.into();
+    match dem {
+        Dem::Aes256Gcm => Aes256Gcm::new(&key.into()).decrypt(&nonce, payload),
+        Dem::ChaCha20Poly1305 => ChaCha20Poly1305::new(&key.into()).decrypt(&nonce, payload),
+    }
+    .map_err(|_| KemDemError::Decryption)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for set in ParameterSet::ALL {
+            let dk = DecapsulationKey::generate(set);
+            let ek = dk.encapsulation_key();
+            for dem in Dem::ALL {
+                let sealed = seal_with(&ek, dem, b"record", b"aad");
+                assert_eq!(
+                    sealed.len(),
+                    HEADER_LEN + set.ciphertext_len() + 6 + TAG_LEN
+                );
+                assert_eq!(sealed[..HEADER_LEN], [1, set.id(), dem.id()]);
+                assert_eq!(open(&dk, &sealed, b"aad").unwrap(), b"record");
+            }
+        }
+    }
+}
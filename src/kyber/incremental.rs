@@ -0,0 +1,222 @@
+use core::fmt;
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+use crate::constants::PolyParams;
+use crate::hash::HashSuite;
+use crate::kyber::kem_scheme::MlKem;
+use crate::kyber::keys::{KeyError, check_modulus};
+
+/// Length of the header rho || H(ek) of an encapsulation key
+pub const HEADER_LEN: usize = 64;
+
+/// Errors raised by the incremental encapsulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncrementalError {
+    /// The header or t_hat does not have the length required by the parameter set
+    InvalidLength { expected: usize, found: usize },
+    /// t_hat does not hash, with rho, to the H(ek) of the header
+    HashMismatch,
+    /// A coefficient of t_hat is not reduced modulo q
+    Key(KeyError),
+}
+
+impl fmt::Display for IncrementalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IncrementalError::InvalidLength { expected, found } => {
+                write!(f, "invalid length {found}, expected {expected}")
+            }
+            IncrementalError::HashMismatch => {
+                write!(f, "encapsulation key does not match its header")
+            }
+            IncrementalError::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for IncrementalError {}
+
+impl From<KeyError> for IncrementalError {
+    fn from(err: KeyError) -> Self {
+        IncrementalError::Key(err)
+    }
+}
+
+pub(crate) fn check_length(expected: usize, found: usize) -> Result<(), IncrementalError> {
+    if expected != found {
+        return Err(IncrementalError::InvalidLength { expected, found });
+    }
+    Ok(())
+}
+
+/// Secrets kept by the encapsulator between encaps1 and encaps2
+pub struct EncapsState {
+    header: [u8; HEADER_LEN],
+    m: [u8; 32],
+    r: [u8; 32],
+}
+
+/// Incremental ML-KEM.Encaps, as used by the ML-KEM Braid of Signal's sparse
+/// post-quantum ratchet.
+///
+/// The shared key K and c_1 only depend on the header rho || H(ek), so they can be
+/// computed as soon as the header has arrived; c_2 is computed once t_hat has
+/// arrived. c_1 || c_2 is exactly the ciphertext of ML-KEM.Encaps_internal(ek, m).
+impl<P: PolyParams, H: HashSuite> MlKem<P, H> {
+    /// Header rho || H(ek) of ek in B^(384*k+32)
+    pub fn header(&self, ek: &[u8]) -> [u8; HEADER_LEN] {
+        let mut header = [0u8; HEADER_LEN];
+        header[..32].copy_from_slice(&ek[ek.len() - 32..]);
+        header[32..].copy_from_slice(&H::h(ek));
+        header
+    }
+
+    /// Rebuilds ek = t_hat || rho from a header in B^64, checking t_hat against H(ek)
+    pub(crate) fn join(&self, header: &[u8], t_hat: &[u8]) -> Result<Vec<u8>, IncrementalError> {
+        check_length(384 * self.0.k, t_hat.len())?;
+        let ek = [t_hat, &header[..32]].concat();
+        if H::h(&ek) != header[32..] {
+            return Err(IncrementalError::HashMismatch);
+        }
+        Ok(ek)
+    }
+
+    /// First step : returns (K, c_1, state)
+    ///
+    /// Input : header rho || H(ek) in B^64
+    pub fn encaps1(
+        &self,
+        header: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>, EncapsState), IncrementalError> {
+        let mut m = [0u8; 32];
+        OsRng.fill_bytes(&mut m);
+        self.encaps1_internal(header, &m)
+    }
+
+    /// Deterministic first step from the randomness m of ML-KEM.Encaps_internal
+    pub fn encaps1_internal(
+        &self,
+        header: &[u8],
+        m: &[u8; 32],
+    ) -> Result<(Vec<u8>, Vec<u8>, EncapsState), IncrementalError> {
+        check_length(HEADER_LEN, header.len())?;
+        let mut g_seed = m.to_vec();
+        g_seed.extend_from_slice(&header[32..]);
+        let (k, r) = H::g(&g_seed);
+
+        let mut c1 = vec![0u8; 32 * self.0.d_u * self.0.k];
+        self.0.encrypt_u_into(&header[..32], &r, &mut c1);
+        let state = EncapsState {
+            header: header.try_into().unwrap(),
+            m: *m,
+            r,
+        };
+        Ok((k.to_vec(), c1, state))
+    }
+
+    /// Second step : checks t_hat against the header and returns c_2
+    ///
+    /// Input : t_hat in B^(384*k)
+    pub fn encaps2(&self, state: EncapsState, t_hat: &[u8]) -> Result<Vec<u8>, IncrementalError> {
+        self.join(&state.header, t_hat)?;
+        check_modulus(t_hat)?;
+
+        let mut c2 = vec![0u8; 32 * self.0.d_v];
+        self.0.encrypt_v_into(t_hat, &state.m, &state.r, &mut c2);
+        Ok(c2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::KyberParams;
+    use crate::hash::{Sha3Suite, h};
+    use crate::kyber::kem_scheme::ParameterSet;
+    use crate::kyber::keys::EncapsulationKey;
+
+    #[test]
+    fn basics() {
+        for set in ParameterSet::ALL {
+            let ml_kem = MlKem::<KyberParams>::from_parameter_set(set);
+            let (ek, dk) = ml_kem.key_gen();
+            let ek = EncapsulationKey::from_bytes(set, &ek).unwrap();
+            let header = ek.header();
+            assert_eq!(ek.t_hat().len(), set.t_hat_len());
+            assert_eq!(
+                EncapsulationKey::from_parts(set, &header, ek.t_hat()).unwrap(),
+                ek
+            );
+
+            let m = h(b"incremental encapsulation");
+            let (k, c1, state) = ml_kem.encaps1_internal(&header, &m).unwrap();
+            assert_eq!(c1.len(), set.ciphertext_u_len());
+            let c2 = ml_kem.encaps2(state, ek.t_hat()).unwrap();
+            assert_eq!(c2.len(), set.ciphertext_v_len());
+            let c = [c1, c2].concat();
+            assert_eq!(
+                ml_kem.encaps_internal(ek.as_bytes(), &m),
+                (k.clone(), c.clone())
+            );
+            assert_eq!(ml_kem.decaps(&dk, &c), k);
+        }
+    }
+
+    /// Sha3Suite with another H, to check that every step uses the H of the suite
+    struct OtherHSuite;
+
+    impl HashSuite for OtherHSuite {
+        type Xof = <Sha3Suite as HashSuite>::Xof;
+
+        fn h(s: &[u8]) -> [u8; 32] {
+            Sha3Suite::j(s)
+        }
+
+        fn g(c: &[u8]) -> ([u8; 32], [u8; 32]) {
+            Sha3Suite::g(c)
+        }
+
+        fn j(s: &[u8]) -> [u8; 32] {
+            Sha3Suite::j(s)
+        }
+
+        fn prf(eta: usize, s: &[u8; 32], b: &[u8; 1]) -> Vec<u8> {
+            Sha3Suite::prf(eta, s, b)
+        }
+
+        fn xof(bytes: &[u8; 34]) -> Self::Xof {
+            Sha3Suite::xof(bytes)
+        }
+
+        type XofX4 = <Sha3Suite as HashSuite>::XofX4;
+
+        fn xof_x4(bytes: [&[u8; 34]; 4]) -> Self::XofX4 {
+            Sha3Suite::xof_x4(bytes)
+        }
+    }
+
+    #[test]
+    fn hash_suite() {
+        let ml_kem = MlKem::<KyberParams, OtherHSuite>::from_parameter_set(ParameterSet::MlKem768);
+        let (ek, dk) = ml_kem.key_gen();
+        let header = ml_kem.header(&ek);
+        assert_ne!(header[32..], h(&ek));
+
+        let m = h(b"incremental encapsulation");
+        let (k, c1, state) = ml_kem.encaps1_internal(&header, &m).unwrap();
+        let c2 = ml_kem.encaps2(state, &ek[..384 * 3]).unwrap();
+        let c = [c1, c2].concat();
+        assert_eq!(ml_kem.encaps_internal(&ek, &m), (k.clone(), c.clone()));
+        assert_eq!(ml_kem.decaps(&dk, &c), k);
+
+        // A header hashed with the H of another suite is rejected by encaps2
+        let ek_default = EncapsulationKey::from_bytes(ParameterSet::MlKem768, &ek).unwrap();
+        let (_, _, state) = ml_kem.encaps1_internal(&ek_default.header(), &m).unwrap();
+        assert_eq!(
+            ml_kem.encaps2(state, ek_default.t_hat()),
+            Err(IncrementalError::HashMismatch)
+        );
+    }
+}
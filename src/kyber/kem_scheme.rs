@@ -1,14 +1,113 @@
 use rand::RngCore;
 use rand::rngs::OsRng;
 
-use crate::hash::{g, h, j};
+use crate::hash::{HashSuite, Sha3Suite};
 use crate::{constants::PolyParams, kyber::pke_scheme::KPke};
 
-pub struct MlKem<P: PolyParams>(pub KPke<P>);
+/// Approved parameter sets of ML-KEM (Table 2 FIPS 203)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParameterSet {
+    MlKem512,
+    MlKem768,
+    MlKem1024,
+}
+
+impl ParameterSet {
+    pub const ALL: [ParameterSet; 3] = [
+        ParameterSet::MlKem512,
+        ParameterSet::MlKem768,
+        ParameterSet::MlKem1024,
+    ];
+
+    /// (k, eta_1, eta_2, d_u, d_v)
+    pub fn params(&self) -> (usize, usize, usize, usize, usize) {
+        match self {
+            ParameterSet::MlKem512 => (2, 3, 2, 10, 4),
+            ParameterSet::MlKem768 => (3, 2, 2, 10, 4),
+            ParameterSet::MlKem1024 => (4, 2, 2, 11, 5),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParameterSet::MlKem512 => "ML-KEM-512",
+            ParameterSet::MlKem768 => "ML-KEM-768",
+            ParameterSet::MlKem1024 => "ML-KEM-1024",
+        }
+    }
+
+    /// Identifier of the parameter set in the binary formats of this crate (KEM-DEM,
+    /// envelope, container) : 1 ML-KEM-512, 2 ML-KEM-768, 3 ML-KEM-1024
+    pub fn id(&self) -> u8 {
+        match self {
+            ParameterSet::MlKem512 => 1,
+            ParameterSet::MlKem768 => 2,
+            ParameterSet::MlKem1024 => 3,
+        }
+    }
+
+    /// Finds the parameter set from its identifier
+    pub fn from_id(id: u8) -> Option<Self> {
+        ParameterSet::ALL.into_iter().find(|set| set.id() == id)
+    }
+
+    /// Length of an encapsulation key ek in bytes : 384*k + 32
+    pub fn ek_len(&self) -> usize {
+        384 * self.params().0 + 32
+    }
+
+    /// Length of a decapsulation key dk in bytes : 768*k + 96
+    pub fn dk_len(&self) -> usize {
+        768 * self.params().0 + 96
+    }
+
+    /// Length of a ciphertext c in bytes : 32 * (d_u*k + d_v)
+    pub fn ciphertext_len(&self) -> usize {
+        let (k, _, _, d_u, d_v) = self.params();
+        32 * (d_u * k + d_v)
+    }
 
-impl<P: PolyParams> MlKem<P> {
+    /// Length of t_hat, the first part of ek : 384*k
+    pub fn t_hat_len(&self) -> usize {
+        384 * self.params().0
+    }
+
+    /// Length of c_1, the part of c computed from rho and H(ek) : 32 * d_u*k
+    pub fn ciphertext_u_len(&self) -> usize {
+        let (k, _, _, d_u, _) = self.params();
+        32 * d_u * k
+    }
+
+    /// Length of c_2, the part of c computed from t_hat : 32 * d_v
+    pub fn ciphertext_v_len(&self) -> usize {
+        32 * self.params().4
+    }
+
+    /// Finds the parameter set from the length of an encapsulation key
+    pub fn from_ek_len(len: usize) -> Option<Self> {
+        ParameterSet::ALL
+            .into_iter()
+            .find(|set| set.ek_len() == len)
+    }
+
+    /// Finds the parameter set from the length of a decapsulation key
+    pub fn from_dk_len(len: usize) -> Option<Self> {
+        ParameterSet::ALL
+            .into_iter()
+            .find(|set| set.dk_len() == len)
+    }
+}
+
+pub struct MlKem<P: PolyParams, H: HashSuite = Sha3Suite>(pub KPke<P, H>);
+
+impl<P: PolyParams, H: HashSuite> MlKem<P, H> {
     pub fn new(k: usize, eta_1: usize, eta_2: usize, d_u: usize, d_v: usize) -> Self {
-        MlKem(KPke::<P>::new(k, eta_1, eta_2, d_u, d_v))
+        MlKem(KPke::<P, H>::new(k, eta_1, eta_2, d_u, d_v))
+    }
+
+    pub fn from_parameter_set(set: ParameterSet) -> Self {
+        let (k, eta_1, eta_2, d_u, d_v) = set.params();
+        MlKem::<P, H>::new(k, eta_1, eta_2, d_u, d_v)
     }
 
     /// Algorithm 16 (FIPS 203) : ML-KEM.KeyGen_internal(d, z)
@@ -21,7 +120,7 @@ impl<P: PolyParams> MlKem<P> {
     pub fn key_gen_internal(&self, d: &[u8; 32], z: &[u8; 32]) -> (Vec<u8>, Vec<u8>) {
         let (ek_pke, mut dk) = self.0.key_gen(d);
         dk.extend_from_slice(&ek_pke);
-        dk.extend_from_slice(&h(&ek_pke));
+        dk.extend_from_slice(&H::h(&ek_pke));
         dk.extend_from_slice(z);
 
         (ek_pke, dk)
@@ -36,8 +135,8 @@ impl<P: PolyParams> MlKem<P> {
     /// Output : ciphertext c in B^(32 * (d_u*k + d_v))
     pub fn encaps_internal(&self, ek: &[u8], m: &[u8; 32]) -> (Vec<u8>, Vec<u8>) {
         let mut g_seed = m.to_vec();
-        g_seed.extend_from_slice(&h(ek));
-        let (k, r) = g(&g_seed);
+        g_seed.extend_from_slice(&H::h(ek));
+        let (k, r) = H::g(&g_seed);
         let c = self.0.encrypt(ek, m, &r);
 
         (k.to_vec(), c)
@@ -59,12 +158,12 @@ impl<P: PolyParams> MlKem<P> {
         let mut g_hash = vec![];
         g_hash.extend_from_slice(&m_prime);
         g_hash.extend_from_slice(h);
-        let (mut k_prime, r_prime) = g(&g_hash);
+        let (mut k_prime, r_prime) = H::g(&g_hash);
 
         let mut j_hash = vec![];
         j_hash.extend_from_slice(z);
         j_hash.extend_from_slice(c);
-        let k_bar = j(&j_hash);
+        let k_bar = H::j(&j_hash);
 
         let m_prime_slice: [u8; 32] = m_prime.as_slice().try_into().expect("");
         let c_prime = self.0.encrypt(ek_pke, &m_prime_slice, &r_prime);
@@ -119,6 +218,7 @@ impl<P: PolyParams> MlKem<P> {
 mod tests {
     use super::*;
     use crate::constants::KyberParams;
+    use crate::hash::{h, j};
 
     #[test]
     fn basics() {
@@ -143,5 +243,9 @@ mod tests {
 
         let k_decaps = kem_scheme.decaps(&dk, &c);
         assert_eq!(k_decaps, k);
+
+        assert_eq!(ParameterSet::ALL.map(|set| set.id()), [1, 2, 3]);
+        assert_eq!(ParameterSet::from_id(2), Some(ParameterSet::MlKem768));
+        assert_eq!(ParameterSet::from_id(0), None);
     }
 }
@@ -0,0 +1,268 @@
+use core::fmt;
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+use subtle::ConstantTimeEq;
+
+use crate::constants::{KyberParams, PolyParams};
+use crate::conversion::{byte_decode, byte_encode};
+use crate::hash::h;
+use crate::kyber::incremental::{self, HEADER_LEN, IncrementalError};
+use crate::kyber::kem_scheme::{MlKem, ParameterSet};
+
+/// Errors raised when importing an ML-KEM key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyError {
+    /// The key does not have the length required by its parameter set
+    InvalidLength { expected: usize, found: usize },
+    /// A coefficient of ek is not reduced modulo q (Section 7.2 FIPS 203)
+    InvalidModulus,
+    /// The hash H(ek) stored in dk does not match ek (Section 7.3 FIPS 203)
+    InvalidHash,
+    /// The seed is needed but the key was imported in expanded form only
+    MissingSeed,
+    /// The seed and the expanded key describe different keys
+    InconsistentSeed,
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::InvalidLength { expected, found } => {
+                write!(f, "invalid key length {found}, expected {expected}")
+            }
+            KeyError::InvalidModulus => write!(f, "encapsulation key is not reduced modulo q"),
+            KeyError::InvalidHash => write!(f, "hash of the encapsulation key does not match"),
+            KeyError::MissingSeed => write!(f, "decapsulation key seed is not available"),
+            KeyError::InconsistentSeed => write!(f, "seed and expanded key do not match"),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+pub(crate) fn check_length(expected: usize, found: usize) -> Result<(), KeyError> {
+    if expected != found {
+        return Err(KeyError::InvalidLength { expected, found });
+    }
+    Ok(())
+}
+
+/// Modulus check (Section 7.2 FIPS 203) : ByteEncode_12(ByteDecode_12(t)) == t
+pub(crate) fn check_modulus(t: &[u8]) -> Result<(), KeyError> {
+    if byte_encode(&byte_decode(t, 12, KyberParams::Q), 12) != t {
+        return Err(KeyError::InvalidModulus);
+    }
+    Ok(())
+}
+
+/// ML-KEM encapsulation key, tied to its parameter set
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncapsulationKey {
+    parameter_set: ParameterSet,
+    bytes: Vec<u8>,
+}
+
+impl EncapsulationKey {
+    /// Imports ek after the input checks of ML-KEM.Encaps
+    pub fn from_bytes(parameter_set: ParameterSet, bytes: &[u8]) -> Result<Self, KeyError> {
+        check_length(parameter_set.ek_len(), bytes.len())?;
+        check_modulus(&bytes[..bytes.len() - 32])?;
+        Ok(EncapsulationKey {
+            parameter_set,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    pub fn parameter_set(&self) -> ParameterSet {
+        self.parameter_set
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// ML-KEM.Encaps(ek) : returns (K, c)
+    pub fn encaps(&self) -> (Vec<u8>, Vec<u8>) {
+        MlKem::<KyberParams>::from_parameter_set(self.parameter_set).encaps(&self.bytes)
+    }
+
+    /// Header rho || H(ek) : all `MlKem::encaps1` needs
+    pub fn header(&self) -> [u8; HEADER_LEN] {
+        MlKem::<KyberParams>::from_parameter_set(self.parameter_set).header(&self.bytes)
+    }
+
+    /// Vector t_hat : all `MlKem::encaps2` needs besides the header
+    pub fn t_hat(&self) -> &[u8] {
+        &self.bytes[..self.bytes.len() - 32]
+    }
+
+    /// Rebuilds ek from its header and t_hat, checking t_hat against H(ek)
+    pub fn from_parts(
+        parameter_set: ParameterSet,
+        header: &[u8],
+        t_hat: &[u8],
+    ) -> Result<Self, IncrementalError> {
+        incremental::check_length(HEADER_LEN, header.len())?;
+        let ek = MlKem::<KyberParams>::from_parameter_set(parameter_set).join(header, t_hat)?;
+        Ok(EncapsulationKey::from_bytes(parameter_set, &ek)?)
+    }
+}
+
+impl fmt::Debug for EncapsulationKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "EncapsulationKey({}, {})",
+            self.parameter_set.name(),
+            hex::encode(&self.bytes[..8])
+        )
+    }
+}
+
+/// ML-KEM decapsulation key, tied to its parameter set.
+///
+/// The 64-byte seed d || z is kept when it is known, so that the key can be
+/// exported in its compact form.
+#[derive(Clone)]
+pub struct DecapsulationKey {
+    parameter_set: ParameterSet,
+    seed: Option<[u8; 64]>,
+    expanded: Vec<u8>,
+}
+
+impl DecapsulationKey {
+    /// Generates a fresh key pair, keeping the seed
+    pub fn generate(parameter_set: ParameterSet) -> Self {
+        let mut seed = [0u8; 64];
+        OsRng.fill_bytes(&mut seed);
+        DecapsulationKey::from_seed(parameter_set, &seed)
+    }
+
+    /// Expands the seed d || z with ML-KEM.KeyGen_internal(d, z)
+    pub fn from_seed(parameter_set: ParameterSet, seed: &[u8; 64]) -> Self {
+        let d: [u8; 32] = seed[..32].try_into().unwrap();
+        let z: [u8; 32] = seed[32..].try_into().unwrap();
+        let (_, expanded) =
+            MlKem::<KyberParams>::from_parameter_set(parameter_set).key_gen_internal(&d, &z);
+        DecapsulationKey {
+            parameter_set,
+            seed: Some(*seed),
+            expanded,
+        }
+    }
+
+    /// Imports an expanded dk after the input checks of ML-KEM.Decaps
+    pub fn from_expanded(parameter_set: ParameterSet, bytes: &[u8]) -> Result<Self, KeyError> {
+        check_length(parameter_set.dk_len(), bytes.len())?;
+        let k = parameter_set.params().0;
+        let ek = &bytes[384 * k..768 * k + 32];
+        check_modulus(&ek[..384 * k])?;
+        if h(ek) != bytes[768 * k + 32..768 * k + 64] {
+            return Err(KeyError::InvalidHash);
+        }
+        Ok(DecapsulationKey {
+            parameter_set,
+            seed: None,
+            expanded: bytes.to_vec(),
+        })
+    }
+
+    /// Imports both forms and checks that the seed expands to the given key
+    pub fn from_seed_and_expanded(
+        parameter_set: ParameterSet,
+        seed: &[u8; 64],
+        expanded: &[u8],
+    ) -> Result<Self, KeyError> {
+        check_length(parameter_set.dk_len(), expanded.len())?;
+        let key = DecapsulationKey::from_seed(parameter_set, seed);
+        if !bool::from(key.expanded.ct_eq(expanded)) {
+            return Err(KeyError::InconsistentSeed);
+        }
+        Ok(key)
+    }
+
+    pub fn parameter_set(&self) -> ParameterSet {
+        self.parameter_set
+    }
+
+    /// Seed d || z, when the key was generated or imported from it
+    pub fn seed(&self) -> Option<&[u8; 64]> {
+        self.seed.as_ref()
+    }
+
+    /// Expanded decapsulation key dk in B^(768*k + 96)
+    pub fn expanded(&self) -> &[u8] {
+        &self.expanded
+    }
+
+    /// Encapsulation key embedded in dk
+    pub fn encapsulation_key(&self) -> EncapsulationKey {
+        let k = self.parameter_set.params().0;
+        EncapsulationKey {
+            parameter_set: self.parameter_set,
+            bytes: self.expanded[384 * k..768 * k + 32].to_vec(),
+        }
+    }
+
+    /// ML-KEM.Decaps(dk, c) : returns K
+    pub fn decaps(&self, c: &[u8]) -> Vec<u8> {
+        MlKem::<KyberParams>::from_parameter_set(self.parameter_set).decaps(&self.expanded, c)
+    }
+}
+
+impl fmt::Debug for DecapsulationKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DecapsulationKey({}, ..)", self.parameter_set.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for set in ParameterSet::ALL {
+            let dk = DecapsulationKey::generate(set);
+            let ek = dk.encapsulation_key();
+            assert_eq!(ek.as_bytes().len(), set.ek_len());
+            let (k, c) = ek.encaps();
+            assert_eq!(c.len(), set.ciphertext_len());
+            assert_eq!(dk.decaps(&c), k);
+
+            let seed = *dk.seed().unwrap();
+            let imported = DecapsulationKey::from_expanded(set, dk.expanded()).unwrap();
+            assert!(imported.seed().is_none());
+            assert!(DecapsulationKey::from_seed_and_expanded(set, &seed, dk.expanded()).is_ok());
+            let mut other = seed;
+            other[63] ^= 1;
+            assert_eq!(
+                DecapsulationKey::from_seed_and_expanded(set, &other, dk.expanded()).unwrap_err(),
+                KeyError::InconsistentSeed
+            );
+
+            let mut bad = ek.as_bytes().to_vec();
+            bad[0] = 0xff;
+            bad[1] |= 0x0f;
+            assert_eq!(
+                EncapsulationKey::from_bytes(set, &bad),
+                Err(KeyError::InvalidModulus)
+            );
+            let mut bad = dk.expanded().to_vec();
+            let k = set.params().0;
+            bad[768 * k + 40] ^= 1;
+            assert_eq!(
+                DecapsulationKey::from_expanded(set, &bad).unwrap_err(),
+                KeyError::InvalidHash
+            );
+            assert_eq!(
+                EncapsulationKey::from_bytes(set, &bad),
+                Err(KeyError::InvalidLength {
+                    expected: set.ek_len(),
+                    found: set.dk_len()
+                })
+            );
+        }
+    }
+}
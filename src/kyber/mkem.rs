@@ -0,0 +1,293 @@
+use core::fmt;
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+use subtle::ConstantTimeEq;
+
+use crate::constants::PolyParams;
+use crate::hash::HashSuite;
+use crate::kyber::kem_scheme::{MlKem, ParameterSet};
+use crate::kyber::keys::{KeyError, check_modulus};
+
+/// Errors raised by the multi-recipient KEM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MkemError {
+    NoRecipients,
+    /// The encapsulation key at this index has another seed rho than the first one
+    RhoMismatch(usize),
+    /// A key or ciphertext component does not have the length of the parameter set
+    InvalidLength {
+        expected: usize,
+        found: usize,
+    },
+    Key(KeyError),
+}
+
+impl fmt::Display for MkemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MkemError::NoRecipients => write!(f, "no recipients"),
+            MkemError::RhoMismatch(index) => {
+                write!(f, "encapsulation key {index} does not share the seed rho")
+            }
+            MkemError::InvalidLength { expected, found } => {
+                write!(f, "invalid length {found}, expected {expected}")
+            }
+            MkemError::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for MkemError {}
+
+impl From<KeyError> for MkemError {
+    fn from(err: KeyError) -> Self {
+        MkemError::Key(err)
+    }
+}
+
+fn check_length(expected: usize, found: usize) -> Result<(), MkemError> {
+    if expected != found {
+        return Err(MkemError::InvalidLength { expected, found });
+    }
+    Ok(())
+}
+
+/// Ciphertext of the multi-recipient KEM : c_1 (encoding of u), shared by all the
+/// recipients, and one c_2 (encoding of v) per recipient, in the order of the keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiCiphertext {
+    pub u: Vec<u8>,
+    pub v: Vec<Vec<u8>>,
+}
+
+impl MultiCiphertext {
+    /// c_1 || c_2 of each recipient
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.u.clone();
+        for v in &self.v {
+            out.extend_from_slice(v);
+        }
+        out
+    }
+
+    pub fn from_bytes(parameter_set: ParameterSet, bytes: &[u8]) -> Result<Self, MkemError> {
+        let u_len = parameter_set.ciphertext_u_len();
+        let v_len = parameter_set.ciphertext_v_len();
+        let recipients = (bytes.len().saturating_sub(u_len) / v_len).max(1);
+        check_length(u_len + recipients * v_len, bytes.len())?;
+        let (u, rest) = bytes.split_at(u_len);
+        Ok(MultiCiphertext {
+            u: u.to_vec(),
+            v: rest.chunks(v_len).map(<[u8]>::to_vec).collect(),
+        })
+    }
+}
+
+/// Multi-recipient ML-KEM (mKEM) : the same key K encapsulated to many recipients
+/// whose encapsulation keys share the seed rho of the matrix A_hat.
+///
+/// K-PKE encryption with the same y and e_1 gives the same u = A^T y + e_1 for all
+/// of them, so it is sent once, followed by v_i = t_i^T y + e_2,i + Decompress(m)
+/// for each recipient : 32*d_v bytes (128 for ML-KEM-768) instead of a full
+/// ciphertext. This is the decomposable mPKE of Katsumata, Kwiatkowski, Pintore and
+/// Prest ("Scalable ciphertext compression techniques for post-quantum KEMs",
+/// ASIACRYPT 2020) applied to K-PKE, with their FO transform, in which the shared
+/// part uses randomness derived from m and each recipient part randomness derived
+/// from m and the recipient key :
+///
+/// - (K, r) = G(m || H(rho)), r giving y and e_1;
+/// - (_, sigma_i) = G(m || H(ek_i)), sigma_i giving e_2,i = SamplePolyCBD_eta2(
+///   PRF_eta2(sigma_i, 2k)), so that no two recipients share their e_2;
+/// - decapsulation re-encrypts m' into (u', v_i') with its own t_hat and H(ek) and
+///   returns K' only if (u', v_i') = (u, v_i), J(z || u || v_i) otherwise.
+///
+/// The keys and ciphertexts are not those of FIPS 203 : a recipient key pair has the
+/// ML-KEM format, but single-recipient ciphertexts do not decapsulate with
+/// ML-KEM.Decaps.
+impl<P: PolyParams, H: HashSuite> MlKem<P, H> {
+    /// Key pair under the common seed rho, in the ML-KEM formats :
+    /// sigma is the second half of G(d || k), and dk = dk_pke || ek || H(ek) || z
+    pub fn key_gen_with_rho_internal(
+        &self,
+        rho: &[u8; 32],
+        d: &[u8; 32],
+        z: &[u8; 32],
+    ) -> (Vec<u8>, Vec<u8>) {
+        let mut d_tmp = d.to_vec();
+        d_tmp.push(self.0.k as u8);
+        let (_, sigma) = H::g(&d_tmp);
+
+        let mut ek = vec![0u8; self.0.ek_len()];
+        let mut dk = vec![0u8; self.0.dk_len()];
+        self.0
+            .key_gen_from_seeds_into(rho, &sigma, &mut ek, &mut dk);
+        dk.extend_from_slice(&ek);
+        dk.extend_from_slice(&H::h(&ek));
+        dk.extend_from_slice(z);
+        (ek, dk)
+    }
+
+    pub fn key_gen_with_rho(&self, rho: &[u8; 32]) -> (Vec<u8>, Vec<u8>) {
+        let mut d = [0u8; 32];
+        OsRng.fill_bytes(&mut d);
+        let mut z = [0u8; 32];
+        OsRng.fill_bytes(&mut z);
+        self.key_gen_with_rho_internal(rho, &d, &z)
+    }
+
+    /// Encapsulates a random key to all the encapsulation keys
+    pub fn mencaps(&self, eks: &[&[u8]]) -> Result<(Vec<u8>, MultiCiphertext), MkemError> {
+        let mut m = [0u8; 32];
+        OsRng.fill_bytes(&mut m);
+        self.mencaps_internal(eks, &m)
+    }
+
+    /// Deterministic encapsulation from the randomness m : returns (K, ciphertext)
+    pub fn mencaps_internal(
+        &self,
+        eks: &[&[u8]],
+        m: &[u8; 32],
+    ) -> Result<(Vec<u8>, MultiCiphertext), MkemError> {
+        let first = eks.first().ok_or(MkemError::NoRecipients)?;
+        let rho_offset = 384 * self.0.k;
+        for (index, ek) in eks.iter().enumerate() {
+            check_length(self.0.ek_len(), ek.len())?;
+            check_modulus(&ek[..rho_offset])?;
+            if ek[rho_offset..] != first[rho_offset..] {
+                return Err(MkemError::RhoMismatch(index));
+            }
+        }
+        let rho = &first[rho_offset..];
+        let (k, r) = self.g(m, rho);
+
+        let mut u = vec![0u8; 32 * self.0.d_u * self.0.k];
+        self.0.encrypt_u_into(rho, &r, &mut u);
+        let v = eks
+            .iter()
+            .map(|ek| {
+                let mut v = vec![0u8; 32 * self.0.d_v];
+                let sigma = self.e2_seed(m, &H::h(ek));
+                self.0
+                    .encrypt_v_with_e2_into(&ek[..rho_offset], m, &r, &sigma, &mut v);
+                v
+            })
+            .collect();
+        Ok((k.to_vec(), MultiCiphertext { u, v }))
+    }
+
+    /// Decapsulates the key from the shared u and the v of this recipient
+    ///
+    /// Input : decapsulation key dk in B^(768*k + 96)
+    /// Input : c_1 in B^(32 * d_u*k) and c_2 in B^(32 * d_v)
+    /// Output : shared secret key K in B^32
+    pub fn mdecaps(&self, dk: &[u8], u: &[u8], v: &[u8]) -> Result<Vec<u8>, MkemError> {
+        let k = self.0.k;
+        check_length(768 * k + 96, dk.len())?;
+        check_length(32 * self.0.d_u * k, u.len())?;
+        check_length(32 * self.0.d_v, v.len())?;
+        let dk_pke = &dk[..384 * k];
+        let t_hat = &dk[384 * k..768 * k];
+        let rho = &dk[768 * k..768 * k + 32];
+        let h_ek = &dk[768 * k + 32..768 * k + 64];
+        let z = &dk[768 * k + 64..];
+
+        let c = [u, v].concat();
+        let m_prime: [u8; 32] = self.0.decrypt(dk_pke, &c).try_into().unwrap();
+        let (k_prime, r_prime) = self.g(&m_prime, rho);
+
+        let mut c_prime = vec![0u8; c.len()];
+        let (u_prime, v_prime) = c_prime.split_at_mut(u.len());
+        self.0.encrypt_u_into(rho, &r_prime, u_prime);
+        let sigma = self.e2_seed(&m_prime, h_ek);
+        self.0
+            .encrypt_v_with_e2_into(t_hat, &m_prime, &r_prime, &sigma, v_prime);
+
+        if bool::from(c.ct_eq(&c_prime)) {
+            Ok(k_prime.to_vec())
+        } else {
+            Ok(H::j(&[z, &c].concat()).to_vec())
+        }
+    }
+
+    /// (K, r) = G(m || H(rho))
+    fn g(&self, m: &[u8; 32], rho: &[u8]) -> ([u8; 32], [u8; 32]) {
+        H::g(&[m.as_slice(), &H::h(rho)].concat())
+    }
+
+    /// Seed sigma_i of e_2,i : second half of G(m || H(ek_i))
+    fn e2_seed(&self, m: &[u8; 32], h_ek: &[u8]) -> [u8; 32] {
+        H::g(&[m.as_slice(), h_ek].concat()).1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::KyberParams;
+    use crate::hash::{Sha3Suite, h};
+    use crate::polynomial::Polynomial;
+
+    #[test]
+    fn basics() {
+        for set in ParameterSet::ALL {
+            let ml_kem = MlKem::<KyberParams>::from_parameter_set(set);
+            let rho = h(b"common seed rho");
+            let keys: Vec<_> = (0..3u8)
+                .map(|i| ml_kem.key_gen_with_rho_internal(&rho, &h(&[i]), &h(&[i, i])))
+                .collect();
+            let eks: Vec<&[u8]> = keys.iter().map(|(ek, _)| ek.as_slice()).collect();
+
+            let (k, c) = ml_kem
+                .mencaps_internal(&eks, &h(b"mencaps randomness"))
+                .unwrap();
+            assert_eq!(c.u.len(), set.ciphertext_u_len());
+            assert_eq!(c.v.len(), 3);
+            for ((_, dk), v) in keys.iter().zip(&c.v) {
+                assert_eq!(dk.len(), set.dk_len());
+                assert_eq!(ml_kem.mdecaps(dk, &c.u, v).unwrap(), k);
+            }
+            assert_eq!(MultiCiphertext::from_bytes(set, &c.to_bytes()).unwrap(), c);
+        }
+    }
+
+    #[test]
+    fn recipient_noise() {
+        let set = ParameterSet::MlKem768;
+        let ml_kem = MlKem::<KyberParams>::from_parameter_set(set);
+        let (k, eta_2) = (3, 2);
+        let rho = h(b"common seed rho");
+        let keys: Vec<_> = (0..2u8)
+            .map(|i| ml_kem.key_gen_with_rho_internal(&rho, &h(&[i]), &h(&[i, i])))
+            .collect();
+        let eks: Vec<&[u8]> = keys.iter().map(|(ek, _)| ek.as_slice()).collect();
+        let m = h(b"mencaps randomness");
+        let (_, c) = ml_kem.mencaps_internal(&eks, &m).unwrap();
+        let (_, r) = ml_kem.g(&m, &rho);
+
+        // Each v_i carries the e_2 of its own recipient, not the e_2 of r
+        let e_2: Vec<_> = eks
+            .iter()
+            .map(|ek| {
+                let sigma = ml_kem.e2_seed(&m, &h(ek));
+                let prf = Sha3Suite::prf(eta_2, &sigma, &[2 * k as u8]);
+                (
+                    sigma,
+                    Polynomial::<KyberParams>::sample_poly_cbd(&prf, eta_2).values(),
+                )
+            })
+            .collect();
+        assert_ne!(e_2[0].1, e_2[1].1);
+        for ((ek, v), (sigma, _)) in eks.iter().zip(&c.v).zip(&e_2) {
+            let t_hat = &ek[..384 * k];
+            let mut expected = vec![0u8; v.len()];
+            ml_kem
+                .0
+                .encrypt_v_with_e2_into(t_hat, &m, &r, sigma, &mut expected);
+            assert_eq!(v, &expected);
+            let mut shared = vec![0u8; v.len()];
+            ml_kem.0.encrypt_v_into(t_hat, &m, &r, &mut shared);
+            assert_ne!(v, &shared);
+        }
+    }
+}
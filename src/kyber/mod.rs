@@ -1,5 +1,9 @@
+pub mod incremental;
 pub mod kem_scheme;
+pub mod keys;
+pub mod mkem;
 pub mod pke_scheme;
+pub mod pke_streaming;
 
 use crate::constants::KyberParams;
 use crate::polynomial::Polynomial;
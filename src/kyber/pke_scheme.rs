@@ -1,29 +1,71 @@
 use crate::constants::PolyParams;
 use crate::conversion::{byte_decode, byte_encode, compress, decompress};
-use crate::hash::{g, prf};
+use crate::hash::{HashSuite, Sha3Suite};
 use crate::polynomial::{Polynomial, PolynomialNTT};
 
-pub struct KPke<P: PolyParams> {
+pub struct KPke<P: PolyParams, H: HashSuite = Sha3Suite> {
     pub k: usize,
-    eta_1: usize,
-    eta_2: usize,
-    d_u: usize,
-    d_v: usize,
-    _marker: std::marker::PhantomData<P>,
+    pub(crate) eta_1: usize,
+    pub(crate) eta_2: usize,
+    pub(crate) d_u: usize,
+    pub(crate) d_v: usize,
+    _marker: std::marker::PhantomData<(P, H)>,
 }
 
-impl<P: PolyParams> KPke<P> {
+impl<P: PolyParams, H: HashSuite> KPke<P, H> {
     pub fn new(k: usize, eta_1: usize, eta_2: usize, d_u: usize, d_v: usize) -> Self {
-        KPke::<P> {
+        KPke::<P, H> {
             k,
             eta_1,
             eta_2,
             d_u,
             d_v,
-            _marker: std::marker::PhantomData::<P>,
+            _marker: std::marker::PhantomData::<(P, H)>,
         }
     }
 
+    /// Length of an encryption key ek in bytes : 384*k + 32
+    pub fn ek_len(&self) -> usize {
+        384 * self.k + 32
+    }
+
+    /// Length of a decryption key dk in bytes : 384*k
+    pub fn dk_len(&self) -> usize {
+        384 * self.k
+    }
+
+    /// Length of a ciphertext c in bytes : 32 * (d_u*k + d_v)
+    pub fn ciphertext_len(&self) -> usize {
+        32 * (self.d_u * self.k + self.d_v)
+    }
+
+    /// Generates the matrix A_hat in T_Q^(k*k) from the seed rho (lines 3-7 of Algorithm 13),
+    /// with A_hat[i, j] = SampleNTT(rho || j || i) stored at index i*k + j.
+    /// Entries are sampled four at a time with the batched XOF of the hash suite.
+    fn sample_matrix(&self, rho: &[u8]) -> Vec<PolynomialNTT<P>> {
+        let inputs: Vec<[u8; 34]> = (0..self.k * self.k)
+            .map(|idx| {
+                let mut input = [0u8; 34];
+                input[0..32].copy_from_slice(rho);
+                input[32] = (idx % self.k) as u8;
+                input[33] = (idx / self.k) as u8;
+                input
+            })
+            .collect();
+
+        let mut a_ntt = Vec::with_capacity(self.k * self.k);
+        let mut batches = inputs.chunks_exact(4);
+        for batch in &mut batches {
+            a_ntt.extend(PolynomialNTT::<P>::sample_ntt_x4::<H>([
+                &batch[0], &batch[1], &batch[2], &batch[3],
+            ]));
+        }
+        for input in batches.remainder() {
+            a_ntt.push(PolynomialNTT::<P>::sample_ntt_with::<H>(input));
+        }
+        a_ntt
+    }
+
     /// Algorithm 13 (FIPS 203) : K-PKE.KeyGen(d)
     ///
     /// Input : randomness d in B^32
@@ -32,25 +74,16 @@ impl<P: PolyParams> KPke<P> {
     pub fn key_gen(&self, d: &[u8; 32]) -> (Vec<u8>, Vec<u8>) {
         let mut d_tmp = d.to_vec();
         d_tmp.extend_from_slice(&[self.k as u8]);
-        let (rho, gamma) = g(&d_tmp);
+        let (rho, gamma) = H::g(&d_tmp);
 
         let mut n_var = 0usize;
 
-        let mut a_ntt: Vec<PolynomialNTT<P>> = Vec::with_capacity(self.k * self.k);
-        for i in 0..self.k {
-            for j in 0..self.k {
-                let mut input = [0u8; 34];
-                input[0..32].copy_from_slice(&rho);
-                input[32] = j as u8;
-                input[33] = i as u8;
-                a_ntt.push(PolynomialNTT::<P>::sample_ntt(&input));
-            }
-        }
+        let a_ntt = self.sample_matrix(&rho);
 
         let mut s: Vec<Polynomial<P>> = vec![];
         for _i in 0..self.k {
             s.push(Polynomial::<P>::sample_poly_cbd(
-                &prf(self.eta_1, &gamma, &[n_var as u8]),
+                &H::prf(self.eta_1, &gamma, &[n_var as u8]),
                 self.eta_1,
             ));
             n_var += 1;
@@ -59,7 +92,7 @@ impl<P: PolyParams> KPke<P> {
         let mut e: Vec<Polynomial<P>> = vec![];
         for _i in 0..self.k {
             e.push(Polynomial::<P>::sample_poly_cbd(
-                &prf(self.eta_1, &gamma, &[n_var as u8]),
+                &H::prf(self.eta_1, &gamma, &[n_var as u8]),
                 self.eta_1,
             ));
             n_var += 1;
@@ -85,13 +118,13 @@ impl<P: PolyParams> KPke<P> {
 
         let mut ek = Vec::new();
         for poly in &t_ntt {
-            ek.extend(byte_encode(&poly.coeffs, CONST_D));
+            ek.extend(byte_encode(&poly.values(), CONST_D));
         }
         ek.extend_from_slice(&rho);
 
         let mut dk = Vec::new();
         for poly in &s_ntt {
-            dk.extend(byte_encode(&poly.coeffs, CONST_D));
+            dk.extend(byte_encode(&poly.values(), CONST_D));
         }
 
         (ek, dk)
@@ -113,21 +146,12 @@ impl<P: PolyParams> KPke<P> {
         }
         let rho = &ek[384 * self.k..];
 
-        let mut a_ntt = Vec::with_capacity(self.k * self.k);
-        for i in 0..self.k {
-            for j in 0..self.k {
-                let mut input = [0u8; 34];
-                input[0..32].copy_from_slice(rho);
-                input[32] = j as u8;
-                input[33] = i as u8;
-                a_ntt.push(PolynomialNTT::<P>::sample_ntt(&input));
-            }
-        }
+        let a_ntt = self.sample_matrix(rho);
 
         let mut y = Vec::with_capacity(self.k);
         for _i in 0..self.k {
             y.push(Polynomial::<P>::sample_poly_cbd(
-                &prf(self.eta_1, r, &[n_var as u8]),
+                &H::prf(self.eta_1, r, &[n_var as u8]),
                 self.eta_1,
             ));
             n_var += 1;
@@ -136,13 +160,14 @@ impl<P: PolyParams> KPke<P> {
         let mut e_1 = Vec::with_capacity(self.k);
         for _i in 0..self.k {
             e_1.push(Polynomial::<P>::sample_poly_cbd(
-                &prf(self.eta_2, r, &[n_var as u8]),
+                &H::prf(self.eta_2, r, &[n_var as u8]),
                 self.eta_2,
             ));
             n_var += 1;
         }
 
-        let e_2 = Polynomial::<P>::sample_poly_cbd(&prf(self.eta_2, r, &[n_var as u8]), self.eta_2);
+        let e_2 =
+            Polynomial::<P>::sample_poly_cbd(&H::prf(self.eta_2, r, &[n_var as u8]), self.eta_2);
         let y_ntt: Vec<PolynomialNTT<P>> = y.iter().map(|p| p.to_ntt()).collect();
 
         let mut u = Vec::with_capacity(self.k);
@@ -170,7 +195,7 @@ impl<P: PolyParams> KPke<P> {
             let compressed: Vec<i64> = poly
                 .coeffs
                 .iter()
-                .map(|&c| compress(c, self.d_u, P::Q))
+                .map(|c| compress(c.value(), self.d_u, P::Q))
                 .collect();
             c1.extend(byte_encode(&compressed, self.d_u));
         }
@@ -178,7 +203,7 @@ impl<P: PolyParams> KPke<P> {
         let compressed_v: Vec<i64> = v
             .coeffs
             .iter()
-            .map(|&c| compress(c, self.d_v, P::Q))
+            .map(|c| compress(c.value(), self.d_v, P::Q))
             .collect();
         let c2 = byte_encode(&compressed_v, self.d_v);
 
@@ -232,7 +257,7 @@ impl<P: PolyParams> KPke<P> {
         let compressed_w: Vec<i64> = w
             .coeffs
             .iter()
-            .map(|&coeff| compress(coeff, 1, P::Q))
+            .map(|coeff| compress(coeff.value(), 1, P::Q))
             .collect();
 
         byte_encode(&compressed_w, 1)
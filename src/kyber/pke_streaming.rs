@@ -0,0 +1,216 @@
+use crate::constants::PolyParams;
+use crate::conversion::{byte_decode_into, byte_encode_into, compress, decompress};
+use crate::field::Zq;
+use crate::hash::HashSuite;
+use crate::kyber::pke_scheme::KPke;
+use crate::polynomial::{Polynomial, PolynomialNTT};
+
+/// Low-memory variants of K-PKE.KeyGen and K-PKE.Encrypt, for constrained devices.
+///
+/// The matrix A_hat is never materialized: each entry is sampled right before it is
+/// used in the matrix-vector product, and the secret/noise vectors are regenerated from
+/// their seed instead of being kept around. Encodings are written directly into
+/// caller-provided buffers, so only a handful of polynomials are alive at any time:
+/// the peak heap use stays under 4 KiB (tests/test_pke_streaming.rs).
+/// The outputs are byte-identical to `key_gen` and `encrypt`.
+impl<P: PolyParams, H: HashSuite> KPke<P, H> {
+    /// Low-memory version of Algorithm 13 (FIPS 203) : K-PKE.KeyGen(d)
+    ///
+    /// Input : randomness d in B^32
+    /// Output : ek in B^(384*k + 32), written into ek_out
+    /// Output : dk in B^(384*k), written into dk_out
+    pub fn key_gen_streaming(&self, d: &[u8; 32], ek_out: &mut [u8], dk_out: &mut [u8]) {
+        if ek_out.len() != self.ek_len() || dk_out.len() != self.dk_len() {
+            panic!("Unauthorized length for the output buffers")
+        }
+
+        let mut d_tmp = [0u8; 33];
+        d_tmp[0..32].copy_from_slice(d);
+        d_tmp[32] = self.k as u8;
+        let (rho, gamma) = H::g(&d_tmp);
+        self.key_gen_from_seeds_into(&rho, &gamma, ek_out, dk_out);
+    }
+
+    /// Lines 3-22 of Algorithm 13 (FIPS 203), from the seeds (rho, sigma) instead
+    /// of d, so that several key pairs can share the matrix A_hat
+    ///
+    /// Input : seed rho in B^32
+    /// Input : seed sigma in B^32
+    /// Output : ek in B^(384*k + 32), written into ek_out
+    /// Output : dk in B^(384*k), written into dk_out
+    pub fn key_gen_from_seeds_into(
+        &self,
+        rho: &[u8; 32],
+        sigma: &[u8; 32],
+        ek_out: &mut [u8],
+        dk_out: &mut [u8],
+    ) {
+        if ek_out.len() != self.ek_len() || dk_out.len() != self.dk_len() {
+            panic!("Unauthorized length for the output buffers")
+        }
+        for i in 0..self.k {
+            let mut t_i = self.noise(self.eta_1, sigma, self.k + i).to_ntt();
+            for j in 0..self.k {
+                let s_j = self.noise(self.eta_1, sigma, j).to_ntt();
+                t_i += &(&matrix_entry::<P, H>(rho, i, j) * &s_j);
+            }
+            encode_into(&t_i.coeffs, 12, &mut ek_out[384 * i..384 * (i + 1)]);
+        }
+        ek_out[384 * self.k..].copy_from_slice(rho);
+
+        for j in 0..self.k {
+            let s_j = self.noise(self.eta_1, sigma, j).to_ntt();
+            encode_into(&s_j.coeffs, 12, &mut dk_out[384 * j..384 * (j + 1)]);
+        }
+    }
+
+    /// Low-memory version of Algorithm 14 (FIPS 203) : K-PKE.Encrypt(ek, m, r)
+    ///
+    /// Input : encryption key ek in B^(384*k + 32)
+    /// Input : message m in B^32
+    /// Input : randomness r in B^32
+    /// Output : ciphertext c in B^(32 * (d_u * k + d_v)), written into c_out
+    pub fn encrypt_streaming(&self, ek: &[u8], m: &[u8; 32], r: &[u8; 32], c_out: &mut [u8]) {
+        if ek.len() != self.ek_len() || c_out.len() != self.ciphertext_len() {
+            panic!("Unauthorized length for ek or for the output buffer")
+        }
+        let (c1_out, c2_out) = c_out.split_at_mut(32 * self.d_u * self.k);
+        self.encrypt_u_into(&ek[384 * self.k..], r, c1_out);
+        self.encrypt_v_into(&ek[..384 * self.k], m, r, c2_out);
+    }
+
+    /// First part of Algorithm 14 (FIPS 203) : c_1 = ByteEncode_du(Compress_du(u)),
+    /// which only depends on the seed rho of ek
+    ///
+    /// Input : seed rho in B^32
+    /// Input : randomness r in B^32
+    /// Output : c_1 in B^(32 * d_u * k), written into c1_out
+    pub fn encrypt_u_into(&self, rho: &[u8], r: &[u8; 32], c1_out: &mut [u8]) {
+        if rho.len() != 32 || c1_out.len() != 32 * self.d_u * self.k {
+            panic!("Unauthorized length for rho or for the output buffer")
+        }
+        for i in 0..self.k {
+            let mut acc = PolynomialNTT::<P>::from(vec![Zq::<P>::zero(); P::N]);
+            for j in 0..self.k {
+                let y_j = self.noise(self.eta_1, r, j).to_ntt();
+                acc += &(&matrix_entry::<P, H>(rho, j, i) * &y_j);
+            }
+            let mut u_i = Polynomial::<P>::from_ntt(&acc);
+            u_i += &self.noise(self.eta_2, r, self.k + i);
+
+            let out = &mut c1_out[32 * self.d_u * i..32 * self.d_u * (i + 1)];
+            encode_compressed_into(&u_i.coeffs, self.d_u, out);
+        }
+    }
+
+    /// Second part of Algorithm 14 (FIPS 203) : c_2 = ByteEncode_dv(Compress_dv(v)),
+    /// which only depends on the vector t_hat of ek
+    ///
+    /// Input : t_hat in B^(384*k)
+    /// Input : message m in B^32
+    /// Input : randomness r in B^32
+    /// Output : c_2 in B^(32 * d_v), written into c2_out
+    pub fn encrypt_v_into(&self, t_hat: &[u8], m: &[u8; 32], r: &[u8; 32], c2_out: &mut [u8]) {
+        self.encrypt_v_with_e2_into(t_hat, m, r, r, c2_out)
+    }
+
+    /// `encrypt_v_into` with e_2 = SamplePolyCBD_eta2(PRF_eta2(e2_seed, 2k)) instead
+    /// of the noise of r : e2_seed = r gives c_2 of Algorithm 14
+    pub fn encrypt_v_with_e2_into(
+        &self,
+        t_hat: &[u8],
+        m: &[u8; 32],
+        r: &[u8; 32],
+        e2_seed: &[u8; 32],
+        c2_out: &mut [u8],
+    ) {
+        if t_hat.len() != 384 * self.k || c2_out.len() != 32 * self.d_v {
+            panic!("Unauthorized length for t_hat or for the output buffer")
+        }
+        let mut acc = PolynomialNTT::<P>::from(vec![Zq::<P>::zero(); P::N]);
+        for i in 0..self.k {
+            let t_i = decode_12::<P>(&t_hat[384 * i..384 * (i + 1)]);
+            let y_i = self.noise(self.eta_1, r, i).to_ntt();
+            acc += &(&t_i * &y_i);
+        }
+        let mut v = Polynomial::<P>::from_ntt(&acc);
+        v += &self.noise(self.eta_2, e2_seed, 2 * self.k);
+        for (idx, coeff) in v.coeffs.iter_mut().enumerate() {
+            let bit = (m[idx / 8] >> (idx % 8)) & 1;
+            *coeff += Zq::<P>::new(decompress(bit as i64, 1, P::Q));
+        }
+
+        encode_compressed_into(&v.coeffs, self.d_v, c2_out);
+    }
+
+    /// SamplePolyCBD_eta(PRF_eta(seed, nonce)), regenerated on demand
+    fn noise(&self, eta: usize, seed: &[u8; 32], nonce: usize) -> Polynomial<P> {
+        Polynomial::<P>::sample_poly_cbd(&H::prf(eta, seed, &[nonce as u8]), eta)
+    }
+}
+
+/// Entry A_hat[i, j] = SampleNTT(rho || j || i)
+fn matrix_entry<P: PolyParams, H: HashSuite>(rho: &[u8], i: usize, j: usize) -> PolynomialNTT<P> {
+    let mut input = [0u8; 34];
+    input[0..32].copy_from_slice(rho);
+    input[32] = j as u8;
+    input[33] = i as u8;
+    PolynomialNTT::<P>::sample_ntt_with::<H>(&input)
+}
+
+/// ByteEncode_d of the coefficients, 8 at a time to avoid a full integer array
+fn encode_into<P: PolyParams>(coeffs: &[Zq<P>], d: usize, out: &mut [u8]) {
+    for (group, chunk) in coeffs.chunks_exact(8).zip(out.chunks_exact_mut(d)) {
+        let values: [i64; 8] = std::array::from_fn(|i| group[i].value());
+        byte_encode_into(&values, d, chunk);
+    }
+}
+
+/// ByteEncode_d(Compress_d(coefficients)), 8 at a time
+fn encode_compressed_into<P: PolyParams>(coeffs: &[Zq<P>], d: usize, out: &mut [u8]) {
+    for (group, chunk) in coeffs.chunks_exact(8).zip(out.chunks_exact_mut(d)) {
+        let values: [i64; 8] = std::array::from_fn(|i| compress(group[i].value(), d, P::Q));
+        byte_encode_into(&values, d, chunk);
+    }
+}
+
+/// ByteDecode_12, 8 coefficients at a time
+fn decode_12<P: PolyParams>(bytes: &[u8]) -> PolynomialNTT<P> {
+    let mut coeffs = Vec::with_capacity(P::N);
+    let mut values = [0i64; 8];
+    for chunk in bytes.chunks_exact(12) {
+        byte_decode_into(chunk, 12, P::Q, &mut values);
+        coeffs.extend(values.iter().map(|&x| Zq::<P>::new(x)));
+    }
+    PolynomialNTT::<P>::from(coeffs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::KyberParams;
+    use crate::hash::h;
+
+    #[test]
+    fn basics() {
+        for (k, eta_1, eta_2, d_u, d_v) in [(2, 3, 2, 10, 4), (3, 2, 2, 10, 4), (4, 2, 2, 11, 5)] {
+            let pke_scheme = KPke::<KyberParams>::new(k, eta_1, eta_2, d_u, d_v);
+
+            let d = h(b"streaming key generation");
+            let (ek, dk) = pke_scheme.key_gen(&d);
+            let mut ek_stream = vec![0u8; pke_scheme.ek_len()];
+            let mut dk_stream = vec![0u8; pke_scheme.dk_len()];
+            pke_scheme.key_gen_streaming(&d, &mut ek_stream, &mut dk_stream);
+            assert_eq!(ek_stream, ek);
+            assert_eq!(dk_stream, dk);
+
+            let message = b"Ce message est tres confidentiel";
+            let r = h(b"streaming encryption");
+            let c = pke_scheme.encrypt(&ek, message, &r);
+            let mut c_stream = vec![0u8; pke_scheme.ciphertext_len()];
+            pke_scheme.encrypt_streaming(&ek, message, &r, &mut c_stream);
+            assert_eq!(c_stream, c);
+            assert_eq!(pke_scheme.decrypt(&dk, &c_stream), message);
+        }
+    }
+}
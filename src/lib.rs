@@ -1,5 +1,23 @@
+pub mod age;
+pub mod ake;
 pub mod constants;
+pub mod container;
 pub mod conversion;
+pub mod der;
+pub mod envelope;
+pub mod erasure;
+pub mod field;
 pub mod hash;
+pub mod hpke;
+pub mod hybrid;
+pub mod kdf;
+pub mod keccak;
+pub mod kem_dem;
 pub mod kyber;
+pub mod noise;
+pub mod pake;
+pub mod pbes2;
+pub mod pem;
+pub mod pkcs8;
 pub mod polynomial;
+pub mod pqxdh;
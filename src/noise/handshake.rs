@@ -0,0 +1,563 @@
+use crate::constants::KyberParams;
+use crate::hybrid::ecdh::{Curve, EcdhKeyPair};
+use crate::kyber::kem_scheme::{MlKem, ParameterSet};
+use crate::kyber::keys::EncapsulationKey;
+use crate::noise::pattern::{HandshakePattern, Token};
+use crate::noise::symmetric::{
+    CipherState, MAX_MESSAGE_LEN, NoiseCipher, NoiseError, NoiseHash, SymmetricState, TAG_LEN,
+};
+
+const DH_LEN: usize = 32;
+
+/// Splits the first n bytes off a message being read
+fn take<'a>(rest: &mut &'a [u8], n: usize) -> Result<&'a [u8], NoiseError> {
+    if rest.len() < n {
+        return Err(NoiseError::Truncated);
+    }
+    let (taken, remaining) = rest.split_at(n);
+    *rest = remaining;
+    Ok(taken)
+}
+
+/// Handshake pattern and primitives of a Noise protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NoiseParams {
+    pub pattern: HandshakePattern,
+    /// ML-KEM parameter set of the PQNoise and HFS patterns, unused by the others
+    pub parameter_set: ParameterSet,
+    pub cipher: NoiseCipher,
+    pub hash: NoiseHash,
+}
+
+impl NoiseParams {
+    pub fn new(
+        pattern: HandshakePattern,
+        parameter_set: ParameterSet,
+        cipher: NoiseCipher,
+        hash: NoiseHash,
+    ) -> Self {
+        NoiseParams {
+            pattern,
+            parameter_set,
+            cipher,
+            hash,
+        }
+    }
+
+    /// Noise_<pattern>_<DH or KEM>_<cipher>_<hash>, e.g.
+    /// Noise_pqXX_MLKEM768_ChaChaPoly_SHA256 or Noise_XXhfs_25519+MLKEM768_AESGCM_SHA512
+    pub fn protocol_name(&self) -> String {
+        let kem = self.parameter_set.name().replace('-', "");
+        let primitive = if self.pattern.is_pq() {
+            kem
+        } else if self.pattern.is_hybrid() {
+            format!("25519+{kem}")
+        } else {
+            "25519".to_string()
+        };
+        format!(
+            "Noise_{}_{}_{}_{}",
+            self.pattern.name(),
+            primitive,
+            self.cipher.name(),
+            self.hash.name()
+        )
+    }
+
+    fn ml_kem(&self) -> MlKem<KyberParams> {
+        MlKem::<KyberParams>::from_parameter_set(self.parameter_set)
+    }
+
+    /// Length of the public part of e and s
+    fn public_len(&self) -> usize {
+        if self.pattern.is_pq() {
+            self.parameter_set.ek_len()
+        } else {
+            DH_LEN
+        }
+    }
+
+    /// Static key pair of the kind used by the pattern : ML-KEM for PQNoise, X25519
+    /// otherwise
+    pub fn generate_keypair(&self) -> KeyPair {
+        if self.pattern.is_pq() {
+            self.generate_kem_keypair()
+        } else {
+            let key_pair = EcdhKeyPair::generate(Curve::X25519);
+            KeyPair {
+                public: key_pair.public_bytes().to_vec(),
+                secret: key_pair.secret_bytes(),
+            }
+        }
+    }
+
+    fn generate_kem_keypair(&self) -> KeyPair {
+        let (ek, dk) = self.ml_kem().key_gen();
+        KeyPair {
+            public: ek,
+            secret: dk,
+        }
+    }
+
+    fn dh(&self, key_pair: &KeyPair, public: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        EcdhKeyPair::from_secret_bytes(Curve::X25519, &key_pair.secret)
+            .and_then(|key_pair| key_pair.diffie_hellman(public))
+            .map_err(|_| NoiseError::InvalidPublicKey)
+    }
+
+    /// Returns (shared secret, ciphertext)
+    fn encaps(&self, ek: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        self.ml_kem().encaps(ek)
+    }
+
+    fn decaps(&self, key_pair: &KeyPair, c: &[u8]) -> Vec<u8> {
+        self.ml_kem().decaps(&key_pair.secret, c)
+    }
+
+    /// Checks a received ML-KEM encapsulation key
+    fn check_ek(&self, ek: &[u8]) -> Result<(), NoiseError> {
+        EncapsulationKey::from_bytes(self.parameter_set, ek)
+            .map(|_| ())
+            .map_err(|_| NoiseError::InvalidPublicKey)
+    }
+}
+
+/// Static or ephemeral key pair : X25519 keys, or an ML-KEM (ek, dk) pair
+#[derive(Clone)]
+pub struct KeyPair {
+    pub public: Vec<u8>,
+    pub secret: Vec<u8>,
+}
+
+/// HandshakeState (Section 5.3 Noise)
+pub struct HandshakeState {
+    params: NoiseParams,
+    initiator: bool,
+    symmetric: SymmetricState,
+    s: Option<KeyPair>,
+    e: Option<KeyPair>,
+    e1: Option<KeyPair>,
+    rs: Option<Vec<u8>>,
+    re: Option<Vec<u8>>,
+    re1: Option<Vec<u8>>,
+    message_index: usize,
+}
+
+impl HandshakeState {
+    /// Initialize(handshake_pattern, initiator, prologue, s, rs)
+    ///
+    /// The local static key pair is needed when the pattern sends or pre-shares it, and
+    /// the initiator needs the remote static key when the responder pre-shares it.
+    pub fn new(
+        params: NoiseParams,
+        initiator: bool,
+        prologue: &[u8],
+        s: Option<KeyPair>,
+        rs: Option<&[u8]>,
+    ) -> Result<Self, NoiseError> {
+        let pattern = params.pattern;
+        if pattern.needs_local_static(initiator) && s.is_none() {
+            return Err(NoiseError::MissingKey);
+        }
+        if let Some(s) = &s
+            && s.public.len() != params.public_len()
+        {
+            return Err(NoiseError::MissingKey);
+        }
+        let pre_shared_rs = initiator && !pattern.responder_pre_message().is_empty();
+        if pre_shared_rs && rs.is_none() {
+            return Err(NoiseError::MissingKey);
+        }
+        if let Some(rs) = rs {
+            if rs.len() != params.public_len() {
+                return Err(NoiseError::InvalidPublicKey);
+            }
+            if pattern.is_pq() {
+                params.check_ek(rs)?;
+            }
+        }
+
+        let mut symmetric =
+            SymmetricState::new(&params.protocol_name(), params.cipher, params.hash);
+        symmetric.mix_hash(prologue);
+        if !pattern.responder_pre_message().is_empty() {
+            let responder_static = if initiator {
+                rs.unwrap()
+            } else {
+                &s.as_ref().unwrap().public
+            };
+            symmetric.mix_hash(responder_static);
+        }
+
+        Ok(HandshakeState {
+            params,
+            initiator,
+            symmetric,
+            s,
+            e: None,
+            e1: None,
+            rs: rs.map(<[u8]>::to_vec),
+            re: None,
+            re1: None,
+            message_index: 0,
+        })
+    }
+
+    pub fn is_initiator(&self) -> bool {
+        self.initiator
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.message_index == self.params.pattern.messages().len()
+    }
+
+    /// Whether the next message is written by this party
+    pub fn is_my_turn(&self) -> bool {
+        !self.is_finished() && self.message_index.is_multiple_of(2) == self.initiator
+    }
+
+    /// Static public key of the remote party, once known
+    pub fn remote_static(&self) -> Option<&[u8]> {
+        self.rs.as_deref()
+    }
+
+    /// Handshake hash h, for channel binding once the handshake is finished
+    pub fn handshake_hash(&self) -> &[u8] {
+        self.symmetric.handshake_hash()
+    }
+
+    /// Length of a ciphertext of n bytes given to EncryptAndHash
+    fn encrypted_len(&self, n: usize) -> usize {
+        if self.symmetric.has_key() {
+            n + TAG_LEN
+        } else {
+            n
+        }
+    }
+
+    /// Length of the next message with a payload of n bytes, from the tokens and
+    /// whether each of them finds a key
+    fn message_len(&self, n: usize) -> usize {
+        let params = self.params;
+        let ek_len = params.parameter_set.ek_len();
+        let c_len = params.parameter_set.ciphertext_len();
+        let mut has_key = self.symmetric.has_key();
+        let tag = |has_key: bool| if has_key { TAG_LEN } else { 0 };
+        let mut len = 0;
+        for &token in params.pattern.messages()[self.message_index] {
+            len += match token {
+                Token::E => params.public_len(),
+                Token::S => params.public_len() + tag(has_key),
+                Token::Ee | Token::Es | Token::Se | Token::Ss => 0,
+                Token::Ekem => c_len,
+                Token::Skem | Token::Ekem1 => c_len + tag(has_key),
+                Token::E1 => ek_len + tag(has_key),
+            };
+            has_key |= !matches!(token, Token::E | Token::S | Token::E1);
+        }
+        len + n + tag(has_key)
+    }
+
+    fn local(key_pair: &Option<KeyPair>) -> &KeyPair {
+        key_pair.as_ref().expect("key pair set by the pattern")
+    }
+
+    fn remote(key: &Option<Vec<u8>>) -> &[u8] {
+        key.as_deref().expect("remote key set by the pattern")
+    }
+
+    /// ee, es, se, ss : MixKey(DH(..)) with the keys given by the token and the role
+    fn mix_dh(&mut self, token: Token) -> Result<(), NoiseError> {
+        let (local, remote) = match (token, self.initiator) {
+            (Token::Ee, _) => (&self.e, &self.re),
+            (Token::Es, true) | (Token::Se, false) => (&self.e, &self.rs),
+            (Token::Es, false) | (Token::Se, true) => (&self.s, &self.re),
+            (Token::Ss, _) => (&self.s, &self.rs),
+            _ => unreachable!("not a DH token"),
+        };
+        let shared = self.params.dh(Self::local(local), Self::remote(remote))?;
+        self.symmetric.mix_key(&shared);
+        Ok(())
+    }
+
+    /// WriteMessage(payload, message_buffer). The length is checked before the
+    /// state changes, so a `MessageTooLong` payload can be retried shorter; after
+    /// another error the handshake must be abandoned.
+    pub fn write_message(&mut self, payload: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if !self.is_my_turn() {
+            return Err(NoiseError::InvalidState);
+        }
+        let expected_len = self.message_len(payload.len());
+        if expected_len > MAX_MESSAGE_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        let params = self.params;
+        let mut message = Vec::new();
+        for &token in params.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => {
+                    let e = params.generate_keypair();
+                    message.extend_from_slice(&e.public);
+                    self.symmetric.mix_hash(&e.public);
+                    self.e = Some(e);
+                }
+                Token::S => {
+                    let public = &Self::local(&self.s).public;
+                    message.extend(self.symmetric.encrypt_and_hash(public)?);
+                }
+                Token::Ee | Token::Es | Token::Se | Token::Ss => self.mix_dh(token)?,
+                Token::Ekem => {
+                    let (shared, c) = params.encaps(Self::remote(&self.re));
+                    message.extend_from_slice(&c);
+                    self.symmetric.mix_hash(&c);
+                    self.symmetric.mix_key(&shared);
+                }
+                Token::Skem => {
+                    let (shared, c) = params.encaps(Self::remote(&self.rs));
+                    message.extend(self.symmetric.encrypt_and_hash(&c)?);
+                    self.symmetric.mix_key(&shared);
+                }
+                Token::E1 => {
+                    let e1 = params.generate_kem_keypair();
+                    message.extend(self.symmetric.encrypt_and_hash(&e1.public)?);
+                    self.e1 = Some(e1);
+                }
+                Token::Ekem1 => {
+                    let (shared, c) = params.encaps(Self::remote(&self.re1));
+                    message.extend(self.symmetric.encrypt_and_hash(&c)?);
+                    self.symmetric.mix_key(&shared);
+                }
+            }
+        }
+        message.extend(self.symmetric.encrypt_and_hash(payload)?);
+        debug_assert_eq!(message.len(), expected_len);
+        self.message_index += 1;
+        Ok(message)
+    }
+
+    /// ReadMessage(message, payload_buffer). After an error the handshake must be
+    /// abandoned.
+    pub fn read_message(&mut self, message: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if self.is_finished() || self.is_my_turn() {
+            return Err(NoiseError::InvalidState);
+        }
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        let params = self.params;
+        let ek_len = params.parameter_set.ek_len();
+        let c_len = params.parameter_set.ciphertext_len();
+        let mut rest = message;
+
+        for &token in params.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => {
+                    let re = take(&mut rest, params.public_len())?;
+                    if params.pattern.is_pq() {
+                        params.check_ek(re)?;
+                    }
+                    self.symmetric.mix_hash(re);
+                    self.re = Some(re.to_vec());
+                }
+                Token::S => {
+                    let len = self.encrypted_len(params.public_len());
+                    let rs = self.symmetric.decrypt_and_hash(take(&mut rest, len)?)?;
+                    if params.pattern.is_pq() {
+                        params.check_ek(&rs)?;
+                    }
+                    self.rs = Some(rs);
+                }
+                Token::Ee | Token::Es | Token::Se | Token::Ss => self.mix_dh(token)?,
+                Token::Ekem => {
+                    let c = take(&mut rest, c_len)?;
+                    self.symmetric.mix_hash(c);
+                    let shared = params.decaps(Self::local(&self.e), c);
+                    self.symmetric.mix_key(&shared);
+                }
+                Token::Skem => {
+                    let len = self.encrypted_len(c_len);
+                    let c = self.symmetric.decrypt_and_hash(take(&mut rest, len)?)?;
+                    let shared = params.decaps(Self::local(&self.s), &c);
+                    self.symmetric.mix_key(&shared);
+                }
+                Token::E1 => {
+                    let len = self.encrypted_len(ek_len);
+                    let re1 = self.symmetric.decrypt_and_hash(take(&mut rest, len)?)?;
+                    params.check_ek(&re1)?;
+                    self.re1 = Some(re1);
+                }
+                Token::Ekem1 => {
+                    let len = self.encrypted_len(c_len);
+                    let c = self.symmetric.decrypt_and_hash(take(&mut rest, len)?)?;
+                    let shared = params.decaps(Self::local(&self.e1), &c);
+                    self.symmetric.mix_key(&shared);
+                }
+            }
+        }
+        if rest.len() < self.encrypted_len(0) {
+            return Err(NoiseError::Truncated);
+        }
+        let payload = self.symmetric.decrypt_and_hash(rest)?;
+        self.message_index += 1;
+        Ok(payload)
+    }
+
+    /// Split() once the last handshake message has been written or read
+    pub fn into_transport(self) -> Result<TransportState, NoiseError> {
+        if !self.is_finished() {
+            return Err(NoiseError::InvalidState);
+        }
+        let (initiator_to_responder, responder_to_initiator) = self.symmetric.split();
+        let (send, receive) = if self.initiator {
+            (initiator_to_responder, responder_to_initiator)
+        } else {
+            (responder_to_initiator, initiator_to_responder)
+        };
+        Ok(TransportState {
+            send,
+            receive,
+            handshake_hash: self.symmetric.handshake_hash().to_vec(),
+            remote_static: self.rs,
+        })
+    }
+}
+
+/// Transport phase : one cipher state per direction
+pub struct TransportState {
+    send: CipherState,
+    receive: CipherState,
+    handshake_hash: Vec<u8>,
+    remote_static: Option<Vec<u8>>,
+}
+
+impl TransportState {
+    pub fn write_message(&mut self, payload: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if payload.len() + TAG_LEN > MAX_MESSAGE_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        self.send.encrypt_with_ad(&[], payload)
+    }
+
+    pub fn read_message(&mut self, message: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        self.receive.decrypt_with_ad(&[], message)
+    }
+
+    pub fn rekey_outgoing(&mut self) {
+        self.send.rekey();
+    }
+
+    pub fn rekey_incoming(&mut self) {
+        self.receive.rekey();
+    }
+
+    pub fn handshake_hash(&self) -> &[u8] {
+        &self.handshake_hash
+    }
+
+    pub fn remote_static(&self) -> Option<&[u8]> {
+        self.remote_static.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for pattern in HandshakePattern::ALL {
+            let params = NoiseParams::new(
+                pattern,
+                ParameterSet::MlKem768,
+                NoiseCipher::ChaChaPoly,
+                NoiseHash::Sha256,
+            );
+            let initiator_static = params.generate_keypair();
+            let responder_static = params.generate_keypair();
+            let pre_shared = !pattern.responder_pre_message().is_empty();
+
+            let mut initiator = HandshakeState::new(
+                params,
+                true,
+                b"prologue",
+                pattern
+                    .needs_local_static(true)
+                    .then(|| initiator_static.clone()),
+                pre_shared.then_some(responder_static.public.as_slice()),
+            )
+            .unwrap();
+            let mut responder = HandshakeState::new(
+                params,
+                false,
+                b"prologue",
+                pattern
+                    .needs_local_static(false)
+                    .then(|| responder_static.clone()),
+                None,
+            )
+            .unwrap();
+            assert_eq!(initiator.read_message(&[]), Err(NoiseError::InvalidState));
+
+            let mut turn = 0;
+            while !initiator.is_finished() {
+                let (sender, receiver) = if turn % 2 == 0 {
+                    (&mut initiator, &mut responder)
+                } else {
+                    (&mut responder, &mut initiator)
+                };
+                // An oversize payload leaves the state as it was
+                let longest = MAX_MESSAGE_LEN - sender.message_len(0);
+                assert_eq!(
+                    sender.write_message(&vec![0; longest + 1]),
+                    Err(NoiseError::MessageTooLong)
+                );
+                let payload = format!("message {turn}");
+                let message = sender.write_message(payload.as_bytes()).unwrap();
+                assert_eq!(receiver.read_message(&message).unwrap(), payload.as_bytes());
+                turn += 1;
+            }
+            assert!(responder.is_finished());
+            assert_eq!(initiator.handshake_hash(), responder.handshake_hash());
+            if pattern.needs_local_static(true) {
+                assert_eq!(
+                    responder.remote_static(),
+                    Some(initiator_static.public.as_slice())
+                );
+            }
+            assert_eq!(
+                initiator.remote_static(),
+                pattern
+                    .needs_local_static(false)
+                    .then_some(responder_static.public.as_slice())
+            );
+            assert_eq!(initiator.write_message(b""), Err(NoiseError::InvalidState));
+
+            let mut initiator = initiator.into_transport().unwrap();
+            let mut responder = responder.into_transport().unwrap();
+            let message = initiator.write_message(b"ping").unwrap();
+            assert_eq!(responder.read_message(&message).unwrap(), b"ping");
+            let message = responder.write_message(b"pong").unwrap();
+            assert_eq!(initiator.read_message(&message).unwrap(), b"pong");
+            assert_eq!(responder.read_message(&message), Err(NoiseError::Decrypt));
+        }
+
+        // The protocol name reflects the primitives
+        let params = NoiseParams::new(
+            HandshakePattern::XxHfs,
+            ParameterSet::MlKem1024,
+            NoiseCipher::AesGcm,
+            NoiseHash::Sha512,
+        );
+        assert_eq!(
+            params.protocol_name(),
+            "Noise_XXhfs_25519+MLKEM1024_AESGCM_SHA512"
+        );
+        assert_eq!(
+            HandshakeState::new(params, true, b"", None, None).err(),
+            Some(NoiseError::MissingKey)
+        );
+    }
+}
@@ -0,0 +1,3 @@
+pub mod handshake;
+pub mod pattern;
+pub mod symmetric;
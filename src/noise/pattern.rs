@@ -0,0 +1,187 @@
+/// Tokens of a handshake pattern
+///
+/// e, s, ee, es, se and ss are those of the Noise specification (Section 7). With the
+/// PQNoise patterns, e and s are ML-KEM key pairs instead of X25519 key pairs, and
+/// the DH tokens are replaced by encapsulations :
+/// - ekem : encapsulation to the remote ephemeral key, the ciphertext is sent in the clear
+/// - skem : encapsulation to the remote static key, the ciphertext is encrypted
+///
+/// The hybrid patterns keep the X25519 e and s, and add the tokens of the Noise HFS
+/// extension :
+/// - e1 : ephemeral ML-KEM key pair, the encapsulation key is encrypted
+/// - ekem1 : encapsulation to the remote e1, the ciphertext is encrypted
+///
+/// Every encapsulation ends with MixKey on the ML-KEM shared secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Token {
+    E,
+    S,
+    Ee,
+    Es,
+    Se,
+    Ss,
+    Ekem,
+    Skem,
+    E1,
+    Ekem1,
+}
+
+use Token::*;
+
+/// Handshake patterns : interactive patterns of the Noise specification, their
+/// PQNoise counterparts (Angel et al., CCS 2022) and their HFS counterparts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HandshakePattern {
+    Nn,
+    Nk,
+    Xx,
+    Ik,
+    PqNn,
+    PqNk,
+    PqXx,
+    PqIk,
+    NnHfs,
+    NkHfs,
+    XxHfs,
+    IkHfs,
+}
+
+impl HandshakePattern {
+    pub const ALL: [HandshakePattern; 12] = [
+        HandshakePattern::Nn,
+        HandshakePattern::Nk,
+        HandshakePattern::Xx,
+        HandshakePattern::Ik,
+        HandshakePattern::PqNn,
+        HandshakePattern::PqNk,
+        HandshakePattern::PqXx,
+        HandshakePattern::PqIk,
+        HandshakePattern::NnHfs,
+        HandshakePattern::NkHfs,
+        HandshakePattern::XxHfs,
+        HandshakePattern::IkHfs,
+    ];
+
+    /// Name used in the protocol name
+    pub fn name(&self) -> &'static str {
+        match self {
+            HandshakePattern::Nn => "NN",
+            HandshakePattern::Nk => "NK",
+            HandshakePattern::Xx => "XX",
+            HandshakePattern::Ik => "IK",
+            HandshakePattern::PqNn => "pqNN",
+            HandshakePattern::PqNk => "pqNK",
+            HandshakePattern::PqXx => "pqXX",
+            HandshakePattern::PqIk => "pqIK",
+            HandshakePattern::NnHfs => "NNhfs",
+            HandshakePattern::NkHfs => "NKhfs",
+            HandshakePattern::XxHfs => "XXhfs",
+            HandshakePattern::IkHfs => "IKhfs",
+        }
+    }
+
+    /// Whether e and s are ML-KEM key pairs (PQNoise) rather than X25519 key pairs
+    pub fn is_pq(&self) -> bool {
+        matches!(
+            self,
+            HandshakePattern::PqNn
+                | HandshakePattern::PqNk
+                | HandshakePattern::PqXx
+                | HandshakePattern::PqIk
+        )
+    }
+
+    /// Whether the pattern uses both X25519 and ML-KEM
+    pub fn is_hybrid(&self) -> bool {
+        matches!(
+            self,
+            HandshakePattern::NnHfs
+                | HandshakePattern::NkHfs
+                | HandshakePattern::XxHfs
+                | HandshakePattern::IkHfs
+        )
+    }
+
+    /// Tokens of the responder pre-message (the initiator pre-message is always empty)
+    pub fn responder_pre_message(&self) -> &'static [Token] {
+        match self {
+            HandshakePattern::Nk
+            | HandshakePattern::Ik
+            | HandshakePattern::PqNk
+            | HandshakePattern::PqIk
+            | HandshakePattern::NkHfs
+            | HandshakePattern::IkHfs => &[S],
+            _ => &[],
+        }
+    }
+
+    /// Message patterns, alternating from the initiator
+    pub fn messages(&self) -> &'static [&'static [Token]] {
+        match self {
+            HandshakePattern::Nn => &[&[E], &[E, Ee]],
+            HandshakePattern::Nk => &[&[E, Es], &[E, Ee]],
+            HandshakePattern::Xx => &[&[E], &[E, Ee, S, Es], &[S, Se]],
+            HandshakePattern::Ik => &[&[E, Es, S, Ss], &[E, Ee, Se]],
+            HandshakePattern::PqNn => &[&[E], &[Ekem]],
+            HandshakePattern::PqNk => &[&[Skem, E], &[Ekem]],
+            HandshakePattern::PqXx => &[&[E], &[Ekem, S], &[Skem, S], &[Skem]],
+            HandshakePattern::PqIk => &[&[Skem, E, S], &[Ekem, Skem]],
+            HandshakePattern::NnHfs => &[&[E, E1], &[E, Ee, Ekem1]],
+            HandshakePattern::NkHfs => &[&[E, Es, E1], &[E, Ee, Ekem1]],
+            HandshakePattern::XxHfs => &[&[E, E1], &[E, Ee, Ekem1, S, Es], &[S, Se]],
+            HandshakePattern::IkHfs => &[&[E, Es, E1, S, Ss], &[E, Ee, Ekem1, Se]],
+        }
+    }
+
+    /// Whether the initiator (or the responder) needs a static key pair
+    pub fn needs_local_static(&self, initiator: bool) -> bool {
+        let sender = if initiator { 0 } else { 1 };
+        (!initiator && self.responder_pre_message().contains(&S))
+            || self
+                .messages()
+                .iter()
+                .skip(sender)
+                .step_by(2)
+                .any(|tokens| tokens.contains(&S))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for pattern in HandshakePattern::ALL {
+            assert!(!(pattern.is_pq() && pattern.is_hybrid()));
+            let messages = pattern.messages();
+            let all_tokens = messages.iter().flat_map(|tokens| tokens.iter());
+            if pattern.is_pq() {
+                assert!(
+                    all_tokens
+                        .clone()
+                        .all(|token| [E, S, Ekem, Skem].contains(token))
+                );
+            } else {
+                assert!(
+                    all_tokens
+                        .clone()
+                        .all(|token| ![Ekem, Skem].contains(token))
+                );
+            }
+            // An ML-KEM ephemeral is always answered
+            assert_eq!(
+                all_tokens.clone().filter(|token| **token == E1).count(),
+                all_tokens.clone().filter(|token| **token == Ekem1).count()
+            );
+            // The responder always knows its static key when it has a pre-message
+            if !pattern.responder_pre_message().is_empty() {
+                assert!(pattern.needs_local_static(false));
+            }
+        }
+        assert!(HandshakePattern::Xx.needs_local_static(true));
+        assert!(!HandshakePattern::Nk.needs_local_static(true));
+        assert!(!HandshakePattern::PqNn.needs_local_static(false));
+        assert_eq!(HandshakePattern::PqXx.messages().len(), 4);
+    }
+}
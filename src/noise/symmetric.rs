@@ -0,0 +1,349 @@
+use core::fmt;
+
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256, Sha512};
+
+/// Maximum size of a Noise message
+pub const MAX_MESSAGE_LEN: usize = 65535;
+/// Size of the AEAD tag
+pub const TAG_LEN: usize = 16;
+
+/// Errors raised by the Noise handshake and transport
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseError {
+    /// write_message or read_message called out of turn, or after the handshake
+    InvalidState,
+    /// The pattern needs a local static key or a remote static key that was not given
+    MissingKey,
+    /// The message is shorter than the tokens of the pattern require
+    Truncated,
+    /// The message would exceed 65535 bytes
+    MessageTooLong,
+    /// The remote public key or KEM ciphertext is invalid
+    InvalidPublicKey,
+    /// AEAD decryption failed
+    Decrypt,
+    /// The nonce reached 2^64 - 1
+    NonceExhausted,
+}
+
+impl fmt::Display for NoiseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoiseError::InvalidState => write!(f, "operation not allowed in this Noise state"),
+            NoiseError::MissingKey => write!(f, "missing Noise static key"),
+            NoiseError::Truncated => write!(f, "truncated Noise message"),
+            NoiseError::MessageTooLong => write!(f, "Noise message too long"),
+            NoiseError::InvalidPublicKey => write!(f, "invalid Noise remote public key"),
+            NoiseError::Decrypt => write!(f, "Noise decryption failed"),
+            NoiseError::NonceExhausted => write!(f, "Noise nonce exhausted"),
+        }
+    }
+}
+
+impl std::error::Error for NoiseError {}
+
+/// Cipher functions (Section 12.3 Noise)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NoiseCipher {
+    ChaChaPoly,
+    AesGcm,
+}
+
+impl NoiseCipher {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoiseCipher::ChaChaPoly => "ChaChaPoly",
+            NoiseCipher::AesGcm => "AESGCM",
+        }
+    }
+
+    /// 32 bits of zeros followed by the 64-bit nonce, little-endian for ChaChaPoly
+    /// and big-endian for AESGCM
+    fn nonce(&self, n: u64) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        match self {
+            NoiseCipher::ChaChaPoly => nonce[4..].copy_from_slice(&n.to_le_bytes()),
+            NoiseCipher::AesGcm => nonce[4..].copy_from_slice(&n.to_be_bytes()),
+        }
+        nonce
+    }
+
+    fn encrypt(&self, k: &[u8; 32], n: u64, ad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let nonce = self.nonce(n);
+        let payload = Payload {
+            msg: plaintext,
+            aad: ad,
+        };
+        let ciphertext = match self {
+            NoiseCipher::ChaChaPoly => {
+                ChaCha20Poly1305::new(k.into()).encrypt(&nonce.into(), payload)
+            }
+            NoiseCipher::AesGcm => Aes256Gcm::new(k.into()).encrypt(&nonce.into(), payload),
+        };
+        ciphertext.expect("Noise messages are shorter than the AEAD limit")
+    }
+
+    fn decrypt(
+        &self,
+        k: &[u8; 32],
+        n: u64,
+        ad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, NoiseError> {
+        let nonce = self.nonce(n);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: ad,
+        };
+        let plaintext = match self {
+            NoiseCipher::ChaChaPoly => {
+                ChaCha20Poly1305::new(k.into()).decrypt(&nonce.into(), payload)
+            }
+            NoiseCipher::AesGcm => Aes256Gcm::new(k.into()).decrypt(&nonce.into(), payload),
+        };
+        plaintext.map_err(|_| NoiseError::Decrypt)
+    }
+}
+
+/// Hash functions (Section 12.4 Noise)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NoiseHash {
+    Sha256,
+    Sha512,
+}
+
+impl NoiseHash {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoiseHash::Sha256 => "SHA256",
+            NoiseHash::Sha512 => "SHA512",
+        }
+    }
+
+    /// HASHLEN
+    pub fn hash_len(&self) -> usize {
+        match self {
+            NoiseHash::Sha256 => 32,
+            NoiseHash::Sha512 => 64,
+        }
+    }
+
+    fn hash(&self, parts: &[&[u8]]) -> Vec<u8> {
+        match self {
+            NoiseHash::Sha256 => parts
+                .iter()
+                .fold(Sha256::new(), |hasher, part| hasher.chain_update(part))
+                .finalize()
+                .to_vec(),
+            NoiseHash::Sha512 => parts
+                .iter()
+                .fold(Sha512::new(), |hasher, part| hasher.chain_update(part))
+                .finalize()
+                .to_vec(),
+        }
+    }
+
+    /// HKDF(chaining_key, input_key_material, num_outputs), which is HKDF-Expand
+    /// (RFC 5869) with an empty info
+    fn hkdf(&self, chaining_key: &[u8], ikm: &[u8], num_outputs: usize) -> Vec<Vec<u8>> {
+        let mut okm = vec![0u8; num_outputs * self.hash_len()];
+        match self {
+            NoiseHash::Sha256 => Hkdf::<Sha256>::new(Some(chaining_key), ikm).expand(&[], &mut okm),
+            NoiseHash::Sha512 => Hkdf::<Sha512>::new(Some(chaining_key), ikm).expand(&[], &mut okm),
+        }
+        .expect("at most 3 outputs");
+        okm.chunks(self.hash_len()).map(<[u8]>::to_vec).collect()
+    }
+}
+
+/// Truncates a HASHLEN output to a 32-byte cipher key
+fn cipher_key(output: &[u8]) -> [u8; 32] {
+    output[..32].try_into().unwrap()
+}
+
+/// CipherState (Section 5.1 Noise)
+#[derive(Clone)]
+pub struct CipherState {
+    cipher: NoiseCipher,
+    k: Option<[u8; 32]>,
+    n: u64,
+}
+
+impl CipherState {
+    fn new(cipher: NoiseCipher, k: Option<[u8; 32]>) -> Self {
+        CipherState { cipher, k, n: 0 }
+    }
+
+    pub fn has_key(&self) -> bool {
+        self.k.is_some()
+    }
+
+    /// Encrypts with the next nonce, or returns the plaintext if there is no key yet
+    pub fn encrypt_with_ad(&mut self, ad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let Some(k) = &self.k else {
+            return Ok(plaintext.to_vec());
+        };
+        if self.n == u64::MAX {
+            return Err(NoiseError::NonceExhausted);
+        }
+        let ciphertext = self.cipher.encrypt(k, self.n, ad, plaintext);
+        self.n += 1;
+        Ok(ciphertext)
+    }
+
+    /// Decrypts with the next nonce, or returns the ciphertext if there is no key yet.
+    /// The nonce only moves on success.
+    pub fn decrypt_with_ad(&mut self, ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let Some(k) = &self.k else {
+            return Ok(ciphertext.to_vec());
+        };
+        if self.n == u64::MAX {
+            return Err(NoiseError::NonceExhausted);
+        }
+        let plaintext = self.cipher.decrypt(k, self.n, ad, ciphertext)?;
+        self.n += 1;
+        Ok(plaintext)
+    }
+
+    /// Rekey() : k = ENCRYPT(k, 2^64 - 1, zerolen, zeros)
+    pub fn rekey(&mut self) {
+        if let Some(k) = &self.k {
+            let new_key = self.cipher.encrypt(k, u64::MAX, &[], &[0u8; 32]);
+            self.k = Some(cipher_key(&new_key));
+        }
+    }
+}
+
+/// SymmetricState (Section 5.2 Noise)
+pub(crate) struct SymmetricState {
+    hash: NoiseHash,
+    cipher_state: CipherState,
+    ck: Vec<u8>,
+    h: Vec<u8>,
+}
+
+impl SymmetricState {
+    /// InitializeSymmetric(protocol_name)
+    pub(crate) fn new(protocol_name: &str, cipher: NoiseCipher, hash: NoiseHash) -> Self {
+        let name = protocol_name.as_bytes();
+        let h = if name.len() <= hash.hash_len() {
+            let mut h = name.to_vec();
+            h.resize(hash.hash_len(), 0);
+            h
+        } else {
+            hash.hash(&[name])
+        };
+        SymmetricState {
+            hash,
+            cipher_state: CipherState::new(cipher, None),
+            ck: h.clone(),
+            h,
+        }
+    }
+
+    pub(crate) fn handshake_hash(&self) -> &[u8] {
+        &self.h
+    }
+
+    pub(crate) fn has_key(&self) -> bool {
+        self.cipher_state.has_key()
+    }
+
+    pub(crate) fn mix_key(&mut self, ikm: &[u8]) {
+        let outputs = self.hash.hkdf(&self.ck, ikm, 2);
+        self.cipher_state =
+            CipherState::new(self.cipher_state.cipher, Some(cipher_key(&outputs[1])));
+        self.ck = outputs[0].clone();
+    }
+
+    pub(crate) fn mix_hash(&mut self, data: &[u8]) {
+        self.h = self.hash.hash(&[&self.h, data]);
+    }
+
+    pub(crate) fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let ciphertext = self.cipher_state.encrypt_with_ad(&self.h, plaintext)?;
+        self.mix_hash(&ciphertext);
+        Ok(ciphertext)
+    }
+
+    pub(crate) fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let plaintext = self.cipher_state.decrypt_with_ad(&self.h, ciphertext)?;
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    /// Split() : the cipher states of the initiator-to-responder and
+    /// responder-to-initiator directions
+    pub(crate) fn split(&self) -> (CipherState, CipherState) {
+        let outputs = self.hash.hkdf(&self.ck, &[], 2);
+        let cipher = self.cipher_state.cipher;
+        (
+            CipherState::new(cipher, Some(cipher_key(&outputs[0]))),
+            CipherState::new(cipher, Some(cipher_key(&outputs[1]))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        // Short protocol names are padded, long ones hashed
+        let short = SymmetricState::new(
+            "Noise_NN_25519_AESGCM_SHA512",
+            NoiseCipher::AesGcm,
+            NoiseHash::Sha512,
+        );
+        assert_eq!(
+            &short.handshake_hash()[..28],
+            b"Noise_NN_25519_AESGCM_SHA512"
+        );
+        assert_eq!(short.handshake_hash()[28..], [0u8; 36]);
+        let name = "Noise_XXhfs_25519+MLKEM768_ChaChaPoly_SHA256";
+        let long = SymmetricState::new(name, NoiseCipher::ChaChaPoly, NoiseHash::Sha256);
+        assert_eq!(long.handshake_hash(), Sha256::digest(name).as_slice());
+
+        for cipher in [NoiseCipher::ChaChaPoly, NoiseCipher::AesGcm] {
+            let mut sender = SymmetricState::new("Noise", cipher, NoiseHash::Sha256);
+            let mut receiver = SymmetricState::new("Noise", cipher, NoiseHash::Sha256);
+            // No key : plaintext, still hashed
+            let clear = sender.encrypt_and_hash(b"hello").unwrap();
+            assert_eq!(clear, b"hello");
+            receiver.decrypt_and_hash(&clear).unwrap();
+
+            sender.mix_key(b"shared");
+            receiver.mix_key(b"shared");
+            let ciphertext = sender.encrypt_and_hash(b"hello").unwrap();
+            assert_eq!(ciphertext.len(), 5 + TAG_LEN);
+            let mut tampered = ciphertext.clone();
+            tampered[0] ^= 1;
+            assert_eq!(
+                receiver.decrypt_and_hash(&tampered),
+                Err(NoiseError::Decrypt)
+            );
+            assert_eq!(receiver.decrypt_and_hash(&ciphertext).unwrap(), b"hello");
+            assert_eq!(sender.handshake_hash(), receiver.handshake_hash());
+
+            let (mut c1, _) = sender.split();
+            let (mut d1, _) = receiver.split();
+            c1.rekey();
+            d1.rekey();
+            let ciphertext = c1.encrypt_with_ad(b"", b"after rekey").unwrap();
+            assert_eq!(
+                d1.decrypt_with_ad(b"", &ciphertext).unwrap(),
+                b"after rekey"
+            );
+            c1.n = u64::MAX;
+            assert_eq!(
+                c1.encrypt_with_ad(b"", b""),
+                Err(NoiseError::NonceExhausted)
+            );
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha3::Shake256;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+
+use crate::constants::{KyberParams, PolyParams};
+use crate::conversion::{byte_decode, byte_encode, compress};
+use crate::hash::{encode_string, tuple_hash256};
+use crate::kyber::kem_scheme::ParameterSet;
+use crate::kyber::keys::{EncapsulationKey, KeyError, check_length, check_modulus};
+
+/// Number of Feistel rounds of both ciphers
+pub const ROUNDS: u8 = 8;
+
+const Q: i64 = KyberParams::Q;
+
+/// Round function of the ciphers : SHAKE256(encode_string(label) || key || round ||
+/// input)
+fn round_xof(label: &[u8], key: &[u8; 32], round: u8, input: &[u8]) -> impl XofReader {
+    let mut xof = Shake256::default();
+    xof.update(&encode_string(label));
+    xof.update(key);
+    xof.update(&[round]);
+    xof.update(input);
+    xof.finalize_xof()
+}
+
+/// Uniform vector of Z_q^n from the XOF, by rejection sampling of 12-bit values as
+/// in SampleNTT
+fn sample_mod_q(mut xof: impl XofReader, n: usize) -> Vec<i64> {
+    let mut out = Vec::with_capacity(n);
+    let mut bytes = [0u8; 3];
+    while out.len() < n {
+        xof.read(&mut bytes);
+        let d_1 = bytes[0] as i64 + 256 * (bytes[1] as i64 % 16);
+        let d_2 = bytes[1] as i64 / 16 + 16 * bytes[2] as i64;
+        for d in [d_1, d_2] {
+            if d < Q && out.len() < n {
+                out.push(d);
+            }
+        }
+    }
+    out
+}
+
+/// Preimages of y by Compress_d : the integers x with (2y - 1) q < 2^(d+1) x <
+/// (2y + 1) q, taken modulo q. Returns (start, count), start > -q.
+///
+/// y is a coefficient of the ciphertext, so this is computed without branches or
+/// table lookups on it.
+fn preimages(y: i64, d: usize) -> (i64, i64) {
+    let ceil = |a: i64| (a + (1 << (d + 1)) - 1) >> (d + 1);
+    let start = ceil((2 * y - 1) * Q);
+    (start, ceil((2 * y + 1) * Q) - start)
+}
+
+/// Uniform x in Z_q with Compress_d(x) = y, in constant time : the offset in the
+/// preimages is the high half of a random 64-bit value times their count, which
+/// is biased by less than 2^-56
+fn lift(y: i64, d: usize) -> i64 {
+    let (start, count) = preimages(y, d);
+    let offset = ((OsRng.next_u64() as u128 * count as u128) >> 64) as i64;
+    let x = start + offset;
+    x + (Q & (x >> 63))
+}
+
+fn xor_into(out: &mut [u8], mut xof: impl XofReader) {
+    let mut mask = vec![0u8; out.len()];
+    xof.read(&mut mask);
+    for (byte, mask) in out.iter_mut().zip(mask) {
+        *byte ^= mask;
+    }
+}
+
+/// Password-keyed permutations instantiating the ideal ciphers of CAKE and OCAKE.
+///
+/// Encapsulation keys are permuted within the set of valid keys
+/// Z_q^(256*k) x B^32, so that decrypting with a wrong password still gives a
+/// valid key and rejected keys do not rule out passwords. The cipher is an 8-round
+/// Feistel network alternating t_hat <- t_hat + F_i(rho) mod q and
+/// rho <- rho XOR G_i(t_hat).
+///
+/// Ciphertexts are not uniform byte strings : their coefficients are Compress_d of
+/// values mod q, and the values of Z_2^d do not all have the same number of
+/// preimages (3 or 4 for d = 10). So each coefficient is first lifted to a uniform
+/// preimage in Z_q, and the vector of Z_q^(256*(k+1)) is permuted by an 8-round
+/// Feistel network on its two halves, L <- L + F_i(R) and R <- R + F_i(L) mod q.
+/// Decrypting with a wrong password then gives coefficients with the distribution
+/// of Compress_d, like the right password. The encrypted ciphertext is the 12-bit
+/// encoding of the permuted vector, 384*(k+1) bytes.
+pub struct PasswordCipher {
+    key: [u8; 32],
+}
+
+impl PasswordCipher {
+    /// Key of the cipher : TupleHash256((sid, password), 256, "CAKE password")
+    pub fn new(sid: &[u8], password: &[u8]) -> Self {
+        let key = tuple_hash256(&[sid, password], 32, b"CAKE password");
+        PasswordCipher {
+            key: key.try_into().unwrap(),
+        }
+    }
+
+    /// Splits a checked ek into t_hat in Z_q^(256*k) and rho
+    fn split_ek(parameter_set: ParameterSet, ek: &[u8]) -> Result<(Vec<i64>, [u8; 32]), KeyError> {
+        EncapsulationKey::from_bytes(parameter_set, ek)?;
+        let (t_hat, rho) = ek.split_at(ek.len() - 32);
+        Ok((byte_decode(t_hat, 12, Q), rho.try_into().unwrap()))
+    }
+
+    fn join_ek(t_hat: &[i64], rho: &[u8; 32]) -> Vec<u8> {
+        let mut ek = byte_encode(t_hat, 12);
+        ek.extend_from_slice(rho);
+        ek
+    }
+
+    pub fn encrypt_ek(&self, parameter_set: ParameterSet, ek: &[u8]) -> Result<Vec<u8>, KeyError> {
+        let (mut t_hat, mut rho) = Self::split_ek(parameter_set, ek)?;
+        for round in 0..ROUNDS {
+            if round.is_multiple_of(2) {
+                let xof = round_xof(b"CAKE ek t", &self.key, round, &rho);
+                let f_i = sample_mod_q(xof, t_hat.len());
+                for (t, f) in t_hat.iter_mut().zip(f_i) {
+                    *t = (*t + f) % Q;
+                }
+            } else {
+                let t_bytes = byte_encode(&t_hat, 12);
+                xor_into(
+                    &mut rho,
+                    round_xof(b"CAKE ek rho", &self.key, round, &t_bytes),
+                );
+            }
+        }
+        Ok(Self::join_ek(&t_hat, &rho))
+    }
+
+    pub fn decrypt_ek(&self, parameter_set: ParameterSet, aek: &[u8]) -> Result<Vec<u8>, KeyError> {
+        let (mut t_hat, mut rho) = Self::split_ek(parameter_set, aek)?;
+        for round in (0..ROUNDS).rev() {
+            if round.is_multiple_of(2) {
+                let xof = round_xof(b"CAKE ek t", &self.key, round, &rho);
+                let f_i = sample_mod_q(xof, t_hat.len());
+                for (t, f) in t_hat.iter_mut().zip(f_i) {
+                    *t = (*t - f + Q) % Q;
+                }
+            } else {
+                let t_bytes = byte_encode(&t_hat, 12);
+                xor_into(
+                    &mut rho,
+                    round_xof(b"CAKE ek rho", &self.key, round, &t_bytes),
+                );
+            }
+        }
+        Ok(Self::join_ek(&t_hat, &rho))
+    }
+
+    /// Length of an encrypted ciphertext : 12-bit encoding of Z_q^(256*(k+1))
+    pub fn ciphertext_len(parameter_set: ParameterSet) -> usize {
+        384 * (parameter_set.params().0 + 1)
+    }
+
+    /// One Feistel round on the halves of a vector of Z_q, adding (sign 1) or
+    /// subtracting (sign -1) the round function
+    fn ciphertext_round(&self, x: &mut [i64], round: u8, sign: i64) {
+        let (left, right) = x.split_at_mut(x.len() / 2);
+        let (target, input) = if round.is_multiple_of(2) {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        let f_i = sample_mod_q(
+            round_xof(b"CAKE ct", &self.key, round, &byte_encode(input, 12)),
+            input.len(),
+        );
+        for (t, f) in target.iter_mut().zip(f_i) {
+            *t = (*t + sign * f).rem_euclid(Q);
+        }
+    }
+
+    /// Lifts the coefficients of c to uniform preimages mod q, then permutes them
+    ///
+    /// Input : ciphertext c in B^(32 * (d_u*k + d_v))
+    pub fn encrypt_ciphertext(
+        &self,
+        parameter_set: ParameterSet,
+        c: &[u8],
+    ) -> Result<Vec<u8>, KeyError> {
+        let (k, _, _, d_u, d_v) = parameter_set.params();
+        check_length(parameter_set.ciphertext_len(), c.len())?;
+        let (c_1, c_2) = c.split_at(32 * d_u * k);
+        let mut x: Vec<i64> = (byte_decode(c_1, d_u, Q).into_iter().map(|y| lift(y, d_u)))
+            .chain(byte_decode(c_2, d_v, Q).into_iter().map(|y| lift(y, d_v)))
+            .collect();
+        for round in 0..ROUNDS {
+            self.ciphertext_round(&mut x, round, 1);
+        }
+        Ok(byte_encode(&x, 12))
+    }
+
+    /// Inverse permutation, then Compress_du and Compress_dv
+    ///
+    /// Input : encrypted ciphertext in B^(384*(k+1)), checked to be in
+    /// Z_q^(256*(k+1)) independently of the password
+    pub fn decrypt_ciphertext(
+        &self,
+        parameter_set: ParameterSet,
+        ec: &[u8],
+    ) -> Result<Vec<u8>, KeyError> {
+        let (k, _, _, d_u, d_v) = parameter_set.params();
+        check_length(Self::ciphertext_len(parameter_set), ec.len())?;
+        check_modulus(ec)?;
+        let mut x = byte_decode(ec, 12, Q);
+        for round in (0..ROUNDS).rev() {
+            self.ciphertext_round(&mut x, round, -1);
+        }
+        let (u, v) = x.split_at(256 * k);
+        let u: Vec<i64> = u.iter().map(|&x| compress(x, d_u, Q)).collect();
+        let v: Vec<i64> = v.iter().map(|&x| compress(x, d_v, Q)).collect();
+        Ok([byte_encode(&u, d_u), byte_encode(&v, d_v)].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kyber::keys::DecapsulationKey;
+
+    #[test]
+    fn basics() {
+        let cipher = PasswordCipher::new(b"sid", b"1234");
+        let other = PasswordCipher::new(b"sid", b"1235");
+        for set in ParameterSet::ALL {
+            let ek = DecapsulationKey::generate(set).encapsulation_key();
+            let ek = ek.as_bytes();
+            let aek = cipher.encrypt_ek(set, ek).unwrap();
+            assert_ne!(aek, ek);
+            assert_eq!(cipher.decrypt_ek(set, &aek).unwrap(), ek);
+            // A wrong password gives another valid key
+            let wrong = other.decrypt_ek(set, &aek).unwrap();
+            assert_ne!(wrong, ek);
+            assert!(EncapsulationKey::from_bytes(set, &wrong).is_ok());
+
+            let c = vec![0x5a; set.ciphertext_len()];
+            let ec = cipher.encrypt_ciphertext(set, &c).unwrap();
+            assert_eq!(ec.len(), PasswordCipher::ciphertext_len(set));
+            assert_eq!(cipher.decrypt_ciphertext(set, &ec).unwrap(), c);
+            assert_ne!(other.decrypt_ciphertext(set, &ec).unwrap(), c);
+            // The lift is randomized
+            assert_ne!(cipher.encrypt_ciphertext(set, &c).unwrap(), ec);
+            assert_eq!(
+                cipher.encrypt_ciphertext(set, &c[1..]),
+                Err(KeyError::InvalidLength {
+                    expected: c.len(),
+                    found: c.len() - 1
+                })
+            );
+            assert_eq!(
+                cipher.decrypt_ciphertext(set, &ec[3..]),
+                Err(KeyError::InvalidLength {
+                    expected: ec.len(),
+                    found: ec.len() - 3
+                })
+            );
+
+            let mut invalid = ec.clone();
+            invalid[0] = 0xff;
+            invalid[1] |= 0x0f;
+            assert_eq!(
+                other.decrypt_ciphertext(set, &invalid),
+                Err(KeyError::InvalidModulus)
+            );
+        }
+
+        for d in [4, 5, 10, 11] {
+            for y in 0..1 << d {
+                let (start, count) = preimages(y, d);
+                let expected: Vec<_> = (0..Q).filter(|&x| compress(x, d, Q) == y).collect();
+                let mut found: Vec<_> = (start..start + count).map(|x| x.rem_euclid(Q)).collect();
+                found.sort();
+                assert_eq!(found, expected);
+            }
+        }
+
+        // Every preimage of Compress_10 is reached
+        for y in [0, 1, 511, 1023] {
+            let mut lifts: Vec<_> = (0..200).map(|_| lift(y, 10)).collect();
+            lifts.sort();
+            lifts.dedup();
+            let preimages: Vec<_> = (0..Q).filter(|&x| compress(x, 10, Q) == y).collect();
+            assert_eq!(lifts, preimages);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use core::fmt;
+
+use subtle::ConstantTimeEq;
+
+use crate::hash::tuple_hash256;
+use crate::kyber::kem_scheme::ParameterSet;
+use crate::kyber::keys::{DecapsulationKey, EncapsulationKey, KeyError};
+use crate::pake::cipher::PasswordCipher;
+
+/// Length of the session key
+pub const SESSION_KEY_LEN: usize = 32;
+/// Length of the key confirmation tag of OCAKE
+pub const CONFIRMATION_LEN: usize = 32;
+
+/// Password-authenticated key exchanges built from a KEM by Beguinet, Chevalier,
+/// Pointcheval, Ricosset and Rossi ("GeT a CAKE: Generic Transformations from Key
+/// Encapsulation Mechanisms to Password Authenticated Key Exchanges", ACNS 2023)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PakeMode {
+    /// CAKE : the encapsulation key and the ciphertext are both password-encrypted,
+    /// and a wrong password only gives different session keys
+    Cake,
+    /// OCAKE : only the encapsulation key is password-encrypted; the ciphertext is
+    /// sent in clear with a key confirmation tag, checked by the initiator
+    Ocake,
+}
+
+impl PakeMode {
+    /// Customization string of the session key derivation
+    fn label(&self) -> &'static [u8] {
+        match self {
+            PakeMode::Cake => b"CAKE",
+            PakeMode::Ocake => b"OCAKE",
+        }
+    }
+
+    /// Length of the message of the initiator : an encrypted encapsulation key
+    pub fn initiator_message_len(&self, parameter_set: ParameterSet) -> usize {
+        parameter_set.ek_len()
+    }
+
+    /// Length of the message of the responder : an encrypted ciphertext for CAKE, a
+    /// ciphertext and a confirmation tag for OCAKE
+    pub fn responder_message_len(&self, parameter_set: ParameterSet) -> usize {
+        match self {
+            PakeMode::Cake => PasswordCipher::ciphertext_len(parameter_set),
+            PakeMode::Ocake => parameter_set.ciphertext_len() + CONFIRMATION_LEN,
+        }
+    }
+}
+
+/// Errors raised by the password-authenticated key exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PakeError {
+    /// The message does not have the length fixed by the parameter set and the mode
+    InvalidMessageLength { expected: usize, found: usize },
+    /// OCAKE : the confirmation tag does not match, because of a wrong password or a
+    /// modified message
+    ConfirmationFailed,
+    /// CAKE : the encrypted ciphertext is not in Z_q^(256*(k+1))
+    InvalidCiphertext,
+    /// The encrypted encapsulation key is not in Z_q^(256*k) x B^32
+    Key(KeyError),
+}
+
+impl fmt::Display for PakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PakeError::InvalidMessageLength { expected, found } => {
+                write!(
+                    f,
+                    "invalid PAKE message length {found}, expected {expected}"
+                )
+            }
+            PakeError::ConfirmationFailed => write!(f, "PAKE key confirmation failed"),
+            PakeError::InvalidCiphertext => write!(f, "invalid PAKE encrypted ciphertext"),
+            PakeError::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for PakeError {}
+
+impl From<KeyError> for PakeError {
+    fn from(err: KeyError) -> Self {
+        PakeError::Key(err)
+    }
+}
+
+fn check_length(expected: usize, found: usize) -> Result<(), PakeError> {
+    if expected != found {
+        return Err(PakeError::InvalidMessageLength { expected, found });
+    }
+    Ok(())
+}
+
+/// Public parameters of a session, agreed on by both parties beforehand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub parameter_set: ParameterSet,
+    /// Session identifier, unique to the session
+    pub sid: Vec<u8>,
+    pub initiator: Vec<u8>,
+    pub responder: Vec<u8>,
+}
+
+impl Session {
+    pub fn new(
+        parameter_set: ParameterSet,
+        sid: &[u8],
+        initiator: &[u8],
+        responder: &[u8],
+    ) -> Self {
+        Session {
+            parameter_set,
+            sid: sid.to_vec(),
+            initiator: initiator.to_vec(),
+            responder: responder.to_vec(),
+        }
+    }
+
+    /// Key derivation from the transcript :
+    ///
+    /// - CAKE : TupleHash256((set, sid, A, B, e_A, e_B, K), 256, "CAKE")
+    /// - OCAKE : TupleHash256((set, sid, A, B, ek, e_A, c, K), 256, s) with s
+    ///   "OCAKE" for the session key and "OCAKE confirmation" for the tag
+    fn derive(&self, transcript: &[&[u8]], label: &[u8]) -> [u8; 32] {
+        let mut input: Vec<&[u8]> = vec![
+            self.parameter_set.name().as_bytes(),
+            &self.sid,
+            &self.initiator,
+            &self.responder,
+        ];
+        input.extend_from_slice(transcript);
+        tuple_hash256(&input, 32, label).try_into().unwrap()
+    }
+}
+
+/// Initiator : sends its ephemeral encapsulation key encrypted under the password
+pub struct Initiator {
+    mode: PakeMode,
+    session: Session,
+    cipher: PasswordCipher,
+    dk: DecapsulationKey,
+    message: Vec<u8>,
+}
+
+impl Initiator {
+    pub fn new(mode: PakeMode, session: Session, password: &[u8]) -> Self {
+        let cipher = PasswordCipher::new(&session.sid, password);
+        let dk = DecapsulationKey::generate(session.parameter_set);
+        let message = cipher
+            .encrypt_ek(session.parameter_set, dk.encapsulation_key().as_bytes())
+            .expect("generated keys are valid");
+        Initiator {
+            mode,
+            session,
+            cipher,
+            dk,
+            message,
+        }
+    }
+
+    pub fn mode(&self) -> PakeMode {
+        self.mode
+    }
+
+    /// Message e_A for the responder
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// Processes the message of the responder and returns the session key
+    pub fn finish(self, response: &[u8]) -> Result<[u8; SESSION_KEY_LEN], PakeError> {
+        let set = self.session.parameter_set;
+        check_length(self.mode.responder_message_len(set), response.len())?;
+        match self.mode {
+            PakeMode::Cake => {
+                let c = self
+                    .cipher
+                    .decrypt_ciphertext(set, response)
+                    .map_err(|_| PakeError::InvalidCiphertext)?;
+                let k = self.dk.decaps(&c);
+                Ok(self
+                    .session
+                    .derive(&[&self.message, response, &k], self.mode.label()))
+            }
+            PakeMode::Ocake => {
+                let (c, tag) = response.split_at(set.ciphertext_len());
+                let k = self.dk.decaps(c);
+                let ek = self.dk.encapsulation_key();
+                let transcript: [&[u8]; 4] = [ek.as_bytes(), &self.message, c, &k];
+                let expected = self.session.derive(&transcript, b"OCAKE confirmation");
+                if !bool::from(expected.ct_eq(tag)) {
+                    return Err(PakeError::ConfirmationFailed);
+                }
+                Ok(self.session.derive(&transcript, self.mode.label()))
+            }
+        }
+    }
+}
+
+/// Responder : encapsulates to the password-decrypted key of the initiator
+pub struct Responder {
+    mode: PakeMode,
+    session: Session,
+    cipher: PasswordCipher,
+}
+
+impl Responder {
+    pub fn new(mode: PakeMode, session: Session, password: &[u8]) -> Self {
+        let cipher = PasswordCipher::new(&session.sid, password);
+        Responder {
+            mode,
+            session,
+            cipher,
+        }
+    }
+
+    pub fn mode(&self) -> PakeMode {
+        self.mode
+    }
+
+    /// Processes the message of the initiator : returns the message e_B for the
+    /// initiator and the session key
+    pub fn respond(&self, message: &[u8]) -> Result<(Vec<u8>, [u8; SESSION_KEY_LEN]), PakeError> {
+        let set = self.session.parameter_set;
+        check_length(self.mode.initiator_message_len(set), message.len())?;
+        let ek = EncapsulationKey::from_bytes(set, &self.cipher.decrypt_ek(set, message)?)?;
+        let (k, c) = ek.encaps();
+        match self.mode {
+            PakeMode::Cake => {
+                let response = self.cipher.encrypt_ciphertext(set, &c)?;
+                let key = self
+                    .session
+                    .derive(&[message, &response, &k], self.mode.label());
+                Ok((response, key))
+            }
+            PakeMode::Ocake => {
+                let transcript: [&[u8]; 4] = [ek.as_bytes(), message, &c, &k];
+                let tag = self.session.derive(&transcript, b"OCAKE confirmation");
+                let key = self.session.derive(&transcript, self.mode.label());
+                Ok(([c.as_slice(), &tag].concat(), key))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for mode in [PakeMode::Cake, PakeMode::Ocake] {
+            for set in ParameterSet::ALL {
+                let session = Session::new(set, b"sid", b"phone", b"laptop");
+                let initiator = Initiator::new(mode, session.clone(), b"482913");
+                let responder = Responder::new(mode, session, b"482913");
+                assert_eq!(initiator.message().len(), mode.initiator_message_len(set));
+                let (response, key) = responder.respond(initiator.message()).unwrap();
+                assert_eq!(response.len(), mode.responder_message_len(set));
+                assert_eq!(initiator.finish(&response).unwrap(), key);
+            }
+        }
+    }
+}
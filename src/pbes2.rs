@@ -0,0 +1,509 @@
+use core::fmt;
+
+use aes::Aes256;
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha2::Sha256;
+
+use crate::der::{self, DerError, Reader};
+use crate::kyber::keys::{DecapsulationKey, KeyError};
+use crate::pkcs8::{Pkcs8Error, PrivateKeyFormat};
+
+/// DER contents of the OIDs used by PBES2 (RFC 8018, RFC 7914, RFC 5084)
+const OID_PBES2: [u8; 9] = [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0d];
+const OID_PBKDF2: [u8; 9] = [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0c];
+const OID_HMAC_SHA256: [u8; 8] = [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x09];
+const OID_SCRYPT: [u8; 9] = [0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x04, 0x0b];
+const OID_AES256_CBC: [u8; 9] = [0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2a];
+const OID_AES256_GCM: [u8; 9] = [0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2e];
+
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const CBC_IV_LEN: usize = 16;
+const GCM_NONCE_LEN: usize = 12;
+const GCM_TAG_LEN: usize = 16;
+
+/// Upper bounds on the cost parameters, so that a crafted file cannot make the key
+/// derivation run for hours or exhaust memory
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+const MAX_SCRYPT_LOG_N: u8 = 22;
+const MAX_SCRYPT_R: u32 = 32;
+const MAX_SCRYPT_P: u32 = 16;
+/// Bound on the memory used by scrypt, 128 * r * N bytes (1 GiB)
+const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+
+type Aes256CbcEnc = cbc::Encryptor<Aes256>;
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+
+/// Password-based key derivation function of PBES2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordKdf {
+    /// PBKDF2 with HMAC-SHA256 (RFC 8018)
+    Pbkdf2Sha256 { iterations: u32 },
+    /// scrypt with N = 2^log_n (RFC 7914)
+    Scrypt { log_n: u8, r: u32, p: u32 },
+}
+
+impl PasswordKdf {
+    /// Checks the cost parameters against the bounds accepted by this crate
+    fn check(&self) -> Result<(), Pbes2Error> {
+        let valid = match *self {
+            PasswordKdf::Pbkdf2Sha256 { iterations } => {
+                (1..=MAX_PBKDF2_ITERATIONS).contains(&iterations)
+            }
+            PasswordKdf::Scrypt { log_n, r, p } => {
+                (1..=MAX_SCRYPT_LOG_N).contains(&log_n)
+                    && (1..=MAX_SCRYPT_R).contains(&r)
+                    && (1..=MAX_SCRYPT_P).contains(&p)
+                    && 128 * r as u64 * (1u64 << log_n) <= MAX_SCRYPT_MEMORY
+            }
+        };
+        if !valid {
+            return Err(Pbes2Error::InvalidParameters);
+        }
+        Ok(())
+    }
+}
+
+/// Encryption scheme of PBES2, always with a 256-bit key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordCipher {
+    /// AES-256-GCM with a 12-byte nonce and a 16-byte tag (RFC 5084)
+    Aes256Gcm,
+    /// AES-256-CBC with PKCS#7 padding, readable by OpenSSL
+    Aes256Cbc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pbes2Params {
+    pub kdf: PasswordKdf,
+    pub cipher: PasswordCipher,
+}
+
+impl Default for Pbes2Params {
+    /// scrypt with N = 2^17, r = 8, p = 1 and AES-256-GCM
+    fn default() -> Self {
+        Pbes2Params {
+            kdf: PasswordKdf::Scrypt {
+                log_n: 17,
+                r: 8,
+                p: 1,
+            },
+            cipher: PasswordCipher::Aes256Gcm,
+        }
+    }
+}
+
+/// Errors raised when decrypting an EncryptedPrivateKeyInfo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pbes2Error {
+    /// The file is damaged : the DER structure around the encrypted key is invalid
+    Corrupted(DerError),
+    /// The encryption scheme, key derivation function or PRF is not supported
+    UnsupportedAlgorithm,
+    /// The parameters are out of range (key length, IV length, cost parameters)
+    InvalidParameters,
+    /// Decryption failed : the password is wrong. With AES-256-GCM, an encrypted
+    /// payload modified after encryption also ends up here.
+    WrongPassword,
+    /// The key decrypted correctly but its content is invalid
+    InvalidKey(Pkcs8Error),
+    /// The key cannot be encoded in the requested format
+    Key(KeyError),
+}
+
+impl fmt::Display for Pbes2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pbes2Error::Corrupted(err) => write!(f, "corrupted encrypted key: {err}"),
+            Pbes2Error::UnsupportedAlgorithm => write!(f, "unsupported PBES2 algorithm"),
+            Pbes2Error::InvalidParameters => write!(f, "invalid PBES2 parameters"),
+            Pbes2Error::WrongPassword => write!(f, "wrong password"),
+            Pbes2Error::InvalidKey(err) => write!(f, "invalid decrypted key: {err}"),
+            Pbes2Error::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Pbes2Error {}
+
+impl From<DerError> for Pbes2Error {
+    fn from(err: DerError) -> Self {
+        Pbes2Error::Corrupted(err)
+    }
+}
+
+impl From<KeyError> for Pbes2Error {
+    fn from(err: KeyError) -> Self {
+        Pbes2Error::Key(err)
+    }
+}
+
+/// KDF parameters as read from or written to the file
+struct KdfParams {
+    kdf: PasswordKdf,
+    salt: Vec<u8>,
+}
+
+impl KdfParams {
+    fn derive_key(&self, password: &[u8]) -> Result<[u8; KEY_LEN], Pbes2Error> {
+        self.kdf.check()?;
+        let mut key = [0u8; KEY_LEN];
+        match self.kdf {
+            PasswordKdf::Pbkdf2Sha256 { iterations } => {
+                pbkdf2::pbkdf2_hmac::<Sha256>(password, &self.salt, iterations, &mut key)
+            }
+            PasswordKdf::Scrypt { log_n, r, p } => {
+                let params = scrypt::Params::new(log_n, r, p, KEY_LEN)
+                    .map_err(|_| Pbes2Error::InvalidParameters)?;
+                scrypt::scrypt(password, &self.salt, &params, &mut key)
+                    .map_err(|_| Pbes2Error::InvalidParameters)?;
+            }
+        }
+        Ok(key)
+    }
+
+    /// keyDerivationFunc AlgorithmIdentifier
+    fn encode(&self) -> Vec<u8> {
+        let mut params = der::tlv(der::TAG_OCTET_STRING, &self.salt);
+        let oid = match self.kdf {
+            PasswordKdf::Pbkdf2Sha256 { iterations } => {
+                der::write_uint(&mut params, iterations as u64);
+                let mut prf = der::tlv(der::TAG_OID, &OID_HMAC_SHA256);
+                der::write_tlv(&mut prf, der::TAG_NULL, &[]);
+                der::write_tlv(&mut params, der::TAG_SEQUENCE, &prf);
+                OID_PBKDF2
+            }
+            PasswordKdf::Scrypt { log_n, r, p } => {
+                der::write_uint(&mut params, 1u64 << log_n);
+                der::write_uint(&mut params, r as u64);
+                der::write_uint(&mut params, p as u64);
+                OID_SCRYPT
+            }
+        };
+        let mut alg = der::tlv(der::TAG_OID, &oid);
+        der::write_tlv(&mut alg, der::TAG_SEQUENCE, &params);
+        der::tlv(der::TAG_SEQUENCE, &alg)
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, Pbes2Error> {
+        let mut alg = Reader::new(reader.read(der::TAG_SEQUENCE)?);
+        let oid = alg.read(der::TAG_OID)?;
+        let mut params = Reader::new(alg.read(der::TAG_SEQUENCE)?);
+        alg.finish()?;
+
+        let salt = params.read(der::TAG_OCTET_STRING)?.to_vec();
+        let kdf = if oid == OID_PBKDF2 {
+            let iterations = read_u32(&mut params)?;
+            read_key_length(&mut params)?;
+            // the default PRF is HMAC-SHA1, which is not supported
+            let mut prf = Reader::new(params.read(der::TAG_SEQUENCE)?);
+            if prf.read(der::TAG_OID)? != OID_HMAC_SHA256 {
+                return Err(Pbes2Error::UnsupportedAlgorithm);
+            }
+            if !prf.is_empty() {
+                prf.read(der::TAG_NULL)?;
+            }
+            prf.finish()?;
+            PasswordKdf::Pbkdf2Sha256 { iterations }
+        } else if oid == OID_SCRYPT {
+            let n = params.read_uint()?;
+            let r = read_u32(&mut params)?;
+            let p = read_u32(&mut params)?;
+            read_key_length(&mut params)?;
+            if !n.is_power_of_two() {
+                return Err(Pbes2Error::InvalidParameters);
+            }
+            PasswordKdf::Scrypt {
+                log_n: n.trailing_zeros() as u8,
+                r,
+                p,
+            }
+        } else {
+            return Err(Pbes2Error::UnsupportedAlgorithm);
+        };
+        params.finish()?;
+        kdf.check()?;
+        Ok(KdfParams { kdf, salt })
+    }
+}
+
+fn read_u32(params: &mut Reader) -> Result<u32, Pbes2Error> {
+    u32::try_from(params.read_uint()?).map_err(|_| Pbes2Error::InvalidParameters)
+}
+
+/// Optional keyLength INTEGER, which must match the 256-bit AES key when present
+fn read_key_length(params: &mut Reader) -> Result<(), Pbes2Error> {
+    if params.peek_tag() == Some(der::TAG_INTEGER) && params.read_uint()? != KEY_LEN as u64 {
+        return Err(Pbes2Error::InvalidParameters);
+    }
+    Ok(())
+}
+
+/// Encryption scheme parameters : the IV for CBC, the nonce for GCM
+struct CipherParams {
+    cipher: PasswordCipher,
+    iv: Vec<u8>,
+}
+
+impl CipherParams {
+    fn encrypt(&self, key: &[u8; KEY_LEN], plaintext: &[u8]) -> Vec<u8> {
+        match self.cipher {
+            PasswordCipher::Aes256Cbc => Aes256CbcEnc::new(key.into(), self.iv.as_slice().into())
+                .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+            PasswordCipher::Aes256Gcm => Aes256Gcm::new(key.into())
+                .encrypt(Nonce::from_slice(&self.iv), plaintext)
+                .expect("AES-GCM encryption of a private key cannot fail"),
+        }
+    }
+
+    fn decrypt(&self, key: &[u8; KEY_LEN], ciphertext: &[u8]) -> Result<Vec<u8>, Pbes2Error> {
+        match self.cipher {
+            PasswordCipher::Aes256Cbc => Aes256CbcDec::new(key.into(), self.iv.as_slice().into())
+                .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+                .map_err(|_| Pbes2Error::WrongPassword),
+            PasswordCipher::Aes256Gcm => Aes256Gcm::new(key.into())
+                .decrypt(Nonce::from_slice(&self.iv), ciphertext)
+                .map_err(|_| Pbes2Error::WrongPassword),
+        }
+    }
+
+    /// encryptionScheme AlgorithmIdentifier
+    fn encode(&self) -> Vec<u8> {
+        let alg = match self.cipher {
+            PasswordCipher::Aes256Cbc => {
+                let mut alg = der::tlv(der::TAG_OID, &OID_AES256_CBC);
+                der::write_tlv(&mut alg, der::TAG_OCTET_STRING, &self.iv);
+                alg
+            }
+            PasswordCipher::Aes256Gcm => {
+                let mut params = der::tlv(der::TAG_OCTET_STRING, &self.iv);
+                der::write_uint(&mut params, GCM_TAG_LEN as u64);
+                let mut alg = der::tlv(der::TAG_OID, &OID_AES256_GCM);
+                der::write_tlv(&mut alg, der::TAG_SEQUENCE, &params);
+                alg
+            }
+        };
+        der::tlv(der::TAG_SEQUENCE, &alg)
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, Pbes2Error> {
+        let mut alg = Reader::new(reader.read(der::TAG_SEQUENCE)?);
+        let oid = alg.read(der::TAG_OID)?;
+        let params = if oid == OID_AES256_CBC {
+            let iv = alg.read(der::TAG_OCTET_STRING)?;
+            if iv.len() != CBC_IV_LEN {
+                return Err(Pbes2Error::InvalidParameters);
+            }
+            CipherParams {
+                cipher: PasswordCipher::Aes256Cbc,
+                iv: iv.to_vec(),
+            }
+        } else if oid == OID_AES256_GCM {
+            let mut gcm = Reader::new(alg.read(der::TAG_SEQUENCE)?);
+            let nonce = gcm.read(der::TAG_OCTET_STRING)?;
+            // aes-ICVlen defaults to 12, only 16-byte tags are supported
+            let tag_len = if gcm.is_empty() { 12 } else { gcm.read_uint()? };
+            gcm.finish()?;
+            if nonce.len() != GCM_NONCE_LEN || tag_len != GCM_TAG_LEN as u64 {
+                return Err(Pbes2Error::InvalidParameters);
+            }
+            CipherParams {
+                cipher: PasswordCipher::Aes256Gcm,
+                iv: nonce.to_vec(),
+            }
+        } else {
+            return Err(Pbes2Error::UnsupportedAlgorithm);
+        };
+        alg.finish()?;
+        Ok(params)
+    }
+}
+
+impl DecapsulationKey {
+    /// Encrypts the PKCS#8 encoding of the key under the password, as an
+    /// EncryptedPrivateKeyInfo with PBES2 (RFC 5958, RFC 8018).
+    ///
+    /// Cost parameters above the bounds accepted by `from_encrypted_pkcs8_der` are
+    /// rejected as `InvalidParameters`.
+    pub fn to_encrypted_pkcs8_der(
+        &self,
+        password: &[u8],
+        format: PrivateKeyFormat,
+        params: Pbes2Params,
+    ) -> Result<Vec<u8>, Pbes2Error> {
+        params.kdf.check()?;
+        let plaintext = self.to_pkcs8_der(format)?;
+
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let kdf = KdfParams {
+            kdf: params.kdf,
+            salt,
+        };
+        let mut iv = vec![
+            0u8;
+            match params.cipher {
+                PasswordCipher::Aes256Cbc => CBC_IV_LEN,
+                PasswordCipher::Aes256Gcm => GCM_NONCE_LEN,
+            }
+        ];
+        OsRng.fill_bytes(&mut iv);
+        let cipher = CipherParams {
+            cipher: params.cipher,
+            iv,
+        };
+
+        let key = kdf.derive_key(password)?;
+        let encrypted = cipher.encrypt(&key, &plaintext);
+
+        let mut pbes2_params = kdf.encode();
+        pbes2_params.extend_from_slice(&cipher.encode());
+        let mut alg = der::tlv(der::TAG_OID, &OID_PBES2);
+        der::write_tlv(&mut alg, der::TAG_SEQUENCE, &pbes2_params);
+
+        let mut content = der::tlv(der::TAG_SEQUENCE, &alg);
+        der::write_tlv(&mut content, der::TAG_OCTET_STRING, &encrypted);
+        Ok(der::tlv(der::TAG_SEQUENCE, &content))
+    }
+
+    /// Decrypts an EncryptedPrivateKeyInfo produced by `to_encrypted_pkcs8_der` or by
+    /// OpenSSL (`openssl pkcs8 -topk8 -v2 aes-256-cbc [-scrypt]`).
+    ///
+    /// A damaged DER structure is reported as `Corrupted`, a failed decryption as
+    /// `WrongPassword`, and a decrypted key that fails its checks as `InvalidKey`.
+    pub fn from_encrypted_pkcs8_der(bytes: &[u8], password: &[u8]) -> Result<Self, Pbes2Error> {
+        let mut outer = Reader::new(bytes);
+        let mut info = Reader::new(outer.read(der::TAG_SEQUENCE)?);
+        outer.finish()?;
+
+        let mut alg = Reader::new(info.read(der::TAG_SEQUENCE)?);
+        if alg.read(der::TAG_OID)? != OID_PBES2 {
+            return Err(Pbes2Error::UnsupportedAlgorithm);
+        }
+        let mut pbes2_params = Reader::new(alg.read(der::TAG_SEQUENCE)?);
+        alg.finish()?;
+        let kdf = KdfParams::decode(&mut pbes2_params)?;
+        let cipher = CipherParams::decode(&mut pbes2_params)?;
+        pbes2_params.finish()?;
+
+        let encrypted = info.read(der::TAG_OCTET_STRING)?;
+        info.finish()?;
+
+        let key = kdf.derive_key(password)?;
+        let plaintext = cipher.decrypt(&key, encrypted)?;
+        DecapsulationKey::from_pkcs8_der(&plaintext).map_err(|err| match err {
+            // CBC with a wrong key yields random bytes that still end with valid padding
+            Pkcs8Error::Der(_) if cipher.cipher == PasswordCipher::Aes256Cbc => {
+                Pbes2Error::WrongPassword
+            }
+            err => Pbes2Error::InvalidKey(err),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kyber::kem_scheme::ParameterSet;
+
+    #[test]
+    fn basics() {
+        let dk = DecapsulationKey::generate(ParameterSet::MlKem512);
+        let password = b"correct horse battery staple";
+
+        for kdf in [
+            PasswordKdf::Pbkdf2Sha256 { iterations: 1000 },
+            PasswordKdf::Scrypt {
+                log_n: 10,
+                r: 8,
+                p: 1,
+            },
+        ] {
+            for cipher in [PasswordCipher::Aes256Gcm, PasswordCipher::Aes256Cbc] {
+                let params = Pbes2Params { kdf, cipher };
+                let encrypted = dk
+                    .to_encrypted_pkcs8_der(password, PrivateKeyFormat::Seed, params)
+                    .unwrap();
+                let decrypted =
+                    DecapsulationKey::from_encrypted_pkcs8_der(&encrypted, password).unwrap();
+                assert_eq!(decrypted.seed(), dk.seed());
+
+                assert_eq!(
+                    DecapsulationKey::from_encrypted_pkcs8_der(&encrypted, b"wrong password")
+                        .unwrap_err(),
+                    Pbes2Error::WrongPassword
+                );
+
+                let truncated = &encrypted[..encrypted.len() - 1];
+                assert_eq!(
+                    DecapsulationKey::from_encrypted_pkcs8_der(truncated, password).unwrap_err(),
+                    Pbes2Error::Corrupted(DerError::Truncated)
+                );
+            }
+        }
+
+        // Out of bounds parameters are rejected before running the KDF
+        for kdf in [
+            PasswordKdf::Pbkdf2Sha256 { iterations: 0 },
+            PasswordKdf::Pbkdf2Sha256 {
+                iterations: MAX_PBKDF2_ITERATIONS + 1,
+            },
+            PasswordKdf::Scrypt {
+                log_n: 0,
+                r: 8,
+                p: 1,
+            },
+            PasswordKdf::Scrypt {
+                log_n: 10,
+                r: 0,
+                p: 1,
+            },
+            PasswordKdf::Scrypt {
+                log_n: 10,
+                r: 8,
+                p: 0,
+            },
+            PasswordKdf::Scrypt {
+                log_n: 10,
+                r: 1 << 20,
+                p: 1,
+            },
+            PasswordKdf::Scrypt {
+                log_n: 10,
+                r: 8,
+                p: 1 << 29,
+            },
+            // 128 * 8 * 2^22 bytes = 4 GiB
+            PasswordKdf::Scrypt {
+                log_n: 22,
+                r: 8,
+                p: 1,
+            },
+        ] {
+            let params = Pbes2Params {
+                kdf,
+                cipher: PasswordCipher::Aes256Gcm,
+            };
+            assert_eq!(
+                dk.to_encrypted_pkcs8_der(password, PrivateKeyFormat::Seed, params),
+                Err(Pbes2Error::InvalidParameters)
+            );
+
+            // The same parameters in a file are rejected when decrypting
+            let file = KdfParams {
+                kdf,
+                salt: vec![0; SALT_LEN],
+            }
+            .encode();
+            assert_eq!(
+                KdfParams::decode(&mut Reader::new(&file)).err(),
+                Some(Pbes2Error::InvalidParameters)
+            );
+        }
+    }
+}
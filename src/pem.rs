@@ -0,0 +1,225 @@
+use core::fmt;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::kyber::kem_scheme::ParameterSet;
+use crate::kyber::keys::{DecapsulationKey, EncapsulationKey, KeyError};
+use crate::pbes2::{Pbes2Error, Pbes2Params};
+use crate::pkcs8::{Pkcs8Error, PrivateKeyFormat};
+
+pub const PUBLIC_KEY_LABEL: &str = "PUBLIC KEY";
+pub const PRIVATE_KEY_LABEL: &str = "PRIVATE KEY";
+pub const ENCRYPTED_PRIVATE_KEY_LABEL: &str = "ENCRYPTED PRIVATE KEY";
+pub const CIPHERTEXT_LABEL: &str = "ML-KEM CIPHERTEXT";
+
+/// Number of base64 characters per line (RFC 7468)
+const LINE_WIDTH: usize = 64;
+
+/// Errors raised while decoding a PEM document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PemError {
+    /// The document does not start with a -----BEGIN label----- line
+    MissingHeader,
+    /// The document does not end with the -----END label----- line
+    MissingFooter,
+    /// The label differs from the expected one
+    UnexpectedLabel {
+        expected: String,
+        found: String,
+    },
+    /// The body is not valid padded base64
+    InvalidBase64,
+    /// The ciphertext length does not match the parameter set
+    InvalidCiphertextLength(usize),
+    Pkcs8(Pkcs8Error),
+    Encrypted(Pbes2Error),
+}
+
+impl fmt::Display for PemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PemError::MissingHeader => write!(f, "missing PEM header"),
+            PemError::MissingFooter => write!(f, "missing PEM footer"),
+            PemError::UnexpectedLabel { expected, found } => {
+                write!(
+                    f,
+                    "unexpected PEM label \"{found}\", expected \"{expected}\""
+                )
+            }
+            PemError::InvalidBase64 => write!(f, "invalid base64 in PEM body"),
+            PemError::InvalidCiphertextLength(len) => {
+                write!(f, "invalid ML-KEM ciphertext length {len}")
+            }
+            PemError::Pkcs8(err) => write!(f, "{err}"),
+            PemError::Encrypted(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for PemError {}
+
+impl From<Pkcs8Error> for PemError {
+    fn from(err: Pkcs8Error) -> Self {
+        PemError::Pkcs8(err)
+    }
+}
+
+impl From<Pbes2Error> for PemError {
+    fn from(err: Pbes2Error) -> Self {
+        PemError::Encrypted(err)
+    }
+}
+
+/// Encodes der between -----BEGIN label----- and -----END label----- lines,
+/// with the base64 body wrapped at 64 characters
+pub fn encode(label: &str, der: &[u8]) -> String {
+    let body = STANDARD.encode(der);
+    let mut out = format!("-----BEGIN {label}-----\n");
+    for line in body.as_bytes().chunks(LINE_WIDTH) {
+        out.push_str(core::str::from_utf8(line).unwrap());
+        out.push('\n');
+    }
+    out.push_str(&format!("-----END {label}-----\n"));
+    out
+}
+
+/// Decodes a PEM document whose label must be exactly `label`.
+///
+/// Whitespace around the document, around the encapsulation boundaries and inside
+/// the base64 body (including CRLF line endings) is ignored.
+pub fn decode(label: &str, pem: &str) -> Result<Vec<u8>, PemError> {
+    let pem = pem.trim();
+    let rest = pem
+        .strip_prefix("-----BEGIN ")
+        .ok_or(PemError::MissingHeader)?;
+    let (found, rest) = rest.split_once("-----").ok_or(PemError::MissingHeader)?;
+    if found != label {
+        return Err(PemError::UnexpectedLabel {
+            expected: label.to_string(),
+            found: found.to_string(),
+        });
+    }
+    let body = rest
+        .strip_suffix(&format!("-----END {label}-----"))
+        .ok_or(PemError::MissingFooter)?;
+
+    let body: String = body.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    STANDARD.decode(body).map_err(|_| PemError::InvalidBase64)
+}
+
+impl EncapsulationKey {
+    /// SubjectPublicKeyInfo in a PUBLIC KEY document
+    pub fn to_pem(&self) -> String {
+        encode(PUBLIC_KEY_LABEL, &self.to_public_key_der())
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self, PemError> {
+        Ok(EncapsulationKey::from_public_key_der(&decode(
+            PUBLIC_KEY_LABEL,
+            pem,
+        )?)?)
+    }
+}
+
+impl DecapsulationKey {
+    /// PKCS#8 OneAsymmetricKey in a PRIVATE KEY document
+    pub fn to_pem(&self, format: PrivateKeyFormat) -> Result<String, KeyError> {
+        Ok(encode(PRIVATE_KEY_LABEL, &self.to_pkcs8_der(format)?))
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self, PemError> {
+        Ok(DecapsulationKey::from_pkcs8_der(&decode(
+            PRIVATE_KEY_LABEL,
+            pem,
+        )?)?)
+    }
+
+    /// Password-encrypted PKCS#8 in an ENCRYPTED PRIVATE KEY document
+    pub fn to_encrypted_pem(
+        &self,
+        password: &[u8],
+        format: PrivateKeyFormat,
+        params: Pbes2Params,
+    ) -> Result<String, Pbes2Error> {
+        let der = self.to_encrypted_pkcs8_der(password, format, params)?;
+        Ok(encode(ENCRYPTED_PRIVATE_KEY_LABEL, &der))
+    }
+
+    pub fn from_encrypted_pem(pem: &str, password: &[u8]) -> Result<Self, PemError> {
+        let der = decode(ENCRYPTED_PRIVATE_KEY_LABEL, pem)?;
+        Ok(DecapsulationKey::from_encrypted_pkcs8_der(&der, password)?)
+    }
+}
+
+/// Raw ciphertext c in an ML-KEM CIPHERTEXT document
+pub fn ciphertext_to_pem(c: &[u8]) -> String {
+    encode(CIPHERTEXT_LABEL, c)
+}
+
+/// Decodes an ML-KEM CIPHERTEXT document, checking the length against the parameter set
+pub fn ciphertext_from_pem(parameter_set: ParameterSet, pem: &str) -> Result<Vec<u8>, PemError> {
+    let c = decode(CIPHERTEXT_LABEL, pem)?;
+    if c.len() != parameter_set.ciphertext_len() {
+        return Err(PemError::InvalidCiphertextLength(c.len()));
+    }
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        let pem = encode("TEST", &[0u8; 100]);
+        assert!(pem.starts_with("-----BEGIN TEST-----\nAAAA"));
+        assert!(pem.ends_with("\n-----END TEST-----\n"));
+        assert!(pem.lines().all(|line| line.len() <= LINE_WIDTH));
+        assert_eq!(decode("TEST", &pem).unwrap(), [0u8; 100]);
+
+        let crlf = format!("\r\n  {}  \r\n", pem.replace('\n', "\r\n"));
+        assert_eq!(decode("TEST", &crlf).unwrap(), [0u8; 100]);
+        let unwrapped = "-----BEGIN TEST-----AAEC-----END TEST-----";
+        assert_eq!(decode("TEST", unwrapped).unwrap(), [0, 1, 2]);
+
+        assert_eq!(
+            decode("OTHER", &pem),
+            Err(PemError::UnexpectedLabel {
+                expected: "OTHER".to_string(),
+                found: "TEST".to_string()
+            })
+        );
+        assert_eq!(decode("TEST", "AAEC"), Err(PemError::MissingHeader));
+        assert_eq!(
+            decode("TEST", "-----BEGIN TEST-----\nAAEC\n-----END OTHER-----"),
+            Err(PemError::MissingFooter)
+        );
+        for body in ["AAE", "AA*C", "AAEC="] {
+            let pem = format!("-----BEGIN TEST-----\n{body}\n-----END TEST-----");
+            assert_eq!(decode("TEST", &pem), Err(PemError::InvalidBase64));
+        }
+
+        let set = ParameterSet::MlKem512;
+        let dk = DecapsulationKey::generate(set);
+        let ek = dk.encapsulation_key();
+        assert_eq!(EncapsulationKey::from_pem(&ek.to_pem()).unwrap(), ek);
+        let private_pem = dk.to_pem(PrivateKeyFormat::Seed).unwrap();
+        assert_eq!(
+            DecapsulationKey::from_pem(&private_pem).unwrap().expanded(),
+            dk.expanded()
+        );
+        assert!(matches!(
+            EncapsulationKey::from_pem(&private_pem),
+            Err(PemError::UnexpectedLabel { .. })
+        ));
+
+        let (k, c) = ek.encaps();
+        let c_pem = ciphertext_to_pem(&c);
+        assert_eq!(dk.decaps(&ciphertext_from_pem(set, &c_pem).unwrap()), k);
+        assert_eq!(
+            ciphertext_from_pem(ParameterSet::MlKem768, &c_pem),
+            Err(PemError::InvalidCiphertextLength(c.len()))
+        );
+    }
+}
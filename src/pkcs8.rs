@@ -0,0 +1,253 @@
+use core::fmt;
+
+use subtle::ConstantTimeEq;
+
+use crate::der::{self, DerError, Reader};
+use crate::kyber::kem_scheme::ParameterSet;
+use crate::kyber::keys::{DecapsulationKey, EncapsulationKey, KeyError};
+
+/// DER content of the OID prefix 2.16.840.1.101.3.4.4 (NIST KEM algorithms)
+const KEM_OID_PREFIX: [u8; 8] = [0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x04];
+
+/// Context-specific tags of OneAsymmetricKey (RFC 5958) and of ML-KEM-PrivateKey
+const TAG_SEED: u8 = 0x80;
+const TAG_ATTRIBUTES: u8 = 0xa0;
+const TAG_PUBLIC_KEY: u8 = 0x81;
+
+/// Errors raised while decoding SubjectPublicKeyInfo or PKCS#8 structures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pkcs8Error {
+    Der(DerError),
+    /// The algorithm identifier is not one of id-alg-ml-kem-512/768/1024
+    UnknownAlgorithm,
+    /// The OneAsymmetricKey version is neither v1 nor v2
+    UnsupportedVersion(u8),
+    /// The private key is not one of the seed, expandedKey or both forms
+    InvalidPrivateKey,
+    /// The optional publicKey field does not match the private key
+    PublicKeyMismatch,
+    Key(KeyError),
+}
+
+impl fmt::Display for Pkcs8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pkcs8Error::Der(err) => write!(f, "{err}"),
+            Pkcs8Error::UnknownAlgorithm => write!(f, "unknown algorithm identifier"),
+            Pkcs8Error::UnsupportedVersion(v) => write!(f, "unsupported PKCS#8 version {v}"),
+            Pkcs8Error::InvalidPrivateKey => write!(f, "invalid ML-KEM private key encoding"),
+            Pkcs8Error::PublicKeyMismatch => write!(f, "public key does not match private key"),
+            Pkcs8Error::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Pkcs8Error {}
+
+impl From<DerError> for Pkcs8Error {
+    fn from(err: DerError) -> Self {
+        Pkcs8Error::Der(err)
+    }
+}
+
+impl From<KeyError> for Pkcs8Error {
+    fn from(err: KeyError) -> Self {
+        Pkcs8Error::Key(err)
+    }
+}
+
+/// Encodings of the ML-KEM-PrivateKey CHOICE (IETF LAMPS ML-KEM certificate profile)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivateKeyFormat {
+    /// seed [0] IMPLICIT OCTET STRING (SIZE (64))
+    Seed,
+    /// expandedKey OCTET STRING
+    ExpandedKey,
+    /// both SEQUENCE { seed OCTET STRING, expandedKey OCTET STRING }
+    Both,
+}
+
+impl ParameterSet {
+    /// DER content of the OID id-alg-ml-kem-512/768/1024
+    pub fn oid(&self) -> [u8; 9] {
+        let mut oid = [0u8; 9];
+        oid[..8].copy_from_slice(&KEM_OID_PREFIX);
+        oid[8] = match self {
+            ParameterSet::MlKem512 => 1,
+            ParameterSet::MlKem768 => 2,
+            ParameterSet::MlKem1024 => 3,
+        };
+        oid
+    }
+
+    pub fn from_oid(oid: &[u8]) -> Option<Self> {
+        ParameterSet::ALL
+            .into_iter()
+            .find(|set| set.oid().as_slice() == oid)
+    }
+}
+
+/// AlgorithmIdentifier ::= SEQUENCE { algorithm OID }, parameters absent
+fn algorithm_identifier(set: ParameterSet) -> Vec<u8> {
+    der::tlv(der::TAG_SEQUENCE, &der::tlv(der::TAG_OID, &set.oid()))
+}
+
+fn read_algorithm_identifier(reader: &mut Reader) -> Result<ParameterSet, Pkcs8Error> {
+    let mut alg = Reader::new(reader.read(der::TAG_SEQUENCE)?);
+    let oid = alg.read(der::TAG_OID)?;
+    let set = ParameterSet::from_oid(oid).ok_or(Pkcs8Error::UnknownAlgorithm)?;
+    alg.finish()?;
+    Ok(set)
+}
+
+impl EncapsulationKey {
+    /// SubjectPublicKeyInfo ::= SEQUENCE { algorithm, subjectPublicKey BIT STRING }
+    pub fn to_public_key_der(&self) -> Vec<u8> {
+        let mut bit_string = vec![0u8];
+        bit_string.extend_from_slice(self.as_bytes());
+
+        let mut content = algorithm_identifier(self.parameter_set());
+        der::write_tlv(&mut content, der::TAG_BIT_STRING, &bit_string);
+        der::tlv(der::TAG_SEQUENCE, &content)
+    }
+
+    pub fn from_public_key_der(bytes: &[u8]) -> Result<Self, Pkcs8Error> {
+        let mut outer = Reader::new(bytes);
+        let mut spki = Reader::new(outer.read(der::TAG_SEQUENCE)?);
+        outer.finish()?;
+
+        let set = read_algorithm_identifier(&mut spki)?;
+        let ek = spki.read_bit_string()?;
+        spki.finish()?;
+        Ok(EncapsulationKey::from_bytes(set, ek)?)
+    }
+}
+
+impl DecapsulationKey {
+    /// OneAsymmetricKey (RFC 5958) v1 with the given ML-KEM-PrivateKey form.
+    /// The seed and both forms require the seed to be known.
+    pub fn to_pkcs8_der(&self, format: PrivateKeyFormat) -> Result<Vec<u8>, KeyError> {
+        let private_key = match format {
+            PrivateKeyFormat::Seed => der::tlv(TAG_SEED, self.seed().ok_or(KeyError::MissingSeed)?),
+            PrivateKeyFormat::ExpandedKey => der::tlv(der::TAG_OCTET_STRING, self.expanded()),
+            PrivateKeyFormat::Both => {
+                let seed = self.seed().ok_or(KeyError::MissingSeed)?;
+                let mut both = der::tlv(der::TAG_OCTET_STRING, seed);
+                der::write_tlv(&mut both, der::TAG_OCTET_STRING, self.expanded());
+                der::tlv(der::TAG_SEQUENCE, &both)
+            }
+        };
+
+        let mut content = der::tlv(der::TAG_INTEGER, &[0]);
+        content.extend_from_slice(&algorithm_identifier(self.parameter_set()));
+        der::write_tlv(&mut content, der::TAG_OCTET_STRING, &private_key);
+        Ok(der::tlv(der::TAG_SEQUENCE, &content))
+    }
+
+    /// Parses a OneAsymmetricKey holding any of the three ML-KEM-PrivateKey forms.
+    /// When both the seed and the expanded key are present, or when the optional
+    /// publicKey is present, they must describe the same key.
+    pub fn from_pkcs8_der(bytes: &[u8]) -> Result<Self, Pkcs8Error> {
+        let mut outer = Reader::new(bytes);
+        let mut info = Reader::new(outer.read(der::TAG_SEQUENCE)?);
+        outer.finish()?;
+
+        let version = info.read_small_uint()?;
+        if version > 1 {
+            return Err(Pkcs8Error::UnsupportedVersion(version));
+        }
+        let set = read_algorithm_identifier(&mut info)?;
+        let key = parse_private_key(set, info.read(der::TAG_OCTET_STRING)?)?;
+
+        if info.peek_tag() == Some(TAG_ATTRIBUTES) {
+            info.read(TAG_ATTRIBUTES)?;
+        }
+        if version == 1 && info.peek_tag() == Some(TAG_PUBLIC_KEY) {
+            let public_key = match info.read(TAG_PUBLIC_KEY)? {
+                [0, ek @ ..] => ek,
+                _ => return Err(DerError::InvalidValue.into()),
+            };
+            if !bool::from(public_key.ct_eq(key.encapsulation_key().as_bytes())) {
+                return Err(Pkcs8Error::PublicKeyMismatch);
+            }
+        }
+        info.finish()?;
+        Ok(key)
+    }
+}
+
+fn parse_private_key(set: ParameterSet, bytes: &[u8]) -> Result<DecapsulationKey, Pkcs8Error> {
+    let mut reader = Reader::new(bytes);
+    let key = match reader.read_any()? {
+        (TAG_SEED, seed) => DecapsulationKey::from_seed(set, &read_seed(seed)?),
+        (der::TAG_OCTET_STRING, expanded) => DecapsulationKey::from_expanded(set, expanded)?,
+        (der::TAG_SEQUENCE, both) => {
+            let mut both = Reader::new(both);
+            let seed = read_seed(both.read(der::TAG_OCTET_STRING)?)?;
+            let expanded = both.read(der::TAG_OCTET_STRING)?;
+            both.finish()?;
+            DecapsulationKey::from_seed_and_expanded(set, &seed, expanded)?
+        }
+        _ => return Err(Pkcs8Error::InvalidPrivateKey),
+    };
+    reader.finish()?;
+    Ok(key)
+}
+
+fn read_seed(bytes: &[u8]) -> Result<[u8; 64], Pkcs8Error> {
+    bytes.try_into().map_err(|_| Pkcs8Error::InvalidPrivateKey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        for set in ParameterSet::ALL {
+            assert_eq!(ParameterSet::from_oid(&set.oid()), Some(set));
+
+            let dk = DecapsulationKey::generate(set);
+            let ek = dk.encapsulation_key();
+            let spki = ek.to_public_key_der();
+            assert_eq!(EncapsulationKey::from_public_key_der(&spki).unwrap(), ek);
+
+            for format in [
+                PrivateKeyFormat::Seed,
+                PrivateKeyFormat::ExpandedKey,
+                PrivateKeyFormat::Both,
+            ] {
+                let encoded = dk.to_pkcs8_der(format).unwrap();
+                let decoded = DecapsulationKey::from_pkcs8_der(&encoded).unwrap();
+                assert_eq!(decoded.expanded(), dk.expanded());
+                assert_eq!(
+                    decoded.seed().is_some(),
+                    format != PrivateKeyFormat::ExpandedKey
+                );
+                assert_eq!(
+                    decoded.to_pkcs8_der(PrivateKeyFormat::ExpandedKey).unwrap(),
+                    dk.to_pkcs8_der(PrivateKeyFormat::ExpandedKey).unwrap()
+                );
+            }
+
+            let expanded_only = DecapsulationKey::from_expanded(set, dk.expanded()).unwrap();
+            assert_eq!(
+                expanded_only.to_pkcs8_der(PrivateKeyFormat::Seed),
+                Err(KeyError::MissingSeed)
+            );
+
+            let mut unknown = spki.clone();
+            unknown[16] = 4;
+            assert_eq!(
+                EncapsulationKey::from_public_key_der(&unknown),
+                Err(Pkcs8Error::UnknownAlgorithm)
+            );
+            let mut trailing = spki.clone();
+            trailing.push(0);
+            assert_eq!(
+                EncapsulationKey::from_public_key_der(&trailing),
+                Err(Pkcs8Error::Der(DerError::TrailingData))
+            );
+        }
+    }
+}
@@ -1,23 +1,26 @@
 use core::fmt;
-use sha3::{
-    Shake128,
-    digest::{ExtendableOutput, Update, XofReader},
-};
+use sha3::digest::XofReader;
 use std::{
     marker::PhantomData,
     ops::{Add, AddAssign, Index, IndexMut, Mul, Sub},
 };
 
-use crate::{constants::PolyParams, conversion::bytes_to_bits};
+use crate::{
+    constants::PolyParams,
+    conversion::bytes_to_bits,
+    field::Zq,
+    hash::{HashSuite, Sha3Suite, XofX4},
+    keccak::SHAKE128_RATE,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Polynomial<P: PolyParams> {
-    pub coeffs: Vec<i64>,
+    pub coeffs: Vec<Zq<P>>,
     _marker: std::marker::PhantomData<P>,
 }
 
-impl<P: PolyParams> From<Vec<i64>> for Polynomial<P> {
-    fn from(value: Vec<i64>) -> Self {
+impl<P: PolyParams> From<Vec<Zq<P>>> for Polynomial<P> {
+    fn from(value: Vec<Zq<P>>) -> Self {
         Polynomial::<P> {
             coeffs: value,
             _marker: PhantomData::<P>,
@@ -25,6 +28,12 @@ impl<P: PolyParams> From<Vec<i64>> for Polynomial<P> {
     }
 }
 
+impl<P: PolyParams> From<Vec<i64>> for Polynomial<P> {
+    fn from(value: Vec<i64>) -> Self {
+        Polynomial::<P>::from(value.into_iter().map(Zq::<P>::new).collect::<Vec<_>>())
+    }
+}
+
 impl<P: PolyParams> From<i64> for Polynomial<P> {
     fn from(value: i64) -> Self {
         let mut coeffs = vec![0i64; P::N];
@@ -34,13 +43,18 @@ impl<P: PolyParams> From<i64> for Polynomial<P> {
 }
 
 impl<P: PolyParams> Polynomial<P> {
-    pub fn new(coeffs: Vec<i64>) -> Self {
+    pub fn new(coeffs: Vec<Zq<P>>) -> Self {
         if coeffs.len() != P::N {
             panic!("The polynomial must have exactly {} coefficients", P::N);
         }
         Polynomial::<P>::from(coeffs)
     }
 
+    /// Canonical representatives in [0, Q) of the coefficients
+    pub fn values(&self) -> Vec<i64> {
+        self.coeffs.iter().map(Zq::value).collect()
+    }
+
     /// Algorithm 8 (FIPS 203) : SimplePolyCBD_eta(B)
     ///
     /// Input : B in B^(64*eta)
@@ -56,7 +70,7 @@ impl<P: PolyParams> Polynomial<P> {
         };
 
         let b_bits = bytes_to_bits(b);
-        let mut coeffs = vec![0i64; P::N];
+        let mut coeffs = vec![Zq::<P>::zero(); P::N];
         for i in 0..P::N {
             let mut x = 0i64;
             for j in 0..eta {
@@ -66,7 +80,7 @@ impl<P: PolyParams> Polynomial<P> {
             for j in 0..eta {
                 y += b_bits[2 * i * eta + eta + j] as i64;
             }
-            coeffs[i] = (x - y).rem_euclid(P::Q);
+            coeffs[i] = Zq::<P>::new(x - y);
         }
         Polynomial::<P>::from(coeffs)
     }
@@ -84,12 +98,12 @@ impl<P: PolyParams> Polynomial<P> {
 
         while len > 1 {
             for start in (0..P::N).step_by(2 * len) {
-                let zeta = zetas[i];
+                let zeta = Zq::<P>::new(zetas[i]);
                 i += 1;
                 for j in start..(start + len) {
-                    let t = (zeta * coeffs[j + len]).rem_euclid(P::Q);
-                    coeffs[j + len] = (coeffs[j] - t).rem_euclid(P::Q);
-                    coeffs[j] = (coeffs[j] + t).rem_euclid(P::Q);
+                    let t = zeta * coeffs[j + len];
+                    coeffs[j + len] = coeffs[j] - t;
+                    coeffs[j] += t;
                 }
             }
             len /= 2;
@@ -113,19 +127,20 @@ impl<P: PolyParams> Polynomial<P> {
 
         while len <= 128 {
             for start in (0..P::N).step_by(2 * len) {
-                let zeta = zetas[i];
+                let zeta = Zq::<P>::new(zetas[i]);
                 i -= 1;
                 for j in start..(start + len) {
                     let t = coeffs[j];
-                    coeffs[j] = (t + coeffs[j + len]).rem_euclid(P::Q);
-                    coeffs[j + len] = (zeta * (coeffs[j + len] - t)).rem_euclid(P::Q);
+                    coeffs[j] = t + coeffs[j + len];
+                    coeffs[j + len] = zeta * (coeffs[j + len] - t);
                 }
             }
             len *= 2;
         }
 
+        let n_inv = Zq::<P>::new(P::N_INV);
         for coeff in coeffs.iter_mut() {
-            *coeff = (*coeff * P::N_INV).rem_euclid(P::Q);
+            *coeff *= n_inv;
         }
 
         Polynomial {
@@ -142,7 +157,7 @@ impl<P: PolyParams> Add for &Polynomial<P> {
             .coeffs
             .iter()
             .zip(rhs.coeffs.iter())
-            .map(|(a, b)| (a + b).rem_euclid(P::Q))
+            .map(|(&a, &b)| a + b)
             .collect();
         Polynomial::<P> {
             coeffs: new_coeffs,
@@ -154,7 +169,7 @@ impl<P: PolyParams> Add for &Polynomial<P> {
 impl<P: PolyParams> AddAssign<&Polynomial<P>> for Polynomial<P> {
     fn add_assign(&mut self, rhs: &Polynomial<P>) {
         for (a, b) in self.coeffs.iter_mut().zip(rhs.coeffs.iter()) {
-            *a = (*a + b).rem_euclid(P::Q);
+            *a += *b;
         }
     }
 }
@@ -166,7 +181,7 @@ impl<P: PolyParams> Sub for &Polynomial<P> {
             .coeffs
             .iter()
             .zip(rhs.coeffs.iter())
-            .map(|(a, b)| (a - b).rem_euclid(P::Q))
+            .map(|(&a, &b)| a - b)
             .collect();
         Polynomial::<P> {
             coeffs: new_coeffs,
@@ -178,7 +193,7 @@ impl<P: PolyParams> Sub for &Polynomial<P> {
 impl<P: PolyParams> Mul for &Polynomial<P> {
     type Output = Polynomial<P>;
     fn mul(self, rhs: Self) -> Self::Output {
-        let mut new_coeffs = vec![0i64; P::N];
+        let mut new_coeffs = vec![Zq::<P>::zero(); P::N];
 
         for i in 0..P::N {
             for j in 0..P::N {
@@ -186,10 +201,9 @@ impl<P: PolyParams> Mul for &Polynomial<P> {
 
                 let k = i + j;
                 if k < P::N {
-                    new_coeffs[k] = (new_coeffs[k] + pdt).rem_euclid(P::Q);
+                    new_coeffs[k] += pdt;
                 } else {
-                    let k_prime = k - P::N;
-                    new_coeffs[k_prime] = (new_coeffs[k_prime] - pdt).rem_euclid(P::Q);
+                    new_coeffs[k - P::N] -= pdt;
                 }
             }
         }
@@ -204,7 +218,7 @@ impl<P: PolyParams> fmt::Display for Polynomial<P> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut terms = Vec::new();
         for i in (0..P::N).rev() {
-            let c = self.coeffs[i];
+            let c = self.coeffs[i].value();
             if c == 0 {
                 continue;
             }
@@ -236,7 +250,7 @@ impl<P: PolyParams> fmt::Display for Polynomial<P> {
 }
 
 impl<P: PolyParams> Index<usize> for Polynomial<P> {
-    type Output = i64;
+    type Output = Zq<P>;
     fn index(&self, index: usize) -> &Self::Output {
         &self.coeffs[index]
     }
@@ -250,12 +264,12 @@ impl<P: PolyParams> IndexMut<usize> for Polynomial<P> {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PolynomialNTT<P: PolyParams> {
-    pub coeffs: Vec<i64>,
+    pub coeffs: Vec<Zq<P>>,
     _marker: std::marker::PhantomData<P>,
 }
 
-impl<P: PolyParams> From<Vec<i64>> for PolynomialNTT<P> {
-    fn from(value: Vec<i64>) -> Self {
+impl<P: PolyParams> From<Vec<Zq<P>>> for PolynomialNTT<P> {
+    fn from(value: Vec<Zq<P>>) -> Self {
         PolynomialNTT::<P> {
             coeffs: value,
             _marker: PhantomData::<P>,
@@ -263,32 +277,133 @@ impl<P: PolyParams> From<Vec<i64>> for PolynomialNTT<P> {
     }
 }
 
+impl<P: PolyParams> From<Vec<i64>> for PolynomialNTT<P> {
+    fn from(value: Vec<i64>) -> Self {
+        PolynomialNTT::<P>::from(value.into_iter().map(Zq::<P>::new).collect::<Vec<_>>())
+    }
+}
+
 impl<P: PolyParams> PolynomialNTT<P> {
+    /// Canonical representatives in [0, Q) of the coefficients
+    pub fn values(&self) -> Vec<i64> {
+        self.coeffs.iter().map(Zq::value).collect()
+    }
+
     /// Algorithm 7 : SampleNTT(B)
     ///
     /// Input : B in B^34
     /// Output : a in PolynomialNTT
     pub fn sample_ntt(bytes: &[u8; 34]) -> Self {
-        let mut a = vec![0i64; P::N];
-        let mut hasher = Shake128::default();
-        hasher.update(bytes);
-        let mut reader = hasher.finalize_xof();
-        let mut j = 0;
-        while j < P::N {
-            let mut c = [0u8; 3];
-            reader.read(&mut c);
-            let d1 = (c[0] as i64) + (P::N as i64) * (c[1] as i64 % 16);
-            let d2 = (c[1] as i64 / 16) + 16 * (c[2] as i64);
-            if d1 < P::Q {
-                a[j] = d1;
-                j += 1;
-            }
-            if (d2 < P::Q) && (j < P::N) {
-                a[j] = d2;
-                j += 1;
+        PolynomialNTT::<P>::sample_ntt_with::<Sha3Suite>(bytes)
+    }
+
+    /// Algorithm 7 : SampleNTT(B), using the XOF of the given hash suite
+    ///
+    /// Input : B in B^34
+    /// Output : a in PolynomialNTT
+    pub fn sample_ntt_with<H: HashSuite>(bytes: &[u8; 34]) -> Self {
+        PolynomialNTT::<P>::sample_ntt_from_reader(H::xof(bytes))
+    }
+
+    /// Algorithm 7 : SampleNTT, reading the bytes from an already absorbed XOF
+    ///
+    /// Input : XOF reader
+    /// Output : a in PolynomialNTT
+    pub fn sample_ntt_from_reader<R: XofReader>(reader: R) -> Self {
+        PolynomialNTT::<P>::from(
+            NttSampler::<P, R>::new(reader)
+                .take(P::N)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Algorithm 7 : SampleNTT(B), run on four inputs at once with the batched XOF
+    /// of the given hash suite. Each output equals `sample_ntt_with::<H>` of its input.
+    ///
+    /// Input : B_0, B_1, B_2, B_3 in B^34
+    /// Output : a_0, a_1, a_2, a_3 in PolynomialNTT
+    pub fn sample_ntt_x4<H: HashSuite>(inputs: [&[u8; 34]; 4]) -> [Self; 4] {
+        let mut coeffs: [Vec<Zq<P>>; 4] = Default::default();
+        let mut xof = H::xof_x4(inputs);
+        let mut blocks = [[0u8; SHAKE128_RATE]; 4];
+        let mut parsed = [Zq::<P>::zero(); MAX_COEFFS_PER_BLOCK];
+
+        while coeffs.iter().any(|a| a.len() < P::N) {
+            xof.squeeze_blocks(&mut blocks);
+            for (a, block) in coeffs.iter_mut().zip(blocks.iter()) {
+                let count = parse_block(block, &mut parsed).min(P::N - a.len());
+                a.extend_from_slice(&parsed[..count]);
             }
         }
-        PolynomialNTT::<P>::from(a)
+        coeffs.map(PolynomialNTT::<P>::from)
+    }
+}
+
+/// At most two coefficients are accepted for each group of 3 bytes of a block
+const MAX_COEFFS_PER_BLOCK: usize = 2 * SHAKE128_RATE / 3;
+
+/// Rejection sampling of lines 4-15 of Algorithm 7 on a whole XOF block.
+/// Writes the accepted coefficients in order and returns how many there are.
+fn parse_block<P: PolyParams>(
+    block: &[u8; SHAKE128_RATE],
+    out: &mut [Zq<P>; MAX_COEFFS_PER_BLOCK],
+) -> usize {
+    let mut count = 0;
+    for c in block.chunks_exact(3) {
+        let d1 = (c[0] as i64) + 256 * (c[1] as i64 % 16);
+        let d2 = (c[1] as i64 / 16) + 16 * (c[2] as i64);
+        if d1 < P::Q {
+            out[count] = Zq::<P>::new(d1);
+            count += 1;
+        }
+        if d2 < P::Q {
+            out[count] = Zq::<P>::new(d2);
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Incremental SampleNTT (Algorithm 7 FIPS 203) over any XOF reader
+///
+/// The reader is squeezed one rate block (168 bytes) at a time and each block is parsed
+/// in bulk. The iterator is infinite: taking the first N items gives the same
+/// coefficients as `sample_ntt` when the reader is SHAKE128(B).
+pub struct NttSampler<P: PolyParams, R: XofReader> {
+    reader: R,
+    parsed: [Zq<P>; MAX_COEFFS_PER_BLOCK],
+    len: usize,
+    pos: usize,
+}
+
+impl<P: PolyParams, R: XofReader> NttSampler<P, R> {
+    pub fn new(reader: R) -> Self {
+        NttSampler::<P, R> {
+            reader,
+            parsed: [Zq::<P>::zero(); MAX_COEFFS_PER_BLOCK],
+            len: 0,
+            pos: 0,
+        }
+    }
+
+    /// Gives back the underlying reader, positioned after the last squeezed block
+    pub fn into_reader(self) -> R {
+        self.reader
+    }
+}
+
+impl<P: PolyParams, R: XofReader> Iterator for NttSampler<P, R> {
+    type Item = Zq<P>;
+
+    fn next(&mut self) -> Option<Zq<P>> {
+        while self.pos == self.len {
+            let mut block = [0u8; SHAKE128_RATE];
+            self.reader.read(&mut block);
+            self.len = parse_block(&block, &mut self.parsed);
+            self.pos = 0;
+        }
+        self.pos += 1;
+        Some(self.parsed[self.pos - 1])
     }
 }
 
@@ -299,7 +414,7 @@ impl<P: PolyParams> Add for &PolynomialNTT<P> {
             .coeffs
             .iter()
             .zip(rhs.coeffs.iter())
-            .map(|(a, b)| (a + b).rem_euclid(P::Q))
+            .map(|(&a, &b)| a + b)
             .collect();
         PolynomialNTT::<P> {
             coeffs: new_coeffs,
@@ -311,7 +426,7 @@ impl<P: PolyParams> Add for &PolynomialNTT<P> {
 impl<P: PolyParams> AddAssign<&PolynomialNTT<P>> for PolynomialNTT<P> {
     fn add_assign(&mut self, rhs: &PolynomialNTT<P>) {
         for (a, b) in self.coeffs.iter_mut().zip(rhs.coeffs.iter()) {
-            *a = (*a + b).rem_euclid(P::Q);
+            *a += *b;
         }
     }
 }
@@ -319,16 +434,14 @@ impl<P: PolyParams> AddAssign<&PolynomialNTT<P>> for PolynomialNTT<P> {
 impl<P: PolyParams> Mul for &PolynomialNTT<P> {
     type Output = PolynomialNTT<P>;
     fn mul(self, rhs: Self) -> Self::Output {
-        let mut new_coeffs = vec![0i64; P::N];
+        let mut new_coeffs = vec![Zq::<P>::zero(); P::N];
 
         let zetas = P::zetas();
         for i in 0..128 {
-            let gamma = ((zetas[i] * zetas[i]).rem_euclid(P::Q) * P::ZETA).rem_euclid(P::Q);
-            new_coeffs[2 * i] = (self[2 * i] * rhs[2 * i]
-                + (self[2 * i + 1] * rhs[2 * i + 1]).rem_euclid(P::Q) * gamma)
-                .rem_euclid(P::Q);
-            new_coeffs[2 * i + 1] =
-                (self[2 * i] * rhs[2 * i + 1] + self[2 * i + 1] * rhs[2 * i]).rem_euclid(P::Q);
+            let zeta = Zq::<P>::new(zetas[i]);
+            let gamma = zeta * zeta * Zq::<P>::new(P::ZETA);
+            new_coeffs[2 * i] = self[2 * i] * rhs[2 * i] + self[2 * i + 1] * rhs[2 * i + 1] * gamma;
+            new_coeffs[2 * i + 1] = self[2 * i] * rhs[2 * i + 1] + self[2 * i + 1] * rhs[2 * i];
         }
         PolynomialNTT::<P> {
             coeffs: new_coeffs,
@@ -338,7 +451,7 @@ impl<P: PolyParams> Mul for &PolynomialNTT<P> {
 }
 
 impl<P: PolyParams> Index<usize> for PolynomialNTT<P> {
-    type Output = i64;
+    type Output = Zq<P>;
     fn index(&self, index: usize) -> &Self::Output {
         &self.coeffs[index]
     }
@@ -353,14 +466,17 @@ impl<P: PolyParams> IndexMut<usize> for PolynomialNTT<P> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hash::SerialXofX4;
     use crate::{constants::KyberParams, kyber::KyberPoly};
+    use sha3::digest::{ExtendableOutput, Update};
+    use sha3::{Shake128, Shake256};
 
     #[test]
     fn basics() {
         let mut f = KyberPoly::from(0i64);
         let mut g = KyberPoly::from(1i64);
-        (f[255], f[2]) = (6i64, 1i64);
-        (g[19], g[3]) = (43i64, 92i64);
+        (f[255], f[2]) = (Zq::new(6), Zq::new(1));
+        (g[19], g[3]) = (Zq::new(43), Zq::new(92));
         println!("Polynomial f + g: {}", &f + &g);
         println!("Polynomial f * g: {}", &f * &g);
 
@@ -379,5 +495,91 @@ mod tests {
             KyberPoly::from_ntt(&(&p1.to_ntt() * &p2.to_ntt())).coeffs,
             (&p1 * &p2).coeffs
         );
+
+        let inputs: Vec<[u8; 34]> = (0..4u8).map(|i| [i * 41 + 3; 34]).collect();
+        let batched = PolynomialNTT::<KyberParams>::sample_ntt_x4::<Sha3Suite>([
+            &inputs[0], &inputs[1], &inputs[2], &inputs[3],
+        ]);
+        for (input, a) in inputs.iter().zip(batched.iter()) {
+            assert_eq!(
+                a.coeffs,
+                PolynomialNTT::<KyberParams>::sample_ntt(input).coeffs
+            );
+        }
+
+        let bytes = b"Salut de la part de moi meme le ka";
+        let mut hasher = Shake128::default();
+        hasher.update(bytes);
+        let expected = reference_sample(hasher.finalize_xof(), 3 * KyberParams::N);
+        let sampled: Vec<i64> = NttSampler::<KyberParams, _>::new(Sha3Suite::xof(bytes))
+            .take(3 * KyberParams::N)
+            .map(|c| c.value())
+            .collect();
+        assert_eq!(sampled, expected);
+        assert_eq!(
+            PolynomialNTT::<KyberParams>::sample_ntt(bytes).values(),
+            expected[..KyberParams::N]
+        );
+
+        let mut hasher = Shake256::default();
+        hasher.update(bytes);
+        let a = PolynomialNTT::<KyberParams>::sample_ntt_from_reader(hasher.finalize_xof());
+        assert_eq!(
+            a.values(),
+            PolynomialNTT::<KyberParams>::sample_ntt_with::<Shake256Suite>(bytes).values()
+        );
+        assert_eq!(
+            a.values(),
+            reference_sample(Shake256Suite::xof(bytes), KyberParams::N)
+        );
+    }
+
+    /// Lines 4-15 of Algorithm 7, three bytes at a time
+    fn reference_sample(mut reader: impl XofReader, n: usize) -> Vec<i64> {
+        let mut out = Vec::new();
+        while out.len() < n {
+            let mut c = [0u8; 3];
+            reader.read(&mut c);
+            let d1 = (c[0] as i64) + 256 * (c[1] as i64 % 16);
+            let d2 = (c[1] as i64 / 16) + 16 * (c[2] as i64);
+            out.extend([d1, d2].into_iter().filter(|&d| d < KyberParams::Q));
+        }
+        out.truncate(n);
+        out
+    }
+
+    /// Sha3Suite with SHAKE256 as the SampleNTT XOF
+    struct Shake256Suite;
+
+    impl HashSuite for Shake256Suite {
+        type Xof = sha3::Shake256Reader;
+
+        fn h(s: &[u8]) -> [u8; 32] {
+            Sha3Suite::h(s)
+        }
+
+        fn g(c: &[u8]) -> ([u8; 32], [u8; 32]) {
+            Sha3Suite::g(c)
+        }
+
+        fn j(s: &[u8]) -> [u8; 32] {
+            Sha3Suite::j(s)
+        }
+
+        fn prf(eta: usize, s: &[u8; 32], b: &[u8; 1]) -> Vec<u8> {
+            Sha3Suite::prf(eta, s, b)
+        }
+
+        fn xof(bytes: &[u8; 34]) -> Self::Xof {
+            let mut hasher = Shake256::default();
+            hasher.update(bytes);
+            hasher.finalize_xof()
+        }
+
+        type XofX4 = SerialXofX4<Self::Xof>;
+
+        fn xof_x4(bytes: [&[u8; 34]; 4]) -> Self::XofX4 {
+            SerialXofX4(bytes.map(Self::xof))
+        }
     }
 }
@@ -0,0 +1,490 @@
+use core::fmt;
+use std::collections::{BTreeMap, VecDeque};
+
+use hkdf::Hkdf;
+use sha2::Sha512;
+
+use crate::hybrid::ecdh::{Curve, EcdhError, EcdhKeyPair};
+use crate::kyber::kem_scheme::ParameterSet;
+use crate::kyber::keys::{DecapsulationKey, EncapsulationKey, KeyError};
+
+/// pqkem of the specification
+pub const KEM_PARAMETER_SET: ParameterSet = ParameterSet::MlKem1024;
+/// Length of the shared key SK
+pub const SESSION_KEY_LEN: usize = 32;
+/// Length of an X25519 public key
+const EC_LEN: usize = 32;
+/// Type byte of EncodeEC for Curve25519 keys
+const EC_TYPE: u8 = 0x05;
+/// Type byte of EncodeKEM for ML-KEM-1024 keys
+const KEM_TYPE: u8 = 0x0a;
+
+/// Errors raised by the key agreement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PqxdhError {
+    /// A prekey signature does not verify under the identity key
+    InvalidSignature,
+    /// The initial message has the wrong length
+    InvalidMessageLength { expected: usize, found: usize },
+    /// The one-time prekey flag of the initial message is neither 0x00 nor 0x01
+    InvalidPrekeyFlag(u8),
+    /// The initial message refers to a prekey that is unknown or already used
+    UnknownPrekey(u32),
+    /// An X25519 key is invalid, or the identity key is not an X25519 key
+    Ecdh(EcdhError),
+    /// The ML-KEM prekey fails the input checks
+    Key(KeyError),
+}
+
+impl fmt::Display for PqxdhError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PqxdhError::InvalidSignature => write!(f, "invalid PQXDH prekey signature"),
+            PqxdhError::InvalidMessageLength { expected, found } => {
+                write!(
+                    f,
+                    "invalid PQXDH message length {found}, expected {expected}"
+                )
+            }
+            PqxdhError::InvalidPrekeyFlag(flag) => {
+                write!(f, "invalid PQXDH one-time prekey flag {flag:#04x}")
+            }
+            PqxdhError::UnknownPrekey(id) => write!(f, "unknown PQXDH prekey {id}"),
+            PqxdhError::Ecdh(err) => write!(f, "{err}"),
+            PqxdhError::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for PqxdhError {}
+
+impl From<EcdhError> for PqxdhError {
+    fn from(err: EcdhError) -> Self {
+        PqxdhError::Ecdh(err)
+    }
+}
+
+impl From<KeyError> for PqxdhError {
+    fn from(err: KeyError) -> Self {
+        PqxdhError::Key(err)
+    }
+}
+
+/// Signature of the prekeys by the identity key, XEdDSA in the specification
+pub trait PrekeySignature {
+    /// Sig(IK, message) with the private identity key
+    fn sign(&self, identity: &EcdhKeyPair, message: &[u8]) -> Vec<u8>;
+
+    /// Verifies Sig(IK, message) with the public identity key
+    fn verify(&self, identity: &[u8], message: &[u8], signature: &[u8]) -> bool;
+}
+
+/// EncodeEC(PK) : type byte || X25519 public key
+pub fn encode_ec(public: &[u8]) -> Vec<u8> {
+    [&[EC_TYPE], public].concat()
+}
+
+/// EncodeKEM(PK) : type byte || ML-KEM-1024 encapsulation key
+pub fn encode_kem(ek: &EncapsulationKey) -> Vec<u8> {
+    [&[KEM_TYPE], ek.as_bytes()].concat()
+}
+
+fn check_identity(identity: &EcdhKeyPair) -> Result<(), PqxdhError> {
+    if identity.curve() != Curve::X25519 {
+        return Err(PqxdhError::Ecdh(EcdhError::InvalidSecret));
+    }
+    Ok(())
+}
+
+/// KDF(KM) = HKDF-SHA-512 with a zero salt, IKM = F || KM where F is 32 0xFF bytes,
+/// and the application info string
+fn kdf(info: &[u8], km: &[&[u8]]) -> [u8; SESSION_KEY_LEN] {
+    let mut ikm = vec![0xffu8; 32];
+    for part in km {
+        ikm.extend_from_slice(part);
+    }
+    let mut sk = [0u8; SESSION_KEY_LEN];
+    Hkdf::<Sha512>::new(Some(&[0u8; 64]), &ikm)
+        .expand(info, &mut sk)
+        .expect("32 bytes is a valid HKDF-SHA-512 output length");
+    sk
+}
+
+/// Output of the key agreement on both sides
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionKeys {
+    /// Shared key SK
+    pub sk: [u8; SESSION_KEY_LEN],
+    /// Associated data AD = EncodeEC(IK_A) || EncodeEC(IK_B) of the first AEAD message
+    pub ad: Vec<u8>,
+}
+
+impl fmt::Debug for SessionKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionKeys(.., ad: {})", hex::encode(&self.ad))
+    }
+}
+
+/// Curve prekey with its identifier, signed in the case of the signed prekey
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcPrekey {
+    pub id: u32,
+    pub public: Vec<u8>,
+    pub signature: Option<Vec<u8>>,
+}
+
+/// Signed ML-KEM-1024 prekey, one-time or last-resort
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KemPrekey {
+    pub id: u32,
+    pub ek: EncapsulationKey,
+    pub signature: Vec<u8>,
+}
+
+/// Prekey bundle handed out by the server : IK_B, SPK_B and its signature, one
+/// signed ML-KEM prekey (one-time when available, last-resort otherwise), and an
+/// optional one-time curve prekey OPK_B
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrekeyBundle {
+    pub identity_key: Vec<u8>,
+    pub signed_prekey: EcPrekey,
+    pub kem_prekey: KemPrekey,
+    pub one_time_prekey: Option<EcPrekey>,
+}
+
+impl PrekeyBundle {
+    /// Checks the signatures of SPK_B and of the ML-KEM prekey
+    pub fn verify(&self, verifier: &impl PrekeySignature) -> Result<(), PqxdhError> {
+        let spk_signature = self.signed_prekey.signature.as_deref().unwrap_or_default();
+        if !verifier.verify(
+            &self.identity_key,
+            &encode_ec(&self.signed_prekey.public),
+            spk_signature,
+        ) || !verifier.verify(
+            &self.identity_key,
+            &encode_kem(&self.kem_prekey.ek),
+            &self.kem_prekey.signature,
+        ) {
+            return Err(PqxdhError::InvalidSignature);
+        }
+        Ok(())
+    }
+}
+
+/// Initial message from Alice : IK_A, EK_A, the identifiers of the prekeys used and
+/// the ML-KEM ciphertext CT.
+///
+/// Encoding : IK_A (32) || EK_A (32) || SPK id || PQPK id (32-bit big-endian) ||
+/// 0x00, or 0x01 || OPK id || CT (1568).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitialMessage {
+    pub identity_key: Vec<u8>,
+    pub ephemeral_key: Vec<u8>,
+    pub signed_prekey_id: u32,
+    pub kem_prekey_id: u32,
+    pub one_time_prekey_id: Option<u32>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl InitialMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = [self.identity_key.as_slice(), &self.ephemeral_key].concat();
+        bytes.extend_from_slice(&self.signed_prekey_id.to_be_bytes());
+        bytes.extend_from_slice(&self.kem_prekey_id.to_be_bytes());
+        match self.one_time_prekey_id {
+            Some(id) => {
+                bytes.push(0x01);
+                bytes.extend_from_slice(&id.to_be_bytes());
+            }
+            None => bytes.push(0x00),
+        }
+        bytes.extend_from_slice(&self.ciphertext);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PqxdhError> {
+        let header_len = 2 * EC_LEN + 8 + 1;
+        let short_len = header_len + KEM_PARAMETER_SET.ciphertext_len();
+        let flag = bytes.get(header_len - 1).copied();
+        let has_one_time = flag == Some(0x01);
+        let expected = short_len + if has_one_time { 4 } else { 0 };
+        // A bad flag is only reported in a message of a valid length
+        if let Some(flag) = flag.filter(|&flag| flag > 0x01)
+            && [short_len, short_len + 4].contains(&bytes.len())
+        {
+            return Err(PqxdhError::InvalidPrekeyFlag(flag));
+        }
+        if bytes.len() != expected {
+            return Err(PqxdhError::InvalidMessageLength {
+                expected,
+                found: bytes.len(),
+            });
+        }
+        let id = |offset: usize| u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        Ok(InitialMessage {
+            identity_key: bytes[..EC_LEN].to_vec(),
+            ephemeral_key: bytes[EC_LEN..2 * EC_LEN].to_vec(),
+            signed_prekey_id: id(2 * EC_LEN),
+            kem_prekey_id: id(2 * EC_LEN + 4),
+            one_time_prekey_id: has_one_time.then(|| id(header_len)),
+            ciphertext: bytes[expected - KEM_PARAMETER_SET.ciphertext_len()..].to_vec(),
+        })
+    }
+}
+
+/// Alice's side : verifies the bundle, then computes SK and the initial message
+///
+/// DH1 = DH(IK_A, SPK_B), DH2 = DH(EK_A, IK_B), DH3 = DH(EK_A, SPK_B),
+/// DH4 = DH(EK_A, OPK_B) when the bundle has a one-time prekey, (CT, SS) =
+/// ML-KEM.Encaps(PQPK_B), and SK = KDF(DH1 || DH2 || DH3 [|| DH4] || SS).
+pub fn initiate(
+    info: &[u8],
+    identity: &EcdhKeyPair,
+    bundle: &PrekeyBundle,
+    verifier: &impl PrekeySignature,
+) -> Result<(InitialMessage, SessionKeys), PqxdhError> {
+    check_identity(identity)?;
+    bundle.verify(verifier)?;
+    if bundle.kem_prekey.ek.parameter_set() != KEM_PARAMETER_SET {
+        return Err(PqxdhError::Key(KeyError::InvalidLength {
+            expected: KEM_PARAMETER_SET.ek_len(),
+            found: bundle.kem_prekey.ek.as_bytes().len(),
+        }));
+    }
+
+    let ephemeral = EcdhKeyPair::generate(Curve::X25519);
+    let dh1 = identity.diffie_hellman(&bundle.signed_prekey.public)?;
+    let dh2 = ephemeral.diffie_hellman(&bundle.identity_key)?;
+    let dh3 = ephemeral.diffie_hellman(&bundle.signed_prekey.public)?;
+    let dh4 = match &bundle.one_time_prekey {
+        Some(opk) => ephemeral.diffie_hellman(&opk.public)?,
+        None => Vec::new(),
+    };
+    let (ss, ciphertext) = bundle.kem_prekey.ek.encaps();
+
+    let keys = SessionKeys {
+        sk: kdf(info, &[&dh1, &dh2, &dh3, &dh4, &ss]),
+        ad: [
+            encode_ec(identity.public_bytes()),
+            encode_ec(&bundle.identity_key),
+        ]
+        .concat(),
+    };
+    let message = InitialMessage {
+        identity_key: identity.public_bytes().to_vec(),
+        ephemeral_key: ephemeral.public_bytes().to_vec(),
+        signed_prekey_id: bundle.signed_prekey.id,
+        kem_prekey_id: bundle.kem_prekey.id,
+        one_time_prekey_id: bundle.one_time_prekey.as_ref().map(|opk| opk.id),
+        ciphertext,
+    };
+    Ok((message, keys))
+}
+
+/// Bob's private prekeys, and the queues of one-time prekeys not yet handed out.
+///
+/// One-time prekeys are deleted once used; the signed prekey and the last-resort
+/// ML-KEM prekey are kept until they are rotated.
+pub struct PrekeyStore {
+    identity: EcdhKeyPair,
+    signed_prekey: (u32, EcdhKeyPair, Vec<u8>),
+    last_resort: KemPrekey,
+    last_resort_dk: DecapsulationKey,
+    kem_prekeys: BTreeMap<u32, (DecapsulationKey, Vec<u8>)>,
+    one_time_prekeys: BTreeMap<u32, EcdhKeyPair>,
+    unissued_kem: VecDeque<u32>,
+    unissued_one_time: VecDeque<u32>,
+    next_id: u32,
+}
+
+impl PrekeyStore {
+    /// Generates the signed prekey, the last-resort ML-KEM prekey, and the given
+    /// numbers of one-time ML-KEM and curve prekeys
+    pub fn generate(
+        identity: EcdhKeyPair,
+        signer: &impl PrekeySignature,
+        kem_prekeys: usize,
+        one_time_prekeys: usize,
+    ) -> Result<Self, PqxdhError> {
+        check_identity(&identity)?;
+        let spk = EcdhKeyPair::generate(Curve::X25519);
+        let spk_signature = signer.sign(&identity, &encode_ec(spk.public_bytes()));
+        let last_resort_dk = DecapsulationKey::generate(KEM_PARAMETER_SET);
+        let ek = last_resort_dk.encapsulation_key();
+        let last_resort = KemPrekey {
+            id: 1,
+            signature: signer.sign(&identity, &encode_kem(&ek)),
+            ek,
+        };
+        let mut store = PrekeyStore {
+            identity,
+            signed_prekey: (0, spk, spk_signature),
+            last_resort,
+            last_resort_dk,
+            kem_prekeys: BTreeMap::new(),
+            one_time_prekeys: BTreeMap::new(),
+            unissued_kem: VecDeque::new(),
+            unissued_one_time: VecDeque::new(),
+            next_id: 2,
+        };
+        store.add_kem_prekeys(signer, kem_prekeys);
+        store.add_one_time_prekeys(one_time_prekeys);
+        Ok(store)
+    }
+
+    fn next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Uploads more signed one-time ML-KEM prekeys
+    pub fn add_kem_prekeys(&mut self, signer: &impl PrekeySignature, count: usize) {
+        for _ in 0..count {
+            let id = self.next_id();
+            let dk = DecapsulationKey::generate(KEM_PARAMETER_SET);
+            let signature = signer.sign(&self.identity, &encode_kem(&dk.encapsulation_key()));
+            self.kem_prekeys.insert(id, (dk, signature));
+            self.unissued_kem.push_back(id);
+        }
+    }
+
+    /// Uploads more one-time curve prekeys
+    pub fn add_one_time_prekeys(&mut self, count: usize) {
+        for _ in 0..count {
+            let id = self.next_id();
+            self.one_time_prekeys
+                .insert(id, EcdhKeyPair::generate(Curve::X25519));
+            self.unissued_one_time.push_back(id);
+        }
+    }
+
+    pub fn identity_key(&self) -> &[u8] {
+        self.identity.public_bytes()
+    }
+
+    /// Server role : hands out a bundle, with fresh one-time prekeys while any are
+    /// left and the last-resort ML-KEM prekey otherwise
+    pub fn bundle(&mut self) -> PrekeyBundle {
+        let (spk_id, spk, spk_signature) = &self.signed_prekey;
+        let kem_prekey = match self.unissued_kem.pop_front() {
+            Some(id) => {
+                let (dk, signature) = &self.kem_prekeys[&id];
+                KemPrekey {
+                    id,
+                    ek: dk.encapsulation_key(),
+                    signature: signature.clone(),
+                }
+            }
+            None => self.last_resort.clone(),
+        };
+        let one_time_prekey = self.unissued_one_time.pop_front().map(|id| EcPrekey {
+            id,
+            public: self.one_time_prekeys[&id].public_bytes().to_vec(),
+            signature: None,
+        });
+        PrekeyBundle {
+            identity_key: self.identity.public_bytes().to_vec(),
+            signed_prekey: EcPrekey {
+                id: *spk_id,
+                public: spk.public_bytes().to_vec(),
+                signature: Some(spk_signature.clone()),
+            },
+            kem_prekey,
+            one_time_prekey,
+        }
+    }
+
+    /// Bob's side : recomputes SK from the initial message and deletes the one-time
+    /// prekeys it used, so that a replayed message is rejected
+    pub fn respond(
+        &mut self,
+        info: &[u8],
+        message: &InitialMessage,
+    ) -> Result<SessionKeys, PqxdhError> {
+        if message.signed_prekey_id != self.signed_prekey.0 {
+            return Err(PqxdhError::UnknownPrekey(message.signed_prekey_id));
+        }
+        let kem_id = message.kem_prekey_id;
+        let kem_dk = if kem_id == self.last_resort.id {
+            &self.last_resort_dk
+        } else {
+            &self
+                .kem_prekeys
+                .get(&kem_id)
+                .ok_or(PqxdhError::UnknownPrekey(kem_id))?
+                .0
+        };
+        let one_time = match message.one_time_prekey_id {
+            Some(id) => Some(
+                self.one_time_prekeys
+                    .get(&id)
+                    .ok_or(PqxdhError::UnknownPrekey(id))?,
+            ),
+            None => None,
+        };
+
+        let spk = &self.signed_prekey.1;
+        let dh1 = spk.diffie_hellman(&message.identity_key)?;
+        let dh2 = self.identity.diffie_hellman(&message.ephemeral_key)?;
+        let dh3 = spk.diffie_hellman(&message.ephemeral_key)?;
+        let dh4 = match one_time {
+            Some(opk) => opk.diffie_hellman(&message.ephemeral_key)?,
+            None => Vec::new(),
+        };
+        let ss = kem_dk.decaps(&message.ciphertext);
+        let keys = SessionKeys {
+            sk: kdf(info, &[&dh1, &dh2, &dh3, &dh4, &ss]),
+            ad: [
+                encode_ec(&message.identity_key),
+                encode_ec(self.identity.public_bytes()),
+            ]
+            .concat(),
+        };
+
+        self.kem_prekeys.remove(&kem_id);
+        if let Some(id) = message.one_time_prekey_id {
+            self.one_time_prekeys.remove(&id);
+        }
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basics() {
+        let mut message = InitialMessage {
+            identity_key: vec![1; EC_LEN],
+            ephemeral_key: vec![2; EC_LEN],
+            signed_prekey_id: 3,
+            kem_prekey_id: 4,
+            one_time_prekey_id: None,
+            ciphertext: vec![5; KEM_PARAMETER_SET.ciphertext_len()],
+        };
+        let bytes = message.to_bytes();
+        assert_eq!(bytes.len(), 2 * EC_LEN + 9 + 1568);
+        assert_eq!(InitialMessage::from_bytes(&bytes), Ok(message.clone()));
+
+        message.one_time_prekey_id = Some(6);
+        let bytes = message.to_bytes();
+        assert_eq!(bytes[2 * EC_LEN + 8..2 * EC_LEN + 13], [1, 0, 0, 0, 6]);
+        assert_eq!(InitialMessage::from_bytes(&bytes), Ok(message));
+        assert_eq!(
+            InitialMessage::from_bytes(&bytes[..bytes.len() - 4]),
+            Err(PqxdhError::InvalidMessageLength {
+                expected: bytes.len(),
+                found: bytes.len() - 4
+            })
+        );
+
+        let mut bad_flag = bytes;
+        bad_flag[2 * EC_LEN + 8] = 0x02;
+        assert_eq!(
+            InitialMessage::from_bytes(&bad_flag),
+            Err(PqxdhError::InvalidPrekeyFlag(0x02))
+        );
+    }
+}
@@ -0,0 +1,123 @@
+"""Independent age v1 / mlkem768x25519 implementation on pyca/cryptography HPKE."""
+import base64, hashlib, hmac, os, sys
+from cryptography.hazmat.primitives import hpke
+from cryptography.hazmat.primitives.asymmetric import mlkem, x25519
+from cryptography.hazmat.primitives.ciphers.aead import ChaCha20Poly1305
+from cryptography.hazmat.primitives.kdf.hkdf import HKDF
+from cryptography.hazmat.primitives import hashes
+
+INFO = b"age-encryption.org/mlkem768x25519"
+SUITE = hpke.Suite(hpke.KEM.MLKEM768_X25519, hpke.KDF.HKDF_SHA256, hpke.AEAD.CHACHA20_POLY1305)
+CHARSET = "qpzry9x8gf2tvdw0s3jn54khce6mua7l"
+
+def polymod(values):
+    gen = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3]
+    chk = 1
+    for v in values:
+        b = chk >> 25
+        chk = (chk & 0x1ffffff) << 5 ^ v
+        for i in range(5):
+            chk ^= gen[i] if ((b >> i) & 1) else 0
+    return chk
+
+def hrp_expand(hrp):
+    return [ord(x) >> 5 for x in hrp] + [0] + [ord(x) & 31 for x in hrp]
+
+def convertbits(data, f, t, pad):
+    acc = bits = 0; ret = []; maxv = (1 << t) - 1
+    for v in data:
+        acc = (acc << f) | v; bits += f
+        while bits >= t:
+            bits -= t; ret.append((acc >> bits) & maxv)
+    if pad and bits: ret.append((acc << (t - bits)) & maxv)
+    return ret
+
+def bech32_encode(hrp, data):
+    d = convertbits(data, 8, 5, True)
+    chk = polymod(hrp_expand(hrp) + d + [0] * 6) ^ 1
+    return hrp + "1" + "".join(CHARSET[x] for x in d + [(chk >> 5 * (5 - i)) & 31 for i in range(6)])
+
+def bech32_decode(s):
+    s = s.lower(); pos = s.rfind("1"); hrp = s[:pos]
+    d = [CHARSET.find(x) for x in s[pos + 1:]]
+    assert polymod(hrp_expand(hrp) + d) == 1
+    return hrp, bytes(convertbits(d[:-6], 5, 8, False))
+
+def xwing_private(seed):
+    expanded = hashlib.shake_256(seed).digest(96)
+    return hpke.MLKEM768X25519PrivateKey(
+        mlkem.MLKEM768PrivateKey.from_seed_bytes(expanded[:64]),
+        x25519.X25519PrivateKey.from_private_bytes(expanded[64:]))
+
+def hkdf(ikm, salt, info):
+    return HKDF(hashes.SHA256(), 32, salt, info).derive(ikm)
+
+b64 = lambda b: base64.b64encode(b).decode().rstrip("=")
+unb64 = lambda s: base64.b64decode(s + "=" * (-len(s) % 4))
+
+def stream_encrypt(key, pt):
+    aead = ChaCha20Poly1305(key); chunks = [pt[i:i + 65536] for i in range(0, len(pt), 65536)] or [b""]
+    return b"".join(aead.encrypt(i.to_bytes(11, "big") + bytes([i == len(chunks) - 1]), c, None) for i, c in enumerate(chunks))
+
+def stream_decrypt(key, ct):
+    aead = ChaCha20Poly1305(key); chunks = [ct[i:i + 65552] for i in range(0, len(ct), 65552)]
+    return b"".join(aead.decrypt(i.to_bytes(11, "big") + bytes([i == len(chunks) - 1]), c, None) for i, c in enumerate(chunks))
+
+def encrypt(recipient_pks, pt):
+    file_key = os.urandom(16)
+    header = b"age-encryption.org/v1\n"
+    for pk in recipient_pks:
+        out = SUITE.encrypt(file_key, pk, INFO)
+        enc, body = out[:1120], out[1120:]
+        header += b"-> mlkem768x25519 " + b64(enc).encode() + b"\n"
+        body = b64(body)
+        lines = [body[i:i + 64] for i in range(0, len(body), 64)]
+        if len(body) % 64 == 0: lines.append("")
+        header += "".join(l + "\n" for l in lines).encode()
+    header += b"---"
+    mac = hmac.new(hkdf(file_key, b"", b"header"), header, "sha256").digest()
+    nonce = os.urandom(16)
+    return header + b" " + b64(mac).encode() + b"\n" + nonce + stream_encrypt(hkdf(file_key, nonce, b"payload"), pt)
+
+def decrypt(seed, data):
+    sk = xwing_private(seed)
+    lines = data.split(b"\n")
+    assert lines[0] == b"age-encryption.org/v1"
+    i = 1; file_key = None
+    while not lines[i].startswith(b"--- "):
+        args = lines[i][3:].split(b" "); i += 1
+        body = b""
+        while True:
+            body += lines[i]; i += 1
+            if len(lines[i - 1]) < 64: break
+        if args[0] == b"mlkem768x25519" and file_key is None:
+            try:
+                file_key = SUITE.decrypt(unb64(args[1].decode()) + unb64(body.decode()), sk, INFO)
+            except Exception:
+                pass
+    header_len = sum(len(l) + 1 for l in lines[:i]) + 3
+    mac = unb64(lines[i][4:].decode())
+    assert hmac.compare_digest(mac, hmac.new(hkdf(file_key, b"", b"header"), data[:header_len], "sha256").digest())
+    payload = data[header_len + 1 + len(lines[i]) - 3:]
+    nonce, ct = payload[:16], payload[16:]
+    return stream_decrypt(hkdf(file_key, nonce, b"payload"), ct)
+
+if __name__ == "__main__":
+    cmd = sys.argv[1]
+    if cmd == "encrypt":  # encrypt <out> <recipient>... plaintext on stdin
+        pks = [hpke.MLKEM768X25519PublicKey(
+            mlkem.MLKEM768PublicKey.from_public_bytes(d[:1184]),
+            x25519.X25519PublicKey.from_public_bytes(d[1184:]))
+            for d in (bech32_decode(r)[1] for r in sys.argv[3:])]
+        open(sys.argv[2], "wb").write(encrypt(pks, sys.stdin.buffer.read()))
+    elif cmd == "decrypt":  # decrypt <file> <identity>
+        hrp, seed = bech32_decode(sys.argv[3]); assert hrp == "age-secret-key-pq-"
+        sys.stdout.buffer.write(decrypt(seed, open(sys.argv[2], "rb").read()))
+    elif cmd == "recipient":  # recipient <identity> -> independently derived recipient
+        hrp, seed = bech32_decode(sys.argv[2])
+        sk = xwing_private(seed)
+        # X-Wing pk = pk_M || pk_X
+        expanded = hashlib.shake_256(seed).digest(96)
+        pk_m = mlkem.MLKEM768PrivateKey.from_seed_bytes(expanded[:64]).public_key().public_bytes_raw()
+        pk_x = x25519.X25519PrivateKey.from_private_bytes(expanded[64:]).public_key().public_bytes_raw()
+        print(bech32_encode("age1pq", pk_m + pk_x))
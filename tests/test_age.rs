@@ -0,0 +1,107 @@
+use kyber_rs::age::format::{AgeError, decrypt, encrypt};
+use kyber_rs::age::recipient::{Identity, Recipient};
+use kyber_rs::age::stream::CHUNK_SIZE;
+
+/// Sealed to the recipients of IDENTITY_B then IDENTITY_A by
+/// data/age-mlkem768x25519-pyca.py, an age implementation written alongside this
+/// crate on the HPKE of pyca/cryptography 48 (OpenSSL); the plaintext is 65600
+/// bytes i % 251, so that the payload has two chunks. See
+/// data/age-mlkem768x25519-README.txt for the tools and the commands
+const PYCA_FILE: &[u8] = include_bytes!("data/age-mlkem768x25519-pyca.age");
+/// Sealed by this crate to the same recipients, and checked to decrypt with the
+/// implementation above
+const CRATE_FILE: &[u8] = include_bytes!("data/age-mlkem768x25519-crate.age");
+
+/// Seeds 00..1f and 20..3f
+const IDENTITY_A: &str =
+    "AGE-SECRET-KEY-PQ-1QQQSYQCYQ5RQWZQFPG9SCRGWPUGPZYSNZS23V9CCRYDPK8QARC0S5DYVQQ";
+const IDENTITY_B: &str =
+    "AGE-SECRET-KEY-PQ-1YQSJYGEYY5NZW2PF9G4JCTFW9UCRZV3NXS6NVDEC8YARK0PA8CLS5GGFSG";
+
+// This does not meet the interop requirement with other age implementations : only
+// the HPKE of the script is independent, its header, stanza, MAC and STREAM code
+// share the author of this crate. Files of age >= 1.3 (age-keygen -pq, age -r
+// age1pq1...) or rage are still to be added.
+#[test]
+fn interop() {
+    let a = Identity::parse(IDENTITY_A).unwrap();
+    let b = Identity::parse(IDENTITY_B).unwrap();
+    assert_eq!(a.seed(), &core::array::from_fn(|i| i as u8));
+
+    let expected: Vec<u8> = (0..65600u32).map(|i| (i % 251) as u8).collect();
+    for identity in [&a, &b] {
+        let identities = [identity.clone()];
+        assert_eq!(decrypt(&identities, PYCA_FILE).unwrap(), expected);
+        assert_eq!(
+            decrypt(&identities, CRATE_FILE).unwrap(),
+            b"Beauty is truth, truth beauty"
+        );
+    }
+    assert_eq!(
+        decrypt(&[Identity::generate()], PYCA_FILE),
+        Err(AgeError::NoMatchingIdentity)
+    );
+}
+
+#[test]
+fn round_trip() {
+    let identities: Vec<Identity> = (0..3).map(|_| Identity::generate()).collect();
+    let recipients: Vec<Recipient> = identities
+        .iter()
+        .map(|identity| Recipient::parse(&identity.to_recipient().to_string()).unwrap())
+        .collect();
+    for len in [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, 3 * CHUNK_SIZE + 5] {
+        let plaintext = vec![0x5au8; len];
+        let file = encrypt(&recipients, &plaintext).unwrap();
+        for identity in &identities {
+            let identities = [Identity::generate(), identity.clone()];
+            assert_eq!(decrypt(&identities, &file).unwrap(), plaintext);
+        }
+    }
+    assert_eq!(encrypt(&[], b"x"), Err(AgeError::NoRecipients));
+}
+
+#[test]
+fn tampering() {
+    let identities = [Identity::parse(IDENTITY_A).unwrap()];
+    let header_len = CRATE_FILE.windows(4).position(|w| w == b"\n---").unwrap() + 4;
+
+    // Any change of the header before the MAC, or of the MAC
+    let mut file = CRATE_FILE.to_vec();
+    file[header_len - 5] ^= 1;
+    assert!(decrypt(&identities, &file).is_err());
+    let mut file = CRATE_FILE.to_vec();
+    file[header_len + 1] = if file[header_len + 1] == b'A' {
+        b'B'
+    } else {
+        b'A'
+    };
+    assert_eq!(
+        decrypt(&identities, &file),
+        Err(AgeError::HeaderMacMismatch)
+    );
+
+    // Payload nonce, chunk, and truncation
+    let payload = CRATE_FILE.len() - 29 - 16 - 16;
+    for index in [payload, CRATE_FILE.len() - 1] {
+        let mut file = CRATE_FILE.to_vec();
+        file[index] ^= 1;
+        assert_eq!(decrypt(&identities, &file), Err(AgeError::InvalidPayload));
+    }
+    assert_eq!(
+        decrypt(&identities, &CRATE_FILE[..CRATE_FILE.len() - 1]),
+        Err(AgeError::InvalidPayload)
+    );
+    assert_eq!(
+        decrypt(&identities, &CRATE_FILE[..payload]),
+        Err(AgeError::InvalidPayload)
+    );
+
+    // Truncating the pyca file to its first, non-final chunk
+    let identities = [Identity::parse(IDENTITY_B).unwrap()];
+    let end = PYCA_FILE.len() - (65600 - CHUNK_SIZE) - 16;
+    assert_eq!(
+        decrypt(&identities, &PYCA_FILE[..end]),
+        Err(AgeError::InvalidPayload)
+    );
+}
@@ -0,0 +1,133 @@
+use kyber_rs::ake::{AkeError, AkeMode, Initiator, InitiatorMessage, Responder, ResponderMessage};
+use kyber_rs::kyber::kem_scheme::ParameterSet;
+use kyber_rs::kyber::keys::{DecapsulationKey, KeyError};
+
+/// Runs both flights through their byte encoding, passing the second one through
+/// `tamper`, and returns the (initiator, responder) session keys
+fn exchange(
+    set: ParameterSet,
+    initiator: Initiator,
+    responder: &Responder,
+    tamper: impl FnOnce(&mut Vec<u8>),
+) -> ([u8; 32], [u8; 32]) {
+    let first = initiator.message().to_bytes();
+    assert_eq!(first.len(), InitiatorMessage::len(set));
+    let first = InitiatorMessage::from_bytes(set, &first).unwrap();
+    let (reply, responder_key) = responder.respond(&first).unwrap();
+    let mut second = reply.to_bytes();
+    assert_eq!(second.len(), ResponderMessage::len(set, responder.mode()));
+    tamper(&mut second);
+    let second = ResponderMessage::from_bytes(set, initiator.mode(), &second).unwrap();
+    (initiator.finish(&second).unwrap(), responder_key)
+}
+
+#[test]
+fn end_to_end() {
+    for set in ParameterSet::ALL {
+        let alice = DecapsulationKey::generate(set);
+        let bob = DecapsulationKey::generate(set);
+        let ek_alice = alice.encapsulation_key();
+        let ek_bob = bob.encapsulation_key();
+
+        // Kyber.AKE
+        let responder = Responder::mutual(bob.clone(), &ek_alice).unwrap();
+        let initiator = Initiator::mutual(alice.clone(), &ek_bob).unwrap();
+        let (initiator_key, responder_key) = exchange(set, initiator, &responder, |_| {});
+        assert_eq!(initiator_key, responder_key);
+
+        // Fresh ephemerals give fresh session keys
+        let initiator = Initiator::mutual(alice.clone(), &ek_bob).unwrap();
+        let (again, _) = exchange(set, initiator, &responder, |_| {});
+        assert_ne!(again, initiator_key);
+
+        // Kyber.UAKE
+        let responder = Responder::unilateral(bob.clone());
+        let initiator = Initiator::unilateral(&ek_bob);
+        let (initiator_key, responder_key) = exchange(set, initiator, &responder, |_| {});
+        assert_eq!(initiator_key, responder_key);
+    }
+}
+
+#[test]
+fn authentication() {
+    let set = ParameterSet::MlKem768;
+    let alice = DecapsulationKey::generate(set);
+    let bob = DecapsulationKey::generate(set);
+    let mallory = DecapsulationKey::generate(set);
+
+    // Mallory impersonates Alice to Bob : she cannot decapsulate c_A
+    let responder = Responder::mutual(bob.clone(), &alice.encapsulation_key()).unwrap();
+    let initiator = Initiator::mutual(mallory.clone(), &bob.encapsulation_key()).unwrap();
+    let (initiator_key, responder_key) = exchange(set, initiator, &responder, |_| {});
+    assert_ne!(initiator_key, responder_key);
+
+    // Mallory impersonates Bob : she cannot decapsulate c_B
+    for responder in [
+        Responder::mutual(mallory.clone(), &alice.encapsulation_key()).unwrap(),
+        Responder::unilateral(mallory.clone()),
+    ] {
+        let initiator = match responder.mode() {
+            AkeMode::Mutual => Initiator::mutual(alice.clone(), &bob.encapsulation_key()).unwrap(),
+            AkeMode::Unilateral => Initiator::unilateral(&bob.encapsulation_key()),
+        };
+        let (initiator_key, responder_key) = exchange(set, initiator, &responder, |_| {});
+        assert_ne!(initiator_key, responder_key);
+    }
+
+    // A flipped bit in either ciphertext of the second flight
+    for position in [0, set.ciphertext_len()] {
+        let responder = Responder::mutual(bob.clone(), &alice.encapsulation_key()).unwrap();
+        let initiator = Initiator::mutual(alice.clone(), &bob.encapsulation_key()).unwrap();
+        let (initiator_key, responder_key) =
+            exchange(set, initiator, &responder, |message| message[position] ^= 1);
+        assert_ne!(initiator_key, responder_key);
+    }
+}
+
+#[test]
+fn malformed_messages() {
+    let set = ParameterSet::MlKem512;
+    let bob = DecapsulationKey::generate(set);
+    let initiator = Initiator::unilateral(&bob.encapsulation_key());
+    let first = initiator.message().to_bytes();
+
+    assert_eq!(
+        InitiatorMessage::from_bytes(set, &first[1..]).unwrap_err(),
+        AkeError::InvalidMessageLength {
+            expected: InitiatorMessage::len(set),
+            found: first.len() - 1
+        }
+    );
+    let mut bad = first.clone();
+    bad[0] = 0xff;
+    bad[1] |= 0x0f;
+    assert_eq!(
+        InitiatorMessage::from_bytes(set, &bad).unwrap_err(),
+        AkeError::Key(KeyError::InvalidModulus)
+    );
+
+    // A first flight of another parameter set
+    let other = DecapsulationKey::generate(ParameterSet::MlKem768);
+    let foreign = Initiator::unilateral(&other.encapsulation_key());
+    assert_eq!(
+        Responder::unilateral(bob.clone())
+            .respond(foreign.message())
+            .unwrap_err(),
+        AkeError::ParameterSetMismatch
+    );
+    assert_eq!(
+        Responder::mutual(bob.clone(), &other.encapsulation_key()).err(),
+        Some(AkeError::ParameterSetMismatch)
+    );
+
+    // The mode of the second flight is fixed by the initiator
+    let first = InitiatorMessage::from_bytes(set, &first).unwrap();
+    let (reply, _) = Responder::unilateral(bob).respond(&first).unwrap();
+    assert_eq!(
+        ResponderMessage::from_bytes(set, AkeMode::Mutual, &reply.to_bytes()).unwrap_err(),
+        AkeError::InvalidMessageLength {
+            expected: 2 * set.ciphertext_len(),
+            found: set.ciphertext_len()
+        }
+    );
+}
@@ -0,0 +1,144 @@
+use kyber_rs::envelope::{
+    DataKey, Envelope, EnvelopeError, MAX_RECIPIENTS, WrapMethod, fingerprint,
+};
+use kyber_rs::kyber::kem_scheme::ParameterSet;
+use kyber_rs::kyber::keys::{DecapsulationKey, EncapsulationKey};
+
+fn keys() -> Vec<DecapsulationKey> {
+    ParameterSet::ALL
+        .into_iter()
+        .map(DecapsulationKey::generate)
+        .collect()
+}
+
+#[test]
+fn multiple_recipients() {
+    let dks = keys();
+    let eks: Vec<_> = dks.iter().map(|dk| dk.encapsulation_key()).collect();
+    for method in WrapMethod::ALL {
+        for dek in [
+            DataKey::generate(),
+            DataKey::from_bytes(&[7u8; 16]).unwrap(),
+        ] {
+            let envelope =
+                Envelope::new(&dek, &[eks.clone(), eks.clone()].concat(), method).unwrap();
+            assert_eq!(envelope.recipients().len(), 3);
+            let envelope = Envelope::from_bytes(&envelope.to_bytes()).unwrap();
+            for (dk, ek) in dks.iter().zip(&eks) {
+                let wrapped = envelope.recipient(&fingerprint(ek)).unwrap();
+                assert_eq!(wrapped.parameter_set, ek.parameter_set());
+                let overhead = if method == WrapMethod::AesKw { 8 } else { 16 };
+                assert_eq!(wrapped.wrapped.len(), dek.as_bytes().len() + overhead);
+                assert_eq!(envelope.unwrap(dk).unwrap(), dek);
+            }
+        }
+    }
+    assert_eq!(
+        DataKey::from_bytes(&[0u8; 20]),
+        Err(EnvelopeError::InvalidDataKeyLength(20))
+    );
+}
+
+#[test]
+fn rewrap() {
+    let dek = DataKey::generate();
+    let [old, other, new] = [0, 1, 2].map(|_| DecapsulationKey::generate(ParameterSet::MlKem768));
+    let mut envelope = Envelope::new(
+        &dek,
+        &[old.encapsulation_key(), other.encapsulation_key()],
+        WrapMethod::AesKw,
+    )
+    .unwrap();
+    let other_entry = envelope.recipients()[1].clone();
+
+    envelope.rewrap(&old, &new.encapsulation_key()).unwrap();
+    assert_eq!(envelope.unwrap(&old), Err(EnvelopeError::UnknownRecipient));
+    assert_eq!(envelope.unwrap(&new).unwrap(), dek);
+    assert_eq!(envelope.recipients()[1], other_entry);
+    assert_eq!(
+        envelope.rewrap(&new, &other.encapsulation_key()),
+        Err(EnvelopeError::DuplicateRecipient)
+    );
+
+    // Adding a recipient needs a current one; a new key of another parameter set
+    // can be added
+    let added = DecapsulationKey::generate(ParameterSet::MlKem1024);
+    assert_eq!(
+        envelope.add_recipient(&old, &added.encapsulation_key(), WrapMethod::Aes256Gcm),
+        Err(EnvelopeError::UnknownRecipient)
+    );
+    envelope
+        .add_recipient(&other, &added.encapsulation_key(), WrapMethod::Aes256Gcm)
+        .unwrap();
+    assert_eq!(envelope.unwrap(&added).unwrap(), dek);
+    assert!(envelope.remove_recipient(&fingerprint(&other.encapsulation_key())));
+    assert!(!envelope.remove_recipient(&fingerprint(&other.encapsulation_key())));
+    assert_eq!(envelope.recipients().len(), 2);
+}
+
+#[test]
+fn tampering() {
+    let dk = DecapsulationKey::generate(ParameterSet::MlKem512);
+    let ek = dk.encapsulation_key();
+    for method in WrapMethod::ALL {
+        let bytes = Envelope::new(&DataKey::generate(), core::slice::from_ref(&ek), method)
+            .unwrap()
+            .to_bytes();
+        // The fingerprint selects the entry, every other byte is authenticated
+        for index in 3 + 32..bytes.len() {
+            let mut modified = bytes.clone();
+            modified[index] ^= 1;
+            if let Ok(envelope) = Envelope::from_bytes(&modified) {
+                assert_eq!(envelope.unwrap(&dk), Err(EnvelopeError::Unwrap));
+            }
+        }
+        let mut modified = bytes.clone();
+        modified[3] ^= 1;
+        assert_eq!(
+            Envelope::from_bytes(&modified).unwrap().unwrap(&dk),
+            Err(EnvelopeError::UnknownRecipient)
+        );
+
+        assert_eq!(
+            Envelope::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(EnvelopeError::Truncated)
+        );
+        assert_eq!(
+            Envelope::from_bytes(&[bytes.as_slice(), &[0]].concat()),
+            Err(EnvelopeError::TrailingData)
+        );
+        let mut modified = bytes.clone();
+        modified[0] = 2;
+        assert_eq!(
+            Envelope::from_bytes(&modified),
+            Err(EnvelopeError::UnsupportedVersion(2))
+        );
+        let mut modified = bytes.clone();
+        modified[3 + 33] = 9;
+        assert_eq!(
+            Envelope::from_bytes(&modified),
+            Err(EnvelopeError::UnknownMethod(9))
+        );
+    }
+}
+
+#[test]
+fn too_many_recipients() {
+    // Distinct keys with t_hat = 0, cheap to build; the count is checked before
+    // any encapsulation
+    let set = ParameterSet::MlKem512;
+    let ek = |i: usize| {
+        let mut bytes = vec![0u8; set.ek_len()];
+        bytes[set.ek_len() - 4..].copy_from_slice(&(i as u32).to_be_bytes());
+        EncapsulationKey::from_bytes(set, &bytes).unwrap()
+    };
+    let eks: Vec<_> = (0..=MAX_RECIPIENTS).map(ek).collect();
+    assert_eq!(
+        Envelope::new(&DataKey::generate(), &eks, WrapMethod::AesKw),
+        Err(EnvelopeError::TooManyRecipients)
+    );
+    // The 16-bit count is the number of distinct recipients
+    let eks = [eks[0].clone(), eks[0].clone(), eks[1].clone()];
+    let envelope = Envelope::new(&DataKey::generate(), &eks, WrapMethod::AesKw).unwrap();
+    assert_eq!(envelope.to_bytes()[1..3], [0, 2]);
+}
@@ -0,0 +1,156 @@
+use kyber_rs::hpke::context::{Hpke, Psk};
+use kyber_rs::hpke::kem::HpkeKem;
+use kyber_rs::hpke::primitives::{HpkeAead, HpkeError, HpkeKdf};
+
+const RFC9180_VECTORS: &str = include_str!("data/hpke-rfc9180.txt");
+const PQ_INTEROP_VECTORS: &str = include_str!("data/hpke-pq-interop.txt");
+const INTEROP_VECTORS: &str = include_str!("data/hpke-interop.txt");
+
+fn suite(kem: &str, kdf: &str, aead: &str) -> Hpke {
+    let id = |field: &str| u16::from_str_radix(field, 16).unwrap();
+    Hpke::new(
+        HpkeKem::from_id(id(kem)).unwrap(),
+        HpkeKdf::from_id(id(kdf)).unwrap(),
+        HpkeAead::from_id(id(aead)).unwrap(),
+    )
+}
+
+fn decode(field: &str) -> Vec<u8> {
+    if field == "-" {
+        Vec::new()
+    } else {
+        hex::decode(field).unwrap()
+    }
+}
+
+/// Sequence numbers of the ciphertexts of a vector
+const SEQS: [u64; 6] = [0, 1, 2, 4, 255, 256];
+
+/// Checks the lines of hpke-rfc9180.txt or hpke-interop.txt, returns their count
+fn check_vectors(vectors: &str) -> usize {
+    let mut count = 0;
+    for line in vectors.lines().filter(|line| !line.starts_with('#')) {
+        let fields: Vec<&str> = line.split(' ').collect();
+        let hpke = suite(fields[2], fields[3], fields[4]);
+        let [
+            ikm_e,
+            ikm_r,
+            psk,
+            psk_id,
+            info,
+            sk_em,
+            pk_em,
+            sk_rm,
+            pk_rm,
+            enc,
+            shared_secret,
+        ] = core::array::from_fn(|i| decode(fields[5 + i]));
+        let [key, base_nonce, exporter_secret, pt] =
+            core::array::from_fn(|i| decode(fields[16 + i]));
+        let psk = (fields[1] == "1").then_some(Psk {
+            psk: &psk,
+            psk_id: &psk_id,
+        });
+
+        // DeriveKeyPair, Encap and Decap. The ephemeral key pair of a DHKEM is
+        // derived from the Encap randomness ikmE.
+        assert_eq!(
+            hpke.kem.derive_key_pair(&ikm_r).unwrap(),
+            (sk_rm.clone(), pk_rm.clone())
+        );
+        if !sk_em.is_empty() {
+            assert_eq!(hpke.kem.derive_key_pair(&ikm_e).unwrap(), (sk_em, pk_em));
+        }
+        assert_eq!(
+            hpke.kem.encap_derand(&pk_rm, &ikm_e).unwrap(),
+            (shared_secret.clone(), enc.clone())
+        );
+        assert_eq!(hpke.kem.decap(&enc, &sk_rm).unwrap(), shared_secret);
+
+        let (sender_enc, mut sender) = hpke
+            .setup_sender_derand(&pk_rm, &info, psk, &ikm_e)
+            .unwrap();
+        assert_eq!(sender_enc, enc);
+        let mut receiver = hpke.setup_receiver(&enc, &sk_rm, &info, psk).unwrap();
+
+        // key and base_nonce : every message is the AEAD sealing under them
+        let mut cts = fields[20..26].iter().map(|ct| decode(ct));
+        for seq in 0..=SEQS[5] {
+            let aad = format!("Count-{seq}");
+            let mut nonce = base_nonce.clone();
+            for (byte, seq_byte) in nonce[4..].iter_mut().zip(seq.to_be_bytes()) {
+                *byte ^= seq_byte;
+            }
+            let ct = sender.seal(aad.as_bytes(), &pt).unwrap();
+            assert_eq!(ct, hpke.aead.seal(&key, &nonce, aad.as_bytes(), &pt));
+            if SEQS.contains(&seq) {
+                assert_eq!(Some(&ct), cts.next().as_ref());
+            }
+            assert_eq!(receiver.open(aad.as_bytes(), &ct).unwrap(), pt);
+        }
+
+        // exporter_secret
+        let suite_id = [
+            b"HPKE".as_slice(),
+            &hpke.kem.id().to_be_bytes(),
+            &hpke.kdf.id().to_be_bytes(),
+            &hpke.aead.id().to_be_bytes(),
+        ]
+        .concat();
+        for (exporter_context, exported) in [&b""[..], b"\x00", b"TestContext"]
+            .into_iter()
+            .zip(&fields[26..29])
+        {
+            let exported = decode(exported);
+            assert_eq!(
+                hpke.kdf
+                    .labeled_expand(&suite_id, &exporter_secret, b"sec", exporter_context, 32)
+                    .unwrap(),
+                exported
+            );
+            assert_eq!(sender.export(exporter_context, 32).unwrap(), exported);
+            assert_eq!(receiver.export(exporter_context, 32).unwrap(), exported);
+        }
+        count += 1;
+    }
+    count
+}
+
+#[test]
+fn rfc9180_vectors() {
+    assert_eq!(check_vectors(RFC9180_VECTORS), 7);
+}
+
+// Not the draft-ietf-hpke-pq test vectors, which could not be added yet : the
+// ML-KEM and X-Wing lines are checked against OpenSSL and pyca/cryptography
+#[test]
+fn interop_key_schedule() {
+    assert_eq!(check_vectors(INTEROP_VECTORS), 11);
+}
+
+#[test]
+fn pq_interop() {
+    let mut count = 0;
+    for line in PQ_INTEROP_VECTORS
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+    {
+        let fields: Vec<&str> = line.split(' ').collect();
+        let hpke = suite(fields[0], fields[1], fields[2]);
+        let [sk, info, aad, pt, sealed] = core::array::from_fn(|i| decode(fields[3 + i]));
+        let (enc, ct) = sealed.split_at(hpke.kem.n_enc());
+
+        assert_eq!(hpke.open(enc, &sk, &info, &aad, ct, None).unwrap(), pt);
+        assert_eq!(
+            hpke.open(enc, &sk, &info, b"", ct, None),
+            Err(HpkeError::Open)
+        );
+
+        // and back : our sealing opens with our key
+        let pk = hpke.kem.public_key(&sk).unwrap();
+        let (enc, ct) = hpke.seal(&pk, &info, &aad, &pt, None).unwrap();
+        assert_eq!(hpke.open(&enc, &sk, &info, &aad, &ct, None).unwrap(), pt);
+        count += 1;
+    }
+    assert_eq!(count, 6);
+}
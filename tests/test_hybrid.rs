@@ -0,0 +1,68 @@
+use kyber_rs::hybrid::tls::{ClientKeyShare, HybridGroup, TlsError};
+use kyber_rs::hybrid::xwing::XWing;
+use sha3::Shake128;
+use sha3::digest::{ExtendableOutput, XofReader};
+
+const TLS_VECTORS: &str = include_str!("data/tls-hybrid-openssl.txt");
+const XWING_VECTORS: &str = include_str!("data/xwing-vectors.txt");
+
+// Interop with OpenSSL 3.5 only: no draft-ietf-tls-ecdhe-mlkem (or
+// draft-kwiatkowski-tls-ecdhe-mlkem) test vectors are checked here yet
+#[test]
+fn tls_hybrid_openssl() {
+    let mut count = 0;
+    for line in TLS_VECTORS.lines().filter(|line| !line.starts_with('#')) {
+        let fields: Vec<&str> = line.split(' ').collect();
+        let group = HybridGroup::ALL
+            .into_iter()
+            .find(|group| group.name() == fields[0])
+            .unwrap();
+        let seed: [u8; 64] = hex::decode(fields[1]).unwrap().try_into().unwrap();
+        let ecdh_secret = hex::decode(fields[2]).unwrap();
+        let client_share = hex::decode(fields[3]).unwrap();
+        let server_share = hex::decode(fields[4]).unwrap();
+        let shared_secret = hex::decode(fields[5]).unwrap();
+
+        let client = ClientKeyShare::from_secrets(group, &seed, &ecdh_secret).unwrap();
+        assert_eq!(client.share(), client_share);
+        assert_eq!(client.finish(&server_share).unwrap(), shared_secret);
+
+        let mut truncated = server_share.clone();
+        truncated.pop();
+        assert_eq!(
+            client.finish(&truncated).unwrap_err(),
+            TlsError::InvalidShareLength {
+                expected: group.server_share_len(),
+                found: truncated.len()
+            }
+        );
+        count += 1;
+    }
+    assert_eq!(count, 3);
+}
+
+// The shared secrets also fix the order of the combiner : XWingLabel is hashed last
+#[test]
+fn xwing_vectors() {
+    let xwing = XWing::new();
+    let mut seeds = Shake128::default().finalize_xof();
+    let mut count = 0;
+    for line in XWING_VECTORS.lines().filter(|line| !line.starts_with('#')) {
+        let fields: Vec<Vec<u8>> = line.split(' ').map(|f| hex::decode(f).unwrap()).collect();
+        let mut sk = [0u8; 32];
+        let mut eseed = [0u8; 64];
+        seeds.read(&mut sk);
+        seeds.read(&mut eseed);
+        assert_eq!(sk.as_slice(), fields[0]);
+        assert_eq!(eseed.as_slice(), fields[1]);
+
+        let (_, pk) = xwing.key_gen_derand(&sk);
+        assert_eq!(pk, fields[2]);
+        let (ss, ct) = xwing.encaps_derand(&pk, &eseed).unwrap();
+        assert_eq!(ct, fields[3]);
+        assert_eq!(ss, fields[4]);
+        assert_eq!(xwing.decaps(&sk, &ct).unwrap(), ss);
+        count += 1;
+    }
+    assert_eq!(count, 3);
+}
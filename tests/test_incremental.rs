@@ -0,0 +1,107 @@
+use kyber_rs::constants::KyberParams;
+use kyber_rs::erasure::{Decoder, Encoder};
+use kyber_rs::kyber::incremental::{HEADER_LEN, IncrementalError};
+use kyber_rs::kyber::kem_scheme::{MlKem, ParameterSet};
+use kyber_rs::kyber::keys::{DecapsulationKey, EncapsulationKey, KeyError};
+
+const CHUNK_LEN: usize = 32;
+
+/// Sends a message as erasure-coded chunks over a channel that drops every third chunk
+fn transmit(message: &[u8]) -> Vec<u8> {
+    let encoder = Encoder::new(message, CHUNK_LEN).unwrap();
+    let mut decoder = Decoder::new(message.len(), CHUNK_LEN).unwrap();
+    let mut sent = 0;
+    for index in 0..=u8::MAX {
+        if decoder.is_complete() {
+            break;
+        }
+        let chunk = encoder.chunk(index);
+        if index % 3 != 2 {
+            decoder.add_chunk(&chunk).unwrap();
+        }
+        sent += 1;
+    }
+    assert!(sent > encoder.data_chunks());
+    decoder.decode().unwrap()
+}
+
+#[test]
+fn incremental_encapsulation() {
+    for set in ParameterSet::ALL {
+        let ml_kem = MlKem::<KyberParams>::from_parameter_set(set);
+        let dk = DecapsulationKey::generate(set);
+        let ek = dk.encapsulation_key();
+
+        // The decapsulator sends the header, then t_hat in chunks
+        let header = ek.header();
+        let m = [0x42u8; 32];
+        let (k, c1, state) = ml_kem.encaps1_internal(&header, &m).unwrap();
+        let t_hat = transmit(ek.t_hat());
+        assert_eq!(
+            EncapsulationKey::from_parts(set, &header, &t_hat).unwrap(),
+            ek
+        );
+
+        // The encapsulator sends c_1 as soon as it has the header, then c_2
+        let c1 = transmit(&c1);
+        let c2 = ml_kem.encaps2(state, &t_hat).unwrap();
+        let c = [c1, c2].concat();
+        assert_eq!(
+            ml_kem.encaps_internal(ek.as_bytes(), &m),
+            (k.clone(), c.clone())
+        );
+        assert_eq!(dk.decaps(&c), k);
+
+        // Randomized encapsulation
+        let (k, c1, state) = ml_kem.encaps1(&header).unwrap();
+        let c2 = ml_kem.encaps2(state, ek.t_hat()).unwrap();
+        assert_eq!(dk.decaps(&[c1, c2].concat()), k);
+    }
+}
+
+#[test]
+fn mismatched_parts() {
+    let set = ParameterSet::MlKem768;
+    let ml_kem = MlKem::<KyberParams>::from_parameter_set(set);
+    let ek = DecapsulationKey::generate(set).encapsulation_key();
+    let other = DecapsulationKey::generate(set).encapsulation_key();
+
+    let (_, _, state) = ml_kem.encaps1(&ek.header()).unwrap();
+    assert_eq!(
+        ml_kem.encaps2(state, other.t_hat()).unwrap_err(),
+        IncrementalError::HashMismatch
+    );
+    assert_eq!(
+        EncapsulationKey::from_parts(set, &ek.header(), other.t_hat()).unwrap_err(),
+        IncrementalError::HashMismatch
+    );
+
+    // A t_hat that is not reduced modulo q, with a header that matches it
+    let mut t_hat = ek.t_hat().to_vec();
+    t_hat[0] = 0xff;
+    t_hat[1] |= 0x0f;
+    let mut header = ek.header();
+    let unreduced = [t_hat.as_slice(), &header[..32]].concat();
+    header[32..].copy_from_slice(&kyber_rs::hash::h(&unreduced));
+    let (_, _, state) = ml_kem.encaps1(&header).unwrap();
+    assert_eq!(
+        ml_kem.encaps2(state, &t_hat).unwrap_err(),
+        IncrementalError::Key(KeyError::InvalidModulus)
+    );
+
+    assert_eq!(
+        ml_kem.encaps1(&header[1..]).err().unwrap(),
+        IncrementalError::InvalidLength {
+            expected: HEADER_LEN,
+            found: HEADER_LEN - 1
+        }
+    );
+    let (_, _, state) = ml_kem.encaps1(&ek.header()).unwrap();
+    assert_eq!(
+        ml_kem.encaps2(state, &ek.t_hat()[1..]).unwrap_err(),
+        IncrementalError::InvalidLength {
+            expected: set.t_hat_len(),
+            found: set.t_hat_len() - 1
+        }
+    );
+}
@@ -0,0 +1,87 @@
+use kyber_rs::kem_dem::{Dem, HEADER_LEN, KemDemError, TAG_LEN, open, seal, seal_with};
+use kyber_rs::kyber::kem_scheme::ParameterSet;
+use kyber_rs::kyber::keys::DecapsulationKey;
+
+#[test]
+fn round_trip() {
+    for set in ParameterSet::ALL {
+        let dk = DecapsulationKey::generate(set);
+        let ek = dk.encapsulation_key();
+        for len in [0, 1, 32, 1000, 100_000] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            for dem in Dem::ALL {
+                let sealed = seal_with(&ek, dem, &plaintext, b"header");
+                assert_eq!(
+                    sealed.len(),
+                    HEADER_LEN + set.ciphertext_len() + len + TAG_LEN
+                );
+                assert_eq!(open(&dk, &sealed, b"header").unwrap(), plaintext);
+            }
+        }
+        // Two seals of the same message differ
+        assert_ne!(seal(&ek, b"record", b""), seal(&ek, b"record", b""));
+    }
+}
+
+#[test]
+fn tampering() {
+    let set = ParameterSet::MlKem768;
+    let dk = DecapsulationKey::generate(set);
+    let ek = dk.encapsulation_key();
+    let sealed = seal(&ek, b"Beauty is truth, truth beauty", b"aad");
+
+    // Every byte of the ML-KEM ciphertext, AEAD ciphertext and tag is authenticated
+    for index in HEADER_LEN..sealed.len() {
+        let mut modified = sealed.clone();
+        modified[index] ^= 0x80;
+        assert_eq!(open(&dk, &modified, b"aad"), Err(KemDemError::Decryption));
+    }
+    // The DEM identifier selects the cipher and is bound to the key
+    let mut modified = sealed.clone();
+    modified[2] = 2;
+    assert_eq!(open(&dk, &modified, b"aad"), Err(KemDemError::Decryption));
+
+    assert_eq!(open(&dk, &sealed, b"other"), Err(KemDemError::Decryption));
+    assert_eq!(
+        open(&DecapsulationKey::generate(set), &sealed, b"aad"),
+        Err(KemDemError::Decryption)
+    );
+    assert_eq!(
+        open(
+            &DecapsulationKey::generate(ParameterSet::MlKem512),
+            &sealed,
+            b"aad"
+        ),
+        Err(KemDemError::ParameterSetMismatch)
+    );
+
+    // Malformed headers and lengths
+    let mut modified = sealed.clone();
+    modified[0] = 2;
+    assert_eq!(
+        open(&dk, &modified, b"aad"),
+        Err(KemDemError::UnsupportedVersion(2))
+    );
+    let mut modified = sealed.clone();
+    modified[1] = 4;
+    assert_eq!(
+        open(&dk, &modified, b"aad"),
+        Err(KemDemError::UnknownAlgorithm(4))
+    );
+    let mut modified = sealed.clone();
+    modified[2] = 0;
+    assert_eq!(
+        open(&dk, &modified, b"aad"),
+        Err(KemDemError::UnknownDem(0))
+    );
+    let minimum = HEADER_LEN + set.ciphertext_len() + TAG_LEN;
+    assert_eq!(
+        open(&dk, &sealed[..minimum - 1], b"aad"),
+        Err(KemDemError::Truncated)
+    );
+    assert_eq!(
+        open(&dk, &sealed[..sealed.len() - 1], b"aad"),
+        Err(KemDemError::Decryption)
+    );
+    assert_eq!(open(&dk, &sealed[..2], b"aad"), Err(KemDemError::Truncated));
+}
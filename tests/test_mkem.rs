@@ -0,0 +1,104 @@
+use kyber_rs::constants::KyberParams;
+use kyber_rs::kyber::kem_scheme::{MlKem, ParameterSet};
+use kyber_rs::kyber::keys::{DecapsulationKey, EncapsulationKey};
+use kyber_rs::kyber::mkem::{MkemError, MultiCiphertext};
+
+#[test]
+fn broadcast() {
+    for set in ParameterSet::ALL {
+        let ml_kem = MlKem::<KyberParams>::from_parameter_set(set);
+        let rho = [0x42u8; 32];
+        let keys: Vec<_> = (0..20).map(|_| ml_kem.key_gen_with_rho(&rho)).collect();
+        let eks: Vec<&[u8]> = keys.iter().map(|(ek, _)| ek.as_slice()).collect();
+
+        let (k, c) = ml_kem.mencaps(&eks).unwrap();
+        let bytes = c.to_bytes();
+        assert_eq!(
+            bytes.len(),
+            set.ciphertext_u_len() + 20 * set.ciphertext_v_len()
+        );
+        assert!(4 * bytes.len() < 20 * set.ciphertext_len());
+        let c = MultiCiphertext::from_bytes(set, &bytes).unwrap();
+        for ((ek, dk), v) in keys.iter().zip(&c.v) {
+            assert_eq!(ml_kem.mdecaps(dk, &c.u, v).unwrap(), k);
+
+            // Recipient keys are ordinary ML-KEM key pairs
+            let dk = DecapsulationKey::from_expanded(set, dk).unwrap();
+            assert_eq!(
+                dk.encapsulation_key(),
+                EncapsulationKey::from_bytes(set, ek).unwrap()
+            );
+            let (k, c) = dk.encapsulation_key().encaps();
+            assert_eq!(dk.decaps(&c), k);
+        }
+    }
+}
+
+#[test]
+fn implicit_rejection() {
+    let set = ParameterSet::MlKem768;
+    let ml_kem = MlKem::<KyberParams>::from_parameter_set(set);
+    let rho = [7u8; 32];
+    let (ek_a, dk_a) = ml_kem.key_gen_with_rho(&rho);
+    let (ek_b, dk_b) = ml_kem.key_gen_with_rho(&rho);
+    let (k, c) = ml_kem.mencaps(&[&ek_a, &ek_b]).unwrap();
+
+    // A modified u or v, or the v of another recipient, gives a pseudorandom key
+    let mut u = c.u.clone();
+    u[0] ^= 1;
+    let rejected = ml_kem.mdecaps(&dk_a, &u, &c.v[0]).unwrap();
+    assert_ne!(rejected, k);
+    assert_eq!(ml_kem.mdecaps(&dk_a, &u, &c.v[0]).unwrap(), rejected);
+    assert_eq!(ml_kem.mdecaps(&dk_b, &u, &c.v[1]).unwrap().len(), 32);
+    let mut v = c.v[1].clone();
+    v[10] ^= 0x10;
+    assert_ne!(ml_kem.mdecaps(&dk_b, &c.u, &v).unwrap(), k);
+    assert_ne!(ml_kem.mdecaps(&dk_a, &c.u, &c.v[1]).unwrap(), k);
+
+    // Deterministic encapsulation
+    let m = [3u8; 32];
+    assert_eq!(
+        ml_kem.mencaps_internal(&[&ek_a, &ek_b], &m),
+        ml_kem.mencaps_internal(&[&ek_a, &ek_b], &m)
+    );
+}
+
+#[test]
+fn errors() {
+    let set = ParameterSet::MlKem512;
+    let ml_kem = MlKem::<KyberParams>::from_parameter_set(set);
+    let (ek_a, dk_a) = ml_kem.key_gen_with_rho(&[1u8; 32]);
+    let (ek_b, _) = ml_kem.key_gen_with_rho(&[2u8; 32]);
+
+    assert_eq!(ml_kem.mencaps(&[]).unwrap_err(), MkemError::NoRecipients);
+    assert_eq!(
+        ml_kem.mencaps(&[&ek_a, &ek_a, &ek_b]).unwrap_err(),
+        MkemError::RhoMismatch(2)
+    );
+    assert!(matches!(
+        ml_kem.mencaps(&[&ek_a[1..]]),
+        Err(MkemError::InvalidLength { .. })
+    ));
+    let mut invalid = ek_a.clone();
+    invalid[0] = 0xff;
+    invalid[1] = 0xff;
+    assert!(matches!(
+        ml_kem.mencaps(&[&invalid]),
+        Err(MkemError::Key(_))
+    ));
+
+    let (_, c) = ml_kem.mencaps(&[&ek_a]).unwrap();
+    assert!(matches!(
+        ml_kem.mdecaps(&dk_a, &c.u[1..], &c.v[0]),
+        Err(MkemError::InvalidLength { .. })
+    ));
+    let bytes = c.to_bytes();
+    for len in [set.ciphertext_u_len(), bytes.len() + 1, 0] {
+        let mut bytes = bytes.clone();
+        bytes.resize(len, 0);
+        assert!(matches!(
+            MultiCiphertext::from_bytes(set, &bytes),
+            Err(MkemError::InvalidLength { .. })
+        ));
+    }
+}
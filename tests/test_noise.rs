@@ -0,0 +1,245 @@
+use kyber_rs::kyber::kem_scheme::ParameterSet;
+use kyber_rs::noise::handshake::{HandshakeState, KeyPair, NoiseParams, TransportState};
+use kyber_rs::noise::pattern::HandshakePattern;
+use kyber_rs::noise::symmetric::{NoiseCipher, NoiseError, NoiseHash};
+
+struct Parties {
+    initiator: HandshakeState,
+    responder: HandshakeState,
+    initiator_static: KeyPair,
+    responder_static: KeyPair,
+}
+
+fn new_parties(params: NoiseParams, prologues: (&[u8], &[u8])) -> Parties {
+    let pattern = params.pattern;
+    let initiator_static = params.generate_keypair();
+    let responder_static = params.generate_keypair();
+    let known_responder =
+        (!pattern.responder_pre_message().is_empty()).then_some(responder_static.public.as_slice());
+    Parties {
+        initiator: HandshakeState::new(
+            params,
+            true,
+            prologues.0,
+            pattern
+                .needs_local_static(true)
+                .then(|| initiator_static.clone()),
+            known_responder,
+        )
+        .unwrap(),
+        responder: HandshakeState::new(
+            params,
+            false,
+            prologues.1,
+            pattern
+                .needs_local_static(false)
+                .then(|| responder_static.clone()),
+            None,
+        )
+        .unwrap(),
+        initiator_static,
+        responder_static,
+    }
+}
+
+/// Runs the handshake, passing each message through `tamper`
+fn run(
+    parties: &mut Parties,
+    mut tamper: impl FnMut(usize, &mut Vec<u8>),
+) -> Result<(), NoiseError> {
+    let mut index = 0;
+    while !parties.initiator.is_finished() {
+        let (sender, receiver) = if index % 2 == 0 {
+            (&mut parties.initiator, &mut parties.responder)
+        } else {
+            (&mut parties.responder, &mut parties.initiator)
+        };
+        let payload = vec![index as u8; index * 100];
+        let mut message = sender.write_message(&payload)?;
+        tamper(index, &mut message);
+        assert_eq!(receiver.read_message(&message)?, payload);
+        index += 1;
+    }
+    Ok(())
+}
+
+fn exchange(initiator: &mut TransportState, responder: &mut TransportState) {
+    for round in 0..3u8 {
+        let message = initiator.write_message(&[round; 1000]).unwrap();
+        assert_eq!(responder.read_message(&message).unwrap(), [round; 1000]);
+        let message = responder.write_message(&[round]).unwrap();
+        assert_eq!(initiator.read_message(&message).unwrap(), [round]);
+    }
+}
+
+#[test]
+fn end_to_end() {
+    for pattern in HandshakePattern::ALL {
+        for parameter_set in ParameterSet::ALL {
+            for (cipher, hash) in [
+                (NoiseCipher::ChaChaPoly, NoiseHash::Sha256),
+                (NoiseCipher::AesGcm, NoiseHash::Sha512),
+            ] {
+                let params = NoiseParams::new(pattern, parameter_set, cipher, hash);
+                let mut parties = new_parties(params, (b"rpc v1", b"rpc v1"));
+                run(&mut parties, |_, _| {}).unwrap();
+                assert!(parties.responder.is_finished());
+                assert_eq!(
+                    parties.initiator.handshake_hash(),
+                    parties.responder.handshake_hash()
+                );
+                assert_eq!(parties.initiator.handshake_hash().len(), hash.hash_len());
+
+                let mut initiator = parties.initiator.into_transport().unwrap();
+                let mut responder = parties.responder.into_transport().unwrap();
+                if pattern.needs_local_static(true) {
+                    assert_eq!(
+                        responder.remote_static(),
+                        Some(parties.initiator_static.public.as_slice())
+                    );
+                }
+                if pattern.needs_local_static(false) {
+                    assert_eq!(
+                        initiator.remote_static(),
+                        Some(parties.responder_static.public.as_slice())
+                    );
+                }
+                exchange(&mut initiator, &mut responder);
+
+                initiator.rekey_outgoing();
+                responder.rekey_incoming();
+                exchange(&mut initiator, &mut responder);
+            }
+        }
+    }
+}
+
+#[test]
+fn message_sizes() {
+    // pqXX with ML-KEM-768 : ek, then c || enc(ek), then enc(c) || enc(ek), then enc(c)
+    let params = NoiseParams::new(
+        HandshakePattern::PqXx,
+        ParameterSet::MlKem768,
+        NoiseCipher::ChaChaPoly,
+        NoiseHash::Sha256,
+    );
+    let mut parties = new_parties(params, (b"", b""));
+    let mut sizes = Vec::new();
+    let mut index = 0;
+    while !parties.initiator.is_finished() {
+        let (sender, receiver) = if index % 2 == 0 {
+            (&mut parties.initiator, &mut parties.responder)
+        } else {
+            (&mut parties.responder, &mut parties.initiator)
+        };
+        let message = sender.write_message(b"").unwrap();
+        receiver.read_message(&message).unwrap();
+        sizes.push(message.len());
+        index += 1;
+    }
+    assert_eq!(
+        sizes,
+        [
+            1184,
+            1088 + 1184 + 16 + 16,
+            1088 + 16 + 1184 + 16 + 16,
+            1088 + 16 + 16
+        ]
+    );
+
+    let params = NoiseParams::new(
+        HandshakePattern::NnHfs,
+        ParameterSet::MlKem768,
+        NoiseCipher::ChaChaPoly,
+        NoiseHash::Sha256,
+    );
+    let mut parties = new_parties(params, (b"", b""));
+    let first = parties.initiator.write_message(b"").unwrap();
+    assert_eq!(first.len(), 32 + 1184);
+    parties.responder.read_message(&first).unwrap();
+    let second = parties.responder.write_message(b"").unwrap();
+    assert_eq!(second.len(), 32 + 1088 + 16 + 16);
+}
+
+#[test]
+fn failures() {
+    for pattern in HandshakePattern::ALL {
+        let params = NoiseParams::new(
+            pattern,
+            ParameterSet::MlKem512,
+            NoiseCipher::ChaChaPoly,
+            NoiseHash::Sha256,
+        );
+
+        // A flipped bit in any handshake message is detected by the end of the handshake
+        for target in 0..pattern.messages().len() {
+            let mut parties = new_parties(params, (b"", b""));
+            let result = run(&mut parties, |index, message| {
+                if index == target {
+                    let last = message.len() - 1;
+                    message[last] ^= 1;
+                }
+            });
+            assert!(result.is_err(), "{} message {target}", pattern.name());
+        }
+
+        // Different prologues
+        let mut parties = new_parties(params, (b"v1", b"v2"));
+        assert_eq!(run(&mut parties, |_, _| {}), Err(NoiseError::Decrypt));
+
+        // Truncated first message
+        let mut parties = new_parties(params, (b"", b""));
+        let message = parties.initiator.write_message(b"").unwrap();
+        assert_eq!(
+            parties.responder.read_message(&message[..10]),
+            Err(NoiseError::Truncated)
+        );
+
+        // Out of turn
+        let mut parties = new_parties(params, (b"", b""));
+        assert_eq!(
+            parties.responder.write_message(b""),
+            Err(NoiseError::InvalidState)
+        );
+        assert!(parties.initiator.into_transport().is_err());
+    }
+
+    // The initiator of IK-like patterns encrypts to the wrong responder
+    for pattern in [
+        HandshakePattern::Ik,
+        HandshakePattern::PqIk,
+        HandshakePattern::IkHfs,
+    ] {
+        let params = NoiseParams::new(
+            pattern,
+            ParameterSet::MlKem768,
+            NoiseCipher::AesGcm,
+            NoiseHash::Sha256,
+        );
+        let mut parties = new_parties(params, (b"", b""));
+        let impostor = params.generate_keypair();
+        parties.responder = HandshakeState::new(params, false, b"", Some(impostor), None).unwrap();
+        assert_eq!(run(&mut parties, |_, _| {}), Err(NoiseError::Decrypt));
+    }
+
+    // Missing static keys
+    let params = NoiseParams::new(
+        HandshakePattern::PqIk,
+        ParameterSet::MlKem768,
+        NoiseCipher::ChaChaPoly,
+        NoiseHash::Sha256,
+    );
+    let s = params.generate_keypair();
+    assert_eq!(
+        HandshakeState::new(params, true, b"", Some(s.clone()), None).err(),
+        Some(NoiseError::MissingKey)
+    );
+    assert_eq!(
+        HandshakeState::new(params, false, b"", None, None).err(),
+        Some(NoiseError::MissingKey)
+    );
+    assert_eq!(
+        HandshakeState::new(params, true, b"", Some(s.clone()), Some(&s.public[1..])).err(),
+        Some(NoiseError::InvalidPublicKey)
+    );
+}
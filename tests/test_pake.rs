@@ -0,0 +1,207 @@
+use kyber_rs::constants::{KyberParams, PolyParams};
+use kyber_rs::conversion::{byte_decode, compress};
+use kyber_rs::kyber::kem_scheme::ParameterSet;
+use kyber_rs::kyber::keys::EncapsulationKey;
+use kyber_rs::pake::cipher::PasswordCipher;
+use kyber_rs::pake::exchange::{Initiator, PakeError, PakeMode, Responder, Session};
+
+const MODES: [PakeMode; 2] = [PakeMode::Cake, PakeMode::Ocake];
+
+fn session(set: ParameterSet) -> Session {
+    Session::new(set, b"session 1", b"alice@example.com", b"bob@example.com")
+}
+
+fn run(
+    mode: PakeMode,
+    initiator: (Session, &[u8]),
+    responder: (Session, &[u8]),
+) -> (Result<[u8; 32], PakeError>, [u8; 32]) {
+    let initiator = Initiator::new(mode, initiator.0, initiator.1);
+    let responder = Responder::new(mode, responder.0, responder.1);
+    let (response, key) = responder.respond(initiator.message()).unwrap();
+    (initiator.finish(&response), key)
+}
+
+#[test]
+fn matching_passwords() {
+    for mode in MODES {
+        for set in ParameterSet::ALL {
+            let (initiator_key, responder_key) =
+                run(mode, (session(set), b"hunter2"), (session(set), b"hunter2"));
+            assert_eq!(initiator_key.unwrap(), responder_key);
+        }
+    }
+}
+
+#[test]
+fn fresh_keys() {
+    for mode in MODES {
+        let set = ParameterSet::MlKem768;
+        let (first, _) = run(mode, (session(set), b"pw"), (session(set), b"pw"));
+        let (second, _) = run(mode, (session(set), b"pw"), (session(set), b"pw"));
+        assert_ne!(first.unwrap(), second.unwrap());
+    }
+}
+
+#[test]
+fn mismatching_passwords() {
+    for set in ParameterSet::ALL {
+        // CAKE : both sides end with unrelated keys
+        let (initiator_key, responder_key) = run(
+            PakeMode::Cake,
+            (session(set), b"hunter2"),
+            (session(set), b"hunter3"),
+        );
+        assert_ne!(initiator_key.unwrap(), responder_key);
+
+        // OCAKE : the initiator detects it
+        let (initiator_key, _) = run(
+            PakeMode::Ocake,
+            (session(set), b"hunter2"),
+            (session(set), b"hunter3"),
+        );
+        assert_eq!(initiator_key, Err(PakeError::ConfirmationFailed));
+    }
+}
+
+#[test]
+fn session_binding() {
+    let set = ParameterSet::MlKem768;
+    let others = [
+        Session::new(set, b"session 2", b"alice@example.com", b"bob@example.com"),
+        Session::new(
+            set,
+            b"session 1",
+            b"mallory@example.com",
+            b"bob@example.com",
+        ),
+        Session::new(
+            set,
+            b"session 1",
+            b"alice@example.com",
+            b"mallory@example.com",
+        ),
+    ];
+    for other in others {
+        let (initiator_key, responder_key) = run(
+            PakeMode::Cake,
+            (session(set), b"pw"),
+            (other.clone(), b"pw"),
+        );
+        assert_ne!(initiator_key.unwrap(), responder_key);
+
+        let (initiator_key, _) = run(PakeMode::Ocake, (session(set), b"pw"), (other, b"pw"));
+        assert_eq!(initiator_key, Err(PakeError::ConfirmationFailed));
+    }
+}
+
+#[test]
+fn tampered_response() {
+    let set = ParameterSet::MlKem512;
+    for mode in MODES {
+        let initiator = Initiator::new(mode, session(set), b"pw");
+        let responder = Responder::new(mode, session(set), b"pw");
+        let (mut response, key) = responder.respond(initiator.message()).unwrap();
+        response[0] ^= 1;
+        match mode {
+            // Implicit rejection : another key
+            PakeMode::Cake => assert_ne!(initiator.finish(&response).unwrap(), key),
+            PakeMode::Ocake => assert_eq!(
+                initiator.finish(&response),
+                Err(PakeError::ConfirmationFailed)
+            ),
+        }
+    }
+
+    // A coefficient out of Z_q is rejected, whatever the password
+    let responder = Responder::new(PakeMode::Cake, session(set), b"pw");
+    for password in [&b"pw"[..], b"other"] {
+        let initiator = Initiator::new(PakeMode::Cake, session(set), password);
+        let (mut response, _) = responder.respond(initiator.message()).unwrap();
+        response[0] = 0xff;
+        response[1] |= 0x0f;
+        assert_eq!(
+            initiator.finish(&response),
+            Err(PakeError::InvalidCiphertext)
+        );
+    }
+}
+
+#[test]
+fn invalid_lengths() {
+    let set = ParameterSet::MlKem1024;
+    for mode in MODES {
+        let initiator = Initiator::new(mode, session(set), b"pw");
+        let responder = Responder::new(mode, session(set), b"pw");
+        let message = initiator.message();
+        assert_eq!(
+            responder.respond(&message[1..]),
+            Err(PakeError::InvalidMessageLength {
+                expected: set.ek_len(),
+                found: set.ek_len() - 1,
+            })
+        );
+        let (response, _) = responder.respond(message).unwrap();
+        let response = [response.as_slice(), &[0]].concat();
+        assert_eq!(
+            initiator.finish(&response),
+            Err(PakeError::InvalidMessageLength {
+                expected: mode.responder_message_len(set),
+                found: mode.responder_message_len(set) + 1,
+            })
+        );
+    }
+}
+
+#[test]
+fn cipher_permutes_valid_keys() {
+    // Every password decrypts e_A to a valid encapsulation key, so an offline
+    // attacker cannot discard password guesses by checking the key format
+    let set = ParameterSet::MlKem768;
+    let initiator = Initiator::new(PakeMode::Cake, session(set), b"correct horse");
+    for guess in [&b"battery"[..], b"staple", b"123456", b""] {
+        let ek = PasswordCipher::new(b"session 1", guess)
+            .decrypt_ek(set, initiator.message())
+            .unwrap();
+        assert!(EncapsulationKey::from_bytes(set, &ek).is_ok());
+    }
+}
+
+#[test]
+fn ciphertext_distribution() {
+    // Compress_10 has 3 or 4 preimages mod q : in an ML-KEM ciphertext, the
+    // values with 4 preimages are more frequent than in a uniform byte string. An
+    // offline attacker decrypting the response with each password guess must not
+    // be able to rank the guesses by this frequency.
+    let set = ParameterSet::MlKem768;
+    let q = KyberParams::Q;
+    let (k, _, _, d_u, _) = set.params();
+    let mut preimages = vec![0; 1 << d_u];
+    for x in 0..q {
+        preimages[compress(x, d_u, q) as usize] += 1;
+    }
+    let frequent = |c: &[u8]| {
+        byte_decode(&c[..32 * d_u * k], d_u, q)
+            .into_iter()
+            .filter(|&y| preimages[y as usize] == 4)
+            .count()
+    };
+
+    let (mut right, mut wrong, mut total) = (0, 0, 0);
+    for _ in 0..16 {
+        let initiator = Initiator::new(PakeMode::Cake, session(set), b"482913");
+        let responder = Responder::new(PakeMode::Cake, session(set), b"482913");
+        let (response, _) = responder.respond(initiator.message()).unwrap();
+        let decrypt = |password: &[u8]| {
+            PasswordCipher::new(b"session 1", password)
+                .decrypt_ciphertext(set, &response)
+                .unwrap()
+        };
+        right += frequent(&decrypt(b"482913"));
+        wrong += frequent(&decrypt(b"000000"));
+        total += 256 * k;
+    }
+    // About 0.309 of the coefficients for both, against 0.251 for uniform bytes
+    let (right, wrong) = (right as f64 / total as f64, wrong as f64 / total as f64);
+    assert!((right - wrong).abs() < 0.03, "{right} {wrong}");
+}
@@ -0,0 +1,80 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use kyber_rs::constants::KyberParams;
+use kyber_rs::hash::h;
+use kyber_rs::kyber::pke_scheme::KPke;
+
+/// Allocator keeping the current and the peak number of heap bytes
+struct PeakAlloc {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = self.current.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        self.peak.fetch_max(current, Ordering::SeqCst);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.current.fetch_sub(layout.size(), Ordering::SeqCst);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: PeakAlloc = PeakAlloc {
+    current: AtomicUsize::new(0),
+    peak: AtomicUsize::new(0),
+};
+
+/// Peak heap bytes allocated by f on top of what is already allocated
+fn peak_heap(f: impl FnOnce()) -> usize {
+    let start = ALLOC.current.load(Ordering::SeqCst);
+    ALLOC.peak.store(start, Ordering::SeqCst);
+    f();
+    ALLOC.peak.load(Ordering::SeqCst) - start
+}
+
+/// Heap bound of the streaming functions
+const MAX_HEAP: usize = 4096;
+/// Stack of the thread running them : unoptimized builds need about twice as much
+const STACK_SIZE: usize = if cfg!(debug_assertions) {
+    32 * 1024
+} else {
+    16 * 1024
+};
+
+#[test]
+fn peak_memory() {
+    for (k, eta_1, eta_2, d_u, d_v) in [(2, 3, 2, 10, 4), (3, 2, 2, 10, 4), (4, 2, 2, 11, 5)] {
+        let pke_scheme = KPke::<KyberParams>::new(k, eta_1, eta_2, d_u, d_v);
+        let d = h(b"streaming key generation");
+        let r = h(b"streaming encryption");
+        let mut ek = vec![0u8; pke_scheme.ek_len()];
+        let mut dk = vec![0u8; pke_scheme.dk_len()];
+        let mut c = vec![0u8; pke_scheme.ciphertext_len()];
+
+        // Overflowing the stack of the thread aborts the test
+        let (key_gen, encrypt) = std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .stack_size(STACK_SIZE)
+                .spawn_scoped(scope, || {
+                    let key_gen = peak_heap(|| pke_scheme.key_gen_streaming(&d, &mut ek, &mut dk));
+                    let encrypt =
+                        peak_heap(|| pke_scheme.encrypt_streaming(&ek, &[7; 32], &r, &mut c));
+                    (key_gen, encrypt)
+                })
+                .unwrap()
+                .join()
+                .unwrap()
+        });
+        assert!(key_gen <= MAX_HEAP, "key_gen_streaming : {key_gen} bytes");
+        assert!(encrypt <= MAX_HEAP, "encrypt_streaming : {encrypt} bytes");
+
+        let (ek_full, _) = pke_scheme.key_gen(&d);
+        assert_eq!(ek, ek_full);
+    }
+}
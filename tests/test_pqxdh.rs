@@ -0,0 +1,139 @@
+use kyber_rs::hybrid::ecdh::{Curve, EcdhError, EcdhKeyPair};
+use kyber_rs::pqxdh::{
+    InitialMessage, KEM_PARAMETER_SET, PqxdhError, PrekeySignature, PrekeyStore, initiate,
+};
+use sha2::{Digest, Sha256};
+
+const INFO: &[u8] = b"Messaging_CURVE25519_SHA-512_ML-KEM-1024";
+
+/// Keyless stand-in for XEdDSA, only good enough to exercise the checks
+struct HashSignature;
+
+impl PrekeySignature for HashSignature {
+    fn sign(&self, identity: &EcdhKeyPair, message: &[u8]) -> Vec<u8> {
+        Sha256::digest([identity.public_bytes(), message].concat()).to_vec()
+    }
+
+    fn verify(&self, identity: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        Sha256::digest([identity, message].concat()).as_slice() == signature
+    }
+}
+
+fn bob(kem_prekeys: usize, one_time_prekeys: usize) -> PrekeyStore {
+    PrekeyStore::generate(
+        EcdhKeyPair::generate(Curve::X25519),
+        &HashSignature,
+        kem_prekeys,
+        one_time_prekeys,
+    )
+    .unwrap()
+}
+
+#[test]
+fn end_to_end() {
+    let alice = EcdhKeyPair::generate(Curve::X25519);
+    let mut bob = bob(2, 1);
+
+    // One-time ML-KEM and curve prekeys, then one-time ML-KEM prekey only, then the
+    // last-resort prekey, which can be used several times
+    let mut kem_ids = Vec::new();
+    for round in 0..4 {
+        let bundle = bob.bundle();
+        assert_eq!(bundle.one_time_prekey.is_some(), round == 0);
+        let (message, alice_keys) = initiate(INFO, &alice, &bundle, &HashSignature).unwrap();
+        let bytes = message.to_bytes();
+        let expected_len = 64 + 9 + KEM_PARAMETER_SET.ciphertext_len();
+        assert_eq!(bytes.len(), expected_len + if round == 0 { 4 } else { 0 });
+        let message = InitialMessage::from_bytes(&bytes).unwrap();
+
+        let bob_keys = bob.respond(INFO, &message).unwrap();
+        assert_eq!(alice_keys, bob_keys);
+        assert_eq!(&bob_keys.ad[1..33], alice.public_bytes());
+        assert_eq!(&bob_keys.ad[34..], bob.identity_key());
+        kem_ids.push(message.kem_prekey_id);
+    }
+    assert_ne!(kem_ids[0], kem_ids[1]);
+    assert_ne!(kem_ids[1], kem_ids[2]);
+    assert_eq!(kem_ids[2], kem_ids[3]);
+
+    // The info string separates applications
+    let (message, alice_keys) = initiate(INFO, &alice, &bob.bundle(), &HashSignature).unwrap();
+    let bob_keys = bob.respond(b"Other_CURVE25519_SHA-512", &message).unwrap();
+    assert_ne!(alice_keys.sk, bob_keys.sk);
+}
+
+#[test]
+fn one_time_prekeys_are_deleted() {
+    let alice = EcdhKeyPair::generate(Curve::X25519);
+    let mut bob = bob(1, 1);
+    let (message, _) = initiate(INFO, &alice, &bob.bundle(), &HashSignature).unwrap();
+    bob.respond(INFO, &message).unwrap();
+    assert_eq!(
+        bob.respond(INFO, &message),
+        Err(PqxdhError::UnknownPrekey(message.kem_prekey_id))
+    );
+
+    bob.add_kem_prekeys(&HashSignature, 1);
+    let (mut message, _) = initiate(INFO, &alice, &bob.bundle(), &HashSignature).unwrap();
+    message.one_time_prekey_id = Some(message.kem_prekey_id - 1);
+    assert!(matches!(
+        bob.respond(INFO, &message),
+        Err(PqxdhError::UnknownPrekey(_))
+    ));
+}
+
+#[test]
+fn tampering() {
+    let alice = EcdhKeyPair::generate(Curve::X25519);
+    let mut bob = bob(3, 0);
+
+    // Bundles with a substituted prekey fail the signature checks
+    let mallory = bob.bundle();
+    let mut bundle = bob.bundle();
+    bundle.kem_prekey.ek = mallory.kem_prekey.ek.clone();
+    assert_eq!(
+        initiate(INFO, &alice, &bundle, &HashSignature).unwrap_err(),
+        PqxdhError::InvalidSignature
+    );
+    let mut bundle = bob.bundle();
+    bundle.signed_prekey.public = EcdhKeyPair::generate(Curve::X25519).public_bytes().to_vec();
+    assert_eq!(
+        initiate(INFO, &alice, &bundle, &HashSignature).unwrap_err(),
+        PqxdhError::InvalidSignature
+    );
+
+    // A modified ciphertext gives a different key (implicit rejection)
+    let (mut message, alice_keys) = initiate(INFO, &alice, &bob.bundle(), &HashSignature).unwrap();
+    message.ciphertext[0] ^= 1;
+    assert_ne!(bob.respond(INFO, &message).unwrap().sk, alice_keys.sk);
+
+    // Malformed messages and keys
+    let (message, _) = initiate(INFO, &alice, &bob.bundle(), &HashSignature).unwrap();
+    let bytes = message.to_bytes();
+    assert!(matches!(
+        InitialMessage::from_bytes(&bytes[1..]),
+        Err(PqxdhError::InvalidMessageLength { .. })
+    ));
+    let mut bad_flag = bytes.clone();
+    bad_flag[72] = 0xff;
+    assert_eq!(
+        InitialMessage::from_bytes(&bad_flag),
+        Err(PqxdhError::InvalidPrekeyFlag(0xff))
+    );
+    let mut low_order = message.clone();
+    low_order.ephemeral_key = vec![0u8; 32];
+    assert_eq!(
+        bob.respond(INFO, &low_order),
+        Err(PqxdhError::Ecdh(EcdhError::InvalidPublicKey))
+    );
+    assert_eq!(
+        initiate(
+            INFO,
+            &EcdhKeyPair::generate(Curve::P256),
+            &bob.bundle(),
+            &HashSignature
+        )
+        .unwrap_err(),
+        PqxdhError::Ecdh(EcdhError::InvalidSecret)
+    );
+}